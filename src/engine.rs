@@ -1,9 +1,15 @@
 use std::collections::{HashMap, HashSet, VecDeque};
 use std::fmt::{Formatter, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
 
 use crate::format::intersperse;
 use crate::interpreter::{Interpreter, InterpreterError};
-use crate::parser::{Mets, Statement};
+use crate::parser::{Deadlocks, Location, Mets, Positions, Statement};
+use crate::reduction::Reduction;
+use crate::reporter::Format;
+use crate::scanner::Position;
 use crate::sql_interpreter::SqlDatabase;
 use crate::state::{HashableState, ProcessState, RcState, State, TransactionInfo};
 
@@ -22,19 +28,22 @@ pub struct Transaction(pub TransactionState);
 #[derive(Hash, Eq, PartialEq, PartialOrd, Ord, Debug, Clone)]
 pub enum Value {
     Nil,
+    Null,
     Tx(Transaction),
     Bool(bool),
-    Integer(i16),
+    Integer(i64),
     String(String),
     Set(Vec<Value>),
     Tuple(Vec<Value>),
     Scalar(Box<Value>),
+    Error(String),
 }
 
 impl std::fmt::Display for Value {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
             Value::Nil => f.write_str("nil"),
+            Value::Null => f.write_str("null"),
             Value::Bool(x) => {
                 if *x {
                     f.write_str("true")
@@ -65,6 +74,7 @@ impl std::fmt::Display for Value {
                 std::fmt::Display::fmt(&v, f)?;
                 f.write_char(')')
             }
+            Value::Error(e) => f.write_fmt(format_args!("{e} error")),
         }
     }
 }
@@ -79,136 +89,635 @@ pub enum Violation {
         cycle: HashSet<usize>,
         state: RcState,
     },
+    Lasso {
+        property: Statement,
+        state: RcState,
+        cycle: Vec<(usize, RcState)>,
+    },
+}
+
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub enum UniqueViolationPolicy {
+    #[default]
+    Abort,
+    Error,
+}
+
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct Options {
+    pub unique_violation: UniqueViolationPolicy,
+    pub max_states: Option<usize>,
+    pub max_depth: Option<usize>,
+    pub timeout: Option<Duration>,
+    pub format: Format,
+    pub verbose: bool,
+    pub interactive: bool,
+    pub symmetry: bool,
+    pub partial_order: bool,
+    pub jobs: usize,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum Bound {
+    MaxStates(usize),
+    MaxDepth(usize),
+    Timeout(Duration),
+}
+
+impl std::fmt::Display for Bound {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Bound::MaxStates(max) => f.write_fmt(format_args!("max states of {max} reached")),
+            Bound::MaxDepth(max) => f.write_fmt(format_args!("max depth of {max} reached")),
+            Bound::Timeout(timeout) => f.write_fmt(format_args!(
+                "timeout of {}s reached",
+                timeout.as_secs_f64()
+            )),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum Completeness {
+    Complete,
+    Incomplete { reason: Bound },
+}
+
+pub struct Graph {
+    pub states: Vec<RcState>,
+    pub edges: Vec<Vec<(usize, usize)>>,
 }
 
 pub struct Report {
     pub states_explored: usize,
     pub violation: Option<Violation>,
+    pub completeness: Completeness,
+    pub graph: Option<Graph>,
+    pub duration: Duration,
+    // Distinct states kept in memory to detect already explored states
+    pub visited_states: usize,
 }
 
-#[derive(Debug)]
+impl Report {
+    pub fn states_per_second(&self) -> f64 {
+        self.states_explored as f64 / self.duration.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+#[derive(Debug, Clone)]
 pub enum CheckerError {
-    InterpreterError(InterpreterError),
+    InterpreterError(InterpreterError, Option<Box<Position>>),
+    // Path and source of the included file raising the error
+    Included(String, String, Box<CheckerError>),
 }
 
 impl From<InterpreterError> for CheckerError {
     fn from(value: InterpreterError) -> Self {
-        CheckerError::InterpreterError(value)
+        CheckerError::InterpreterError(value, None)
+    }
+}
+
+fn located<'a>(
+    positions: &'a Positions,
+    location: &'a Location,
+) -> impl Fn(InterpreterError) -> CheckerError + 'a {
+    |err| {
+        let error = CheckerError::InterpreterError(err, Some(Box::new(location.position.clone())));
+        match location.file {
+            Some(file) => {
+                let (path, source) = positions.files[file].clone();
+                CheckerError::Included(path, source, Box::new(error))
+            }
+            None => error,
+        }
     }
 }
 
 type Res<T> = Result<T, CheckerError>;
 
+#[derive(PartialEq)]
 pub enum PropertyCheck {
     Always(bool),
     Eventually(bool),
+    LeadsTo(bool, bool),
 }
 
-pub fn model_checker(mets: &Mets) -> Res<Report> {
-    let init_state = init_state(mets)?;
+struct Expansion {
+    explored: bool,
+    bound: Option<Bound>,
+    violation: Option<Violation>,
+    successors: Vec<(usize, RcState)>,
+}
 
-    let mut deq = VecDeque::from([(1, RcState::new(init_state))]);
-    let mut visited: HashMap<HashableState, RcState> = HashMap::new();
+impl Expansion {
+    fn violation(explored: bool, violation: Violation) -> Self {
+        Expansion {
+            explored,
+            bound: None,
+            violation: Some(violation),
+            successors: vec![],
+        }
+    }
+}
 
-    let mut current_depth = 0;
-    let mut states_explored = 0;
+struct Explorer<'a> {
+    mets: &'a Mets,
+    options: &'a Options,
+    constants: HashMap<String, Value>,
+    symmetric_groups: Vec<Vec<usize>>,
+    reduction: Option<Reduction>,
+}
 
-    while let Some((depth, state)) = deq.pop_front() {
-        if depth != current_depth {
-            println!(
-                "exploring depth {}, states so far: {}",
-                depth, states_explored
-            );
-            current_depth += 1;
+impl Explorer<'_> {
+    fn expand_all(
+        &self,
+        visited: &HashMap<HashableState, usize>,
+        depth: usize,
+        states: &[(usize, RcState)],
+    ) -> Vec<Res<Expansion>> {
+        if self.options.jobs <= 1 {
+            return states
+                .iter()
+                .map(|(index, state)| self.expand(visited, depth, *index, state))
+                .collect();
         }
 
-        let hashed_state = state.borrow().hash();
-        if let Some(existing_state) = visited.get_mut(&hashed_state) {
-            let mut st = existing_state.borrow_mut();
-            st.ancestors.extend_from_slice(&state.borrow().ancestors);
-            continue;
-        }
-        visited.insert(hashed_state, state.clone());
+        let next = AtomicUsize::new(0);
+        let mut expansions: Vec<_> = thread::scope(|scope| {
+            let workers: Vec<_> = (0..self.options.jobs)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut expansions = vec![];
+                        loop {
+                            let i = next.fetch_add(1, Ordering::Relaxed);
+                            let Some((index, state)) = states.get(i) else {
+                                break;
+                            };
+                            expansions.push((i, self.expand(visited, depth, *index, state)));
+                        }
+                        expansions
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().unwrap())
+                .collect()
+        });
+        expansions.sort_by_key(|(i, _)| *i);
+        expansions
+            .into_iter()
+            .map(|(_, expansion)| expansion)
+            .collect()
+    }
 
-        let mut interpreter = Interpreter::new(state.clone());
+    fn expand(
+        &self,
+        visited: &HashMap<HashableState, usize>,
+        depth: usize,
+        index: usize,
+        state: &RcState,
+    ) -> Res<Expansion> {
+        let (mets, options, constants) = (self.mets, self.options, &self.constants);
+        let mut interpreter = Interpreter::new(state.clone(), constants, options);
 
         for (id, property) in mets.properties.iter().enumerate() {
-            let res = interpreter.check_property(property)?;
-            match res {
+            let res = interpreter
+                .check_property(property)
+                .map_err(located(&mets.positions, &mets.positions.properties[id]))?;
+            match &res {
                 PropertyCheck::Always(false) => {
-                    return Ok(Report {
-                        states_explored,
-                        violation: Some(Violation::PropertyViolation {
+                    return Ok(Expansion::violation(
+                        false,
+                        Violation::PropertyViolation {
                             property: property.clone(),
-                            state,
-                        }),
-                    });
+                            state: state.clone(),
+                        },
+                    ));
                 }
                 PropertyCheck::Eventually(res) => {
                     let mut state = state.borrow_mut();
                     let existing = state.eventually.entry(id).or_insert(false);
-                    if !*existing && res {
-                        *existing = res;
+                    if !*existing && *res {
+                        *existing = *res;
+                    }
+                }
+                PropertyCheck::LeadsTo(cause, consequence) => {
+                    let mut state = state.borrow_mut();
+                    let fulfilled = state.eventually.entry(id).or_insert(true);
+                    if *consequence {
+                        *fulfilled = true;
+                    } else if *cause {
+                        *fulfilled = false;
                     }
                 }
                 _ => {}
             }
         }
 
-        states_explored += 1;
+        if let Some(max) = options.max_depth.filter(|max| depth > *max) {
+            if state.borrow().processes.contains(&ProcessState::Running) {
+                return Ok(Expansion {
+                    explored: true,
+                    bound: Some(Bound::MaxDepth(max)),
+                    violation: None,
+                    successors: vec![],
+                });
+            }
+        }
 
-        let mut is_final = true;
+        let mut successors = vec![];
         for (idx, code) in mets.processes.iter().enumerate() {
             if state.borrow().processes[idx] == ProcessState::Running {
                 interpreter.idx = idx;
-                let offset = interpreter.statement(&code[state.borrow().pc[idx]])?;
-                let mut new_state = interpreter.next_state();
+                let pc = state.borrow().pc[idx];
+                let offsets = match &code[pc] {
+                    Statement::Either(offset) => vec![1, offset.get() as isize],
+                    statement => vec![interpreter
+                        .statement(statement)
+                        .map_err(located(&mets.positions, &mets.positions.processes[idx][pc]))?],
+                };
+                for offset in offsets {
+                    let mut new_state = interpreter.next_state();
 
-                new_state.pc[idx] += offset;
-                new_state.ancestors = vec![state.clone()];
-                if new_state.pc[idx] == code.len() {
-                    new_state.processes[idx] = ProcessState::Finished
-                }
+                    new_state.eventually = state.borrow().eventually.clone();
+                    new_state.pc[idx] = new_state.pc[idx].saturating_add_signed(offset);
+                    new_state.ancestors = vec![state.clone()];
+                    new_state.transition = Some((idx, pc));
+                    if new_state.pc[idx] == code.len() {
+                        new_state.processes[idx] = ProcessState::Finished
+                    }
 
-                if let Some(deadlock_cycle) = new_state.find_deadlocks() {
-                    return Ok(Report {
-                        states_explored,
-                        violation: Some(Violation::Deadlock {
-                            cycle: deadlock_cycle,
-                            state: RcState::new(new_state),
-                        }),
-                    });
-                }
+                    new_state.unlock_locks();
+                    if let Some(deadlock_cycle) = new_state
+                        .find_deadlocks()
+                        .filter(|_| mets.deadlocks != Deadlocks::Expected)
+                    {
+                        return Ok(Expansion::violation(
+                            true,
+                            Violation::Deadlock {
+                                cycle: deadlock_cycle,
+                                state: RcState::new(new_state),
+                            },
+                        ));
+                    }
+                    new_state.release_latches();
+
+                    let new_state = RcState::new(new_state);
+                    new_state.release_awaits(|i, expr| {
+                        let mut interpreter =
+                            Interpreter::new(new_state.clone(), constants, options);
+                        interpreter.idx = i;
+                        interpreter
+                            .evaluate(expr)
+                            .map(|value| value == Value::Bool(true))
+                    })?;
 
-                new_state.unlock_locks();
-                new_state.release_latches();
+                    successors.push((idx, new_state));
+                }
+            }
+        }
 
-                deq.push_back((depth + 1, RcState::new(new_state)));
-                is_final = false;
+        if let Some(reduction) = &self.reduction {
+            for idx in 0..mets.processes.len() {
+                let steps: Vec<_> = successors.iter().filter(|(i, _)| *i == idx).collect();
+                if steps.is_empty()
+                    || steps.len() == successors.len()
+                    || !reduction.is_independent(idx, state.borrow().pc[idx])
+                {
+                    continue;
+                }
+                let visible = reduction.is_visible(idx, state.borrow().pc[idx]);
+                let mut ample = true;
+                for (_, new_state) in &steps {
+                    let hashed = new_state.borrow().hash().symmetric(&self.symmetric_groups);
+                    // A step changing a property or reaching a visited state, possibly
+                    // closing a cycle, needs the interleavings of the other processes
+                    let invisible =
+                        !visible && new_state.borrow().txs[idx] == state.borrow().txs[idx];
+                    if !invisible || visited.get(&hashed).is_some_and(|i| *i <= index) {
+                        ample = false;
+                        break;
+                    }
+                }
+                if ample {
+                    successors.retain(|(i, _)| *i == idx);
+                    break;
+                }
             }
         }
 
-        if is_final {
+        if successors.is_empty() {
             if let Some((id, _)) = state.borrow().eventually.iter().find(|(_, b)| !**b) {
-                return Ok(Report {
-                    states_explored,
-                    violation: Some(Violation::PropertyViolation {
+                return Ok(Expansion::violation(
+                    true,
+                    Violation::PropertyViolation {
                         property: mets.properties[*id].clone(),
                         state: state.clone(),
-                    }),
-                });
+                    },
+                ));
             };
         };
+
+        Ok(Expansion {
+            explored: true,
+            bound: None,
+            violation: None,
+            successors,
+        })
     }
+}
+
+pub fn model_checker(
+    mets: &Mets,
+    options: &Options,
+    progress: &mut dyn FnMut(usize, usize),
+) -> Res<Report> {
+    let constants = constants(mets, options)?;
+    let init_state = init_state(mets, &constants, options)?;
+    let explorer = Explorer {
+        mets,
+        options,
+        constants,
+        symmetric_groups: if options.symmetry {
+            symmetric_groups(mets)
+        } else {
+            vec![]
+        },
+        reduction: options.partial_order.then(|| Reduction::new(mets)),
+    };
+
+    let mut frontier = vec![(RcState::new(init_state), None::<(usize, usize)>)];
+    let mut visited: HashMap<HashableState, usize> = HashMap::new();
+    let mut states: Vec<RcState> = vec![];
+    let mut edges: Vec<Vec<(usize, usize)>> = vec![];
+
+    let mut depth = 1;
+    let mut states_explored = 0;
+    let mut completeness = Completeness::Complete;
+    let start = Instant::now();
+
+    let violation = 'search: {
+        while !frontier.is_empty() {
+            progress(depth, states_explored);
+
+            // States of a depth are expanded together, their expansions are then merged in
+            // order so the report does not depend on the number of jobs
+            let mut bound = None;
+            let mut entries = vec![];
+            let mut expanded = vec![];
+            for (state, edge) in frontier {
+                if let Some(max) = options
+                    .max_states
+                    .filter(|max| states_explored + expanded.len() >= *max)
+                {
+                    bound = Some(Bound::MaxStates(max));
+                    break;
+                }
+                if let Some(timeout) = options.timeout.filter(|t| start.elapsed() >= *t) {
+                    bound = Some(Bound::Timeout(timeout));
+                    break;
+                }
+
+                let hashed_state = state.borrow().hash().symmetric(&explorer.symmetric_groups);
+                if let Some(&existing) = visited.get(&hashed_state) {
+                    entries.push((state, edge, existing, false));
+                    continue;
+                }
+                let index = states.len();
+                visited.insert(hashed_state, index);
+                states.push(state.clone());
+                edges.push(vec![]);
+                expanded.push((index, state.clone()));
+                entries.push((state, edge, index, true));
+            }
+
+            let mut expansions = explorer.expand_all(&visited, depth, &expanded).into_iter();
+            let mut next = vec![];
+            for (state, edge, index, new) in entries {
+                if let Some((parent, process)) = edge {
+                    edges[parent].push((process, index));
+                }
+                if !new {
+                    let mut st = states[index].borrow_mut();
+                    st.ancestors.extend_from_slice(&state.borrow().ancestors);
+                    continue;
+                }
+
+                let expansion = expansions.next().unwrap()?;
+                if expansion.explored {
+                    states_explored += 1;
+                }
+                if let Some(violation) = expansion.violation {
+                    states.truncate(index + 1);
+                    edges.truncate(index + 1);
+                    break 'search Some(violation);
+                }
+                if let Some(reason) = expansion.bound {
+                    completeness = Completeness::Incomplete { reason };
+                }
+                next.extend(
+                    expansion
+                        .successors
+                        .into_iter()
+                        .map(|(process, successor)| (successor, Some((index, process)))),
+                );
+            }
+
+            if let Some(reason) = bound {
+                completeness = Completeness::Incomplete { reason };
+                break;
+            }
+            frontier = next;
+            depth += 1;
+        }
+
+        find_lasso(mets, &states, &edges)
+    };
 
     Ok(Report {
+        duration: start.elapsed(),
+        visited_states: visited.len(),
         states_explored,
-        violation: None,
+        completeness: if violation.is_some() {
+            Completeness::Complete
+        } else {
+            completeness
+        },
+        violation,
+        graph: (options.format == Format::Dot).then_some(Graph { states, edges }),
     })
 }
 
-fn init_state(mets: &Mets) -> Res<State> {
-    let init_state = State {
+fn find_lasso(mets: &Mets, states: &[RcState], edges: &[Vec<(usize, usize)>]) -> Option<Violation> {
+    for (id, property) in mets.properties.iter().enumerate() {
+        if !matches!(
+            property,
+            Statement::Eventually(_) | Statement::LeadsTo(_, _)
+        ) {
+            continue;
+        }
+
+        let unsatisfied: Vec<bool> = states
+            .iter()
+            .map(|s| s.borrow().eventually.get(&id) == Some(&false))
+            .collect();
+
+        for component in strongly_connected_components(&unsatisfied, edges) {
+            let members: HashSet<usize> = component.iter().copied().collect();
+            let internal: Vec<(usize, usize, usize)> = component
+                .iter()
+                .flat_map(|&from| {
+                    edges[from]
+                        .iter()
+                        .filter(|(_, to)| members.contains(to))
+                        .map(move |&(process, to)| (from, process, to))
+                })
+                .collect();
+            if internal.is_empty() {
+                continue;
+            }
+
+            let fair = mets
+                .fair_processes
+                .iter()
+                .enumerate()
+                .filter(|(_, fair)| **fair)
+                .all(|(process, _)| {
+                    internal.iter().any(|(_, p, _)| *p == process)
+                        || component.iter().any(|&s| {
+                            states[s].borrow().processes[process] != ProcessState::Running
+                        })
+                });
+            if !fair {
+                continue;
+            }
+
+            let entry = *component.iter().min().unwrap();
+            return Some(Violation::Lasso {
+                property: property.clone(),
+                state: states[entry].clone(),
+                cycle: cycle_from(entry, &internal)
+                    .into_iter()
+                    .map(|(process, from)| (process, states[from].clone()))
+                    .collect(),
+            });
+        }
+    }
+    None
+}
+
+fn strongly_connected_components(
+    included: &[bool],
+    edges: &[Vec<(usize, usize)>],
+) -> Vec<Vec<usize>> {
+    let mut counter = 0;
+    let mut index: Vec<Option<usize>> = vec![None; included.len()];
+    let mut low = vec![0; included.len()];
+    let mut on_stack = vec![false; included.len()];
+    let mut stack = vec![];
+    let mut components = vec![];
+
+    for root in (0..included.len()).filter(|&r| included[r]) {
+        if index[root].is_some() {
+            continue;
+        }
+        let mut work = vec![(root, 0)];
+        while let Some((v, child)) = work.pop() {
+            if child == 0 {
+                index[v] = Some(counter);
+                low[v] = counter;
+                counter += 1;
+                stack.push(v);
+                on_stack[v] = true;
+            }
+            if let Some(&(_, w)) = edges[v].get(child) {
+                work.push((v, child + 1));
+                if !included[w] {
+                    continue;
+                }
+                match index[w] {
+                    None => work.push((w, 0)),
+                    Some(i) if on_stack[w] => low[v] = low[v].min(i),
+                    _ => {}
+                }
+            } else {
+                if Some(low[v]) == index[v] {
+                    let mut component = vec![];
+                    while let Some(w) = stack.pop() {
+                        on_stack[w] = false;
+                        component.push(w);
+                        if w == v {
+                            break;
+                        }
+                    }
+                    components.push(component);
+                }
+                if let Some(&(u, _)) = work.last() {
+                    low[u] = low[u].min(low[v]);
+                }
+            }
+        }
+    }
+    components
+}
+
+fn cycle_from(entry: usize, internal: &[(usize, usize, usize)]) -> Vec<(usize, usize)> {
+    let mut previous: HashMap<usize, (usize, usize)> = HashMap::new();
+    let mut deq = VecDeque::from([entry]);
+    while let Some(from) = deq.pop_front() {
+        for &(_, process, to) in internal.iter().filter(|(f, _, _)| *f == from) {
+            if to == entry {
+                let mut cycle = vec![(process, from)];
+                let mut current = from;
+                while current != entry {
+                    let (p, before) = previous[&current];
+                    cycle.push((p, before));
+                    current = before;
+                }
+                cycle.reverse();
+                return cycle;
+            }
+            if let std::collections::hash_map::Entry::Vacant(e) = previous.entry(to) {
+                e.insert((process, from));
+                deq.push_back(to);
+            }
+        }
+    }
+    vec![]
+}
+
+fn symmetric_groups(mets: &Mets) -> Vec<Vec<usize>> {
+    let mut groups: Vec<Vec<usize>> = vec![];
+    for idx in 0..mets.processes.len() {
+        let group = groups.iter_mut().find(|group| {
+            mets.processes[group[0]] == mets.processes[idx]
+                && mets.fair_processes[group[0]] == mets.fair_processes[idx]
+        });
+        match group {
+            Some(group) => group.push(idx),
+            None => groups.push(vec![idx]),
+        }
+    }
+    groups.retain(|group| group.len() > 1);
+    groups
+}
+
+fn constants(mets: &Mets, options: &Options) -> Res<HashMap<String, Value>> {
+    let mut constants = HashMap::new();
+    for ((name, expr), position) in mets.constants.iter().zip(&mets.positions.constants) {
+        let mut interpreter =
+            Interpreter::new(RcState::new(empty_state(mets)), &constants, options);
+        let value = interpreter
+            .evaluate(expr)
+            .map_err(located(&mets.positions, position))?;
+        constants.insert(name.name.clone(), value);
+    }
+    Ok(constants)
+}
+
+fn empty_state(mets: &Mets) -> State {
+    State {
         pc: mets.processes.iter().map(|_| 0).collect(),
         processes: mets
             .processes
@@ -222,16 +731,31 @@ fn init_state(mets: &Mets) -> Res<State> {
                 id: None,
                 name: None,
                 state: TransactionState::NotExisting,
+                failed: false,
             })
             .collect(),
         sql: SqlDatabase::new(),
-        locals: HashMap::new(),
+        globals: HashMap::new(),
+        locals: mets.processes.iter().map(|_| HashMap::new()).collect(),
         ancestors: vec![],
         eventually: HashMap::new(),
-    };
-    let mut interpreter = Interpreter::new(RcState::new(init_state));
-    for statement in &mets.init {
-        interpreter.statement(statement)?;
+        transition: None,
+        result: None,
+    }
+}
+
+fn init_state(mets: &Mets, constants: &HashMap<String, Value>, options: &Options) -> Res<State> {
+    let mut state = empty_state(mets);
+    state.locals = vec![HashMap::new()];
+    let mut interpreter = Interpreter::new(RcState::new(state), constants, options);
+    let positions = mets.positions.globals.iter().chain(&mets.positions.init);
+    for (statement, position) in mets.globals.iter().chain(&mets.init).zip(positions) {
+        interpreter
+            .statement(statement)
+            .map_err(located(&mets.positions, position))?;
     }
-    Ok(interpreter.next_state())
+    let mut state = interpreter.next_state();
+    state.globals = state.locals.remove(0);
+    state.locals = empty_state(mets).locals;
+    Ok(state)
 }
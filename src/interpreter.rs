@@ -1,13 +1,22 @@
-use crate::engine::{PropertyCheck, Transaction, TransactionState, Value};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use crate::engine::{
+    Options, PropertyCheck, Transaction, TransactionState, UniqueViolationPolicy, Value,
+};
 use crate::interpreter::InterpreterError::{TypeError, Unexpected};
-use crate::parser::{Expression, Operator, SqlExpression, Statement};
-use crate::sql_interpreter::{SqlEngineError, TransactionId};
+use crate::parser::{
+    Expression, InsertSource, OnConflict, Operator, SqlExpression, Statement, Variable,
+};
+use crate::sql_interpreter::{scalar, unquoted, SqlEngineError, TransactionId};
 use crate::state::{ProcessState, RcState, State};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum InterpreterError {
     Unexpected(String),
-    TypeError(Expression, Value, String),
+    TypeError(Box<Expression>, Value, String),
+    ArithmeticOverflow(Box<Expression>),
+    DivisionByZero(Box<Expression>),
     SqlEngineError(SqlEngineError),
 }
 
@@ -19,25 +28,48 @@ impl From<SqlEngineError> for InterpreterError {
 
 type Res<T> = Result<T, InterpreterError>;
 
-pub struct Interpreter {
+fn successor(state: &RcState) -> State {
+    let mut next = state.borrow().clone();
+    next.result = None;
+    next
+}
+
+pub struct Interpreter<'a> {
     pub idx: usize,
     checking: bool,
+    constants: &'a HashMap<String, Value>,
+    options: &'a Options,
     state: RcState,
     next_state: State,
+    bindings: HashMap<String, Value>,
 }
 
-impl Interpreter {
-    pub fn new(state: RcState) -> Self {
+impl<'a> Interpreter<'a> {
+    pub fn new(
+        state: RcState,
+        constants: &'a HashMap<String, Value>,
+        options: &'a Options,
+    ) -> Self {
         Interpreter {
             idx: 0,
             checking: false,
+            constants,
+            options,
             state: state.clone(),
-            next_state: state.borrow().clone(),
+            next_state: successor(&state),
+            bindings: HashMap::new(),
         }
     }
 
     pub fn next_state(&mut self) -> State {
-        std::mem::replace(&mut self.next_state, self.state.borrow().clone())
+        std::mem::replace(&mut self.next_state, successor(&self.state))
+    }
+
+    pub fn evaluate(&mut self, expr: &Expression) -> Res<Value> {
+        self.checking = true;
+        let res = self.interpret(expr);
+        self.checking = false;
+        res
     }
 
     pub fn check_property(&mut self, property: &Statement) -> Res<PropertyCheck> {
@@ -55,6 +87,14 @@ impl Interpreter {
                 let value = self.interpret(never)?;
                 Ok(PropertyCheck::Always(value == Value::Bool(false)))
             }
+            Statement::LeadsTo(expr, consequent) => {
+                let value = self.interpret(expr)?;
+                let consequence = self.interpret(consequent)?;
+                Ok(PropertyCheck::LeadsTo(
+                    value == Value::Bool(true),
+                    consequence == Value::Bool(true),
+                ))
+            }
             _ => Err(Unexpected(format!("unsupported property: {property:?}"))),
         };
 
@@ -62,10 +102,26 @@ impl Interpreter {
         res
     }
 
-    pub fn statement(&mut self, statement: &Statement) -> Res<usize> {
+    pub fn statement(&mut self, statement: &Statement) -> Res<isize> {
         match self.priv_statement(statement) {
-            Err(InterpreterError::SqlEngineError(SqlEngineError::UnicityViolation)) => Ok(1),
-            Err(InterpreterError::SqlEngineError(SqlEngineError::ForeignKeyViolation)) => Ok(1),
+            Err(InterpreterError::SqlEngineError(
+                SqlEngineError::UnicityViolation
+                | SqlEngineError::CheckViolation(_)
+                | SqlEngineError::ForeignKeyViolation
+                | SqlEngineError::LockNotAvailable(_)
+                | SqlEngineError::ReadOnlyTransaction,
+            )) => {
+                if self
+                    .next_state
+                    .txs
+                    .get(self.idx)
+                    .is_some_and(|tx| tx.state == TransactionState::Running)
+                {
+                    self.abort_transaction();
+                    self.next_state.txs[self.idx].failed = true;
+                }
+                Ok(1)
+            }
             Err(InterpreterError::SqlEngineError(SqlEngineError::Locked(lock))) => {
                 self.next_state.processes[self.idx] = ProcessState::Locked(lock);
                 Ok(0)
@@ -74,16 +130,29 @@ impl Interpreter {
         }
     }
 
-    fn priv_statement(&mut self, statement: &Statement) -> Res<usize> {
+    fn priv_statement(&mut self, statement: &Statement) -> Res<isize> {
+        if self
+            .next_state
+            .txs
+            .get(self.idx)
+            .is_some_and(|tx| tx.failed)
+        {
+            // The rest of the transaction block is skipped, control flow included
+            if let Statement::Commit | Statement::Abort = statement {
+                self.next_state.txs[self.idx].failed = false;
+            }
+            return Ok(1);
+        }
+
         match statement {
-            Statement::Begin(isolation, tx_name) => {
+            Statement::Begin(isolation, tx_name, read_only) => {
                 self.next_state.txs[self.idx].name = tx_name.as_ref().map(|v| v.name.clone());
-                let id = self.next_state.sql.open_transaction(*isolation);
+                let id = self.next_state.sql.open_transaction(*isolation, *read_only);
                 self.next_state.txs[self.idx].id = Some(id);
                 self.next_state.txs[self.idx].state = TransactionState::Running;
 
                 if let Some(tx) = tx_name {
-                    self.next_state.locals.insert(
+                    self.assign(
                         tx.name.clone(),
                         Value::Tx(Transaction(TransactionState::Running)),
                     );
@@ -91,13 +160,21 @@ impl Interpreter {
             }
             Statement::Commit => {
                 if self.next_state.txs[self.idx].state == TransactionState::Running {
-                    self.next_state
+                    match self
+                        .next_state
                         .sql
-                        .commit(&self.next_state.txs[self.idx].id.unwrap());
+                        .commit(&self.next_state.txs[self.idx].id.unwrap())
+                    {
+                        Err(SqlEngineError::SerializationFailure) => {
+                            self.abort_transaction();
+                            return Ok(1);
+                        }
+                        res => res?,
+                    }
                     self.next_state.txs.get_mut(self.idx).unwrap().id = None;
 
                     if let Some(tx) = &self.next_state.txs[self.idx].name {
-                        self.next_state.locals.insert(
+                        self.assign(
                             tx.clone(),
                             Value::Tx(Transaction(TransactionState::Committed)),
                         );
@@ -105,19 +182,26 @@ impl Interpreter {
                     self.next_state.txs[self.idx].state = TransactionState::Committed;
                 }
             }
-            Statement::Abort => {
-                self.next_state
-                    .sql
-                    .abort(&self.next_state.txs[self.idx].id.unwrap());
-                self.next_state.txs[self.idx].id = None;
-
-                if let Some(tx) = &&self.next_state.txs[self.idx].name {
-                    self.next_state.locals.insert(
-                        tx.clone(),
-                        Value::Tx(Transaction(TransactionState::Aborted)),
-                    );
+            Statement::Abort => self.abort_transaction(),
+            Statement::Savepoint(name) => {
+                let id = self.savepoint_transaction(statement)?;
+                self.next_state.sql.savepoint(&id, &name.name);
+            }
+            Statement::RollbackTo(name) => {
+                let id = self.savepoint_transaction(statement)?;
+                self.next_state.sql.rollback_to(&id, &name.name)?;
+            }
+            Statement::Expression(Expression::Assignment(variable, expr)) => {
+                let value = self.interpret(expr)?;
+                if let Expression::Sql(_) = expr.as_ref() {
+                    self.next_state.result = Some(value.clone());
                 }
-                self.next_state.txs[self.idx].state = TransactionState::Aborted;
+                self.assign(variable.name.clone(), value);
+            }
+            // The rows of a select run for its locks are not used as a value
+            Statement::Expression(Expression::Sql(sql_expr)) => {
+                let value = self.sql(sql_expr)?;
+                self.next_state.result = Some(value);
             }
             Statement::Expression(expr) => {
                 self.interpret(expr)?;
@@ -125,30 +209,73 @@ impl Interpreter {
             Statement::Latch => {
                 self.next_state.processes[self.idx] = ProcessState::Latching;
             }
+            Statement::Await(expr) => {
+                if !self.assert_bool(expr)? {
+                    self.next_state.processes[self.idx] = ProcessState::Awaiting(expr.clone());
+                    return Ok(0);
+                }
+            }
             Statement::If(expr, offset) => {
                 let cond = self.assert_bool(expr)?;
                 if !cond {
-                    return Ok(offset.get());
+                    return Ok(offset.get() as isize);
                 }
             }
+            Statement::Either(_) => {}
+            Statement::Or(offset) => {
+                return Ok(offset.get() as isize);
+            }
             Statement::Else(offset) => {
-                return Ok(offset.get());
+                return Ok(offset.get() as isize);
+            }
+            Statement::While(expr, offset) => {
+                if !self.assert_bool(expr)? {
+                    return Ok(offset.get() as isize);
+                }
+            }
+            // Jumps back to the start of the loop body while the condition holds
+            Statement::EndWhile(expr, offset) => {
+                if self.assert_bool(expr)? {
+                    return Ok(-(offset.get() as isize));
+                }
             }
             _ => panic!("Unexpected statement in process: {statement:?}"),
         };
         Ok(1)
     }
 
+    fn savepoint_transaction(&self, statement: &Statement) -> Res<TransactionId> {
+        self.next_state.txs[self.idx]
+            .id
+            .ok_or_else(|| Unexpected(format!("{statement} outside of a transaction")))
+    }
+
+    fn abort_transaction(&mut self) {
+        self.next_state
+            .sql
+            .abort(&self.next_state.txs[self.idx].id.unwrap());
+        self.next_state.txs[self.idx].id = None;
+
+        if let Some(tx) = &self.next_state.txs[self.idx].name {
+            self.assign(
+                tx.clone(),
+                Value::Tx(Transaction(TransactionState::Aborted)),
+            );
+        }
+        self.next_state.txs[self.idx].state = TransactionState::Aborted;
+    }
+
     fn interpret(&mut self, expression: &Expression) -> Res<Value> {
         match expression {
-            Expression::Sql(sql_expr) => {
-                let reified = self.reify_up_variable(sql_expr)?;
-                Ok(self.next_state.sql.execute(&reified, self.running_tx())?)
-            }
+            Expression::Sql(sql_expr) => match self.sql(sql_expr)? {
+                // A query returns a single value, set(...) returns all its rows
+                Value::Set(rows) if is_query(sql_expr) => Ok(scalar(rows)?),
+                value => Ok(value),
+            },
             Expression::Assignment(variable, expr) => {
                 let value = self.interpret(expr)?;
                 let name = variable.name.clone();
-                self.next_state.locals.insert(name, value);
+                self.assign(name, value);
                 Ok(Value::Nil)
             }
             Expression::Binary {
@@ -157,11 +284,7 @@ impl Interpreter {
                 right,
             } => self.interpret_binary(left, operator, right),
             Expression::Var(variable) => Ok(self
-                .state
-                .borrow()
-                .locals
-                .get(&variable.name)
-                .cloned()
+                .lookup(&variable.name)
                 .unwrap_or(Value::Tx(Transaction(TransactionState::NotExisting)))),
             Expression::Integer(i) => Ok(Value::Integer(*i)),
             Expression::Set(members) => {
@@ -178,6 +301,10 @@ impl Interpreter {
                 }
                 Ok(Value::Tuple(res))
             }
+            Expression::Member { call_site, member } if member.name == "failed" => {
+                let value = self.interpret(call_site)?;
+                Ok(Value::Bool(matches!(value, Value::Error(_))))
+            }
             Expression::Member { call_site, member } => {
                 let target = self.assert_transaction(call_site)?;
                 match target.0 {
@@ -188,8 +315,53 @@ impl Interpreter {
                 }
             }
             Expression::String(s) => Ok(Value::String(s.clone())),
+            Expression::Null => Ok(Value::Null),
             Expression::Scalar(expr) => Ok(Value::Scalar(Box::new(self.interpret(expr)?))),
+            Expression::ToSet(expr) => match expr.as_ref() {
+                Expression::Sql(sql_expr) if is_query(sql_expr) => self.sql(sql_expr),
+                expr => match self.interpret(expr)? {
+                    Value::Set(members) => Ok(Value::Set(members)),
+                    value => Ok(Value::Set(vec![value])),
+                },
+            },
+            Expression::Forall(variable, domain, body) => {
+                self.quantify(variable, domain, body, true)
+            }
+            Expression::Exists(variable, domain, body) => {
+                self.quantify(variable, domain, body, false)
+            }
+        }
+    }
+
+    fn quantify(
+        &mut self,
+        variable: &Variable,
+        domain: &Expression,
+        body: &Expression,
+        forall: bool,
+    ) -> Res<Value> {
+        let members = self.assert_set(domain)?;
+        let shadowed = self.bindings.remove(&variable.name);
+        let mut res = Ok(Value::Bool(forall));
+        for member in members {
+            self.bindings.insert(variable.name.clone(), member);
+            match self.assert_bool(body) {
+                Ok(holds) if holds != forall => {
+                    res = Ok(Value::Bool(holds));
+                    break;
+                }
+                Ok(_) => {}
+                Err(err) => {
+                    res = Err(err);
+                    break;
+                }
+            }
         }
+        self.bindings.remove(&variable.name);
+        if let Some(value) = shadowed {
+            self.bindings.insert(variable.name.clone(), value);
+        }
+        res
     }
 
     fn assert_transaction(&mut self, expr: &Expression) -> Res<Transaction> {
@@ -198,14 +370,14 @@ impl Interpreter {
             Ok(value)
         } else {
             Err(TypeError(
-                expr.clone(),
+                Box::new(expr.clone()),
                 value.clone(),
                 "transaction".to_string(),
             ))
         }
     }
 
-    fn assert_integer(&mut self, expr: &Expression) -> Res<i16> {
+    fn assert_integer(&mut self, expr: &Expression) -> Res<i64> {
         let value = self.interpret(expr)?;
         if let Value::Integer(value) = value {
             Ok(value)
@@ -213,10 +385,51 @@ impl Interpreter {
             if let Value::Integer(i) = *(*boxed) {
                 Ok(i)
             } else {
-                Err(TypeError(expr.clone(), value, "integer".to_string()))
+                Err(TypeError(
+                    Box::new(expr.clone()),
+                    value,
+                    "integer".to_string(),
+                ))
             }
         } else {
-            Err(TypeError(expr.clone(), value, "integer".to_string()))
+            Err(TypeError(
+                Box::new(expr.clone()),
+                value,
+                "integer".to_string(),
+            ))
+        }
+    }
+
+    // Integers compare numerically and strings lexicographically
+    fn assert_ordering(&mut self, left: &Expression, right: &Expression) -> Res<Ordering> {
+        let unscalar = |value| match value {
+            Value::Scalar(boxed) => *boxed,
+            value => value,
+        };
+        let l = unscalar(self.interpret(left)?);
+        let r = unscalar(self.interpret(right)?);
+        match (&l, &r) {
+            (Value::Integer(a), Value::Integer(b)) => Ok(a.cmp(b)),
+            (Value::String(a), Value::String(b)) => Ok(unquoted(a).cmp(unquoted(b))),
+            (Value::String(_), _) => {
+                Err(TypeError(Box::new(right.clone()), r, "string".to_string()))
+            }
+            (Value::Integer(_), _) => {
+                Err(TypeError(Box::new(right.clone()), r, "integer".to_string()))
+            }
+            _ => Err(TypeError(Box::new(left.clone()), l, "integer".to_string())),
+        }
+    }
+
+    fn sql(&mut self, sql_expr: &SqlExpression) -> Res<Value> {
+        let reified = self.reify_up_variable(sql_expr)?;
+        match self.next_state.sql.execute(&reified, self.running_tx()) {
+            Err(SqlEngineError::UnicityViolation)
+                if self.options.unique_violation == UniqueViolationPolicy::Error =>
+            {
+                Ok(Value::Error("unicity violation".to_string()))
+            }
+            res => Ok(res?),
         }
     }
 
@@ -225,7 +438,7 @@ impl Interpreter {
         if let Value::Set(value) = value {
             Ok(value)
         } else {
-            Err(TypeError(expr.clone(), value, "set".to_string()))
+            Err(TypeError(Box::new(expr.clone()), value, "set".to_string()))
         }
     }
 
@@ -237,10 +450,10 @@ impl Interpreter {
             if let Value::Bool(b) = *(*boxed) {
                 Ok(b)
             } else {
-                Err(TypeError(expr.clone(), value, "bool".to_string()))
+                Err(TypeError(Box::new(expr.clone()), value, "bool".to_string()))
             }
         } else {
-            Err(TypeError(expr.clone(), value, "bool".to_string()))
+            Err(TypeError(Box::new(expr.clone()), value, "bool".to_string()))
         }
     }
 
@@ -251,45 +464,47 @@ impl Interpreter {
         right: &Expression,
     ) -> Res<Value> {
         match operator {
-            Operator::Add => {
-                let left = self.assert_integer(left)?;
-                let right = self.assert_integer(right)?;
-                Ok(Value::Integer(left + right))
-            }
-            Operator::Subtract => {
-                let left = self.assert_integer(left)?;
-                let right = self.assert_integer(right)?;
-                Ok(Value::Integer(left - right))
-            }
-            Operator::Multiply => {
-                let left = self.assert_integer(left)?;
-                let right = self.assert_integer(right)?;
-                Ok(Value::Integer(left * right))
-            }
-            Operator::Divide => {
-                let left = self.assert_integer(left)?;
-                let right = self.assert_integer(right)?;
-                Ok(Value::Integer(left / right))
-            }
-            Operator::Rem => {
+            Operator::Add
+            | Operator::Subtract
+            | Operator::Multiply
+            | Operator::Divide
+            | Operator::Rem => {
+                let expression = || {
+                    Box::new(Expression::Binary {
+                        left: Box::new(left.clone()),
+                        operator: operator.clone(),
+                        right: Box::new(right.clone()),
+                    })
+                };
                 let left = self.assert_integer(left)?;
                 let right = self.assert_integer(right)?;
-                Ok(Value::Integer(left % right))
+                let result = match operator {
+                    Operator::Add => left.checked_add(right),
+                    Operator::Subtract => left.checked_sub(right),
+                    Operator::Multiply => left.checked_mul(right),
+                    Operator::Divide | Operator::Rem if right == 0 => {
+                        return Err(InterpreterError::DivisionByZero(expression()));
+                    }
+                    Operator::Divide => left.checked_div(right),
+                    _ => left.checked_rem(right),
+                };
+                result
+                    .map(Value::Integer)
+                    .ok_or_else(|| InterpreterError::ArithmeticOverflow(expression()))
             }
             Operator::Equal => {
                 let left = self.interpret(left)?;
                 let right = self.interpret(right)?;
                 Ok(Value::Bool(left == right))
             }
-            Operator::LessEqual => {
-                let left = self.assert_integer(left)?;
-                let right = self.assert_integer(right)?;
-                Ok(Value::Bool(left <= right))
-            }
-            Operator::Less => {
-                let left = self.assert_integer(left)?;
-                let right = self.assert_integer(right)?;
-                Ok(Value::Bool(left < right))
+            Operator::Less | Operator::LessEqual | Operator::Greater | Operator::GreaterEqual => {
+                let ordering = self.assert_ordering(left, right)?;
+                Ok(Value::Bool(match operator {
+                    Operator::Less => ordering.is_lt(),
+                    Operator::LessEqual => ordering.is_le(),
+                    Operator::Greater => ordering.is_gt(),
+                    _ => ordering.is_ge(),
+                }))
             }
             Operator::Included => {
                 let left = self.interpret(left)?;
@@ -306,16 +521,6 @@ impl Interpreter {
                 let right = self.assert_bool(right)?;
                 Ok(Value::Bool(left || right))
             }
-            Operator::Greater => {
-                let left = self.assert_integer(left)?;
-                let right = self.assert_integer(right)?;
-                Ok(Value::Bool(left > right))
-            }
-            Operator::GreaterEqual => {
-                let left = self.assert_integer(left)?;
-                let right = self.assert_integer(right)?;
-                Ok(Value::Bool(left >= right))
-            }
             Operator::NotEqual => {
                 let left = self.interpret(left)?;
                 let right = self.interpret(right)?;
@@ -328,8 +533,13 @@ impl Interpreter {
             SqlExpression::Select {
                 columns,
                 from,
+                join_table,
+                join_condition,
                 condition,
+                group_by,
+                having,
                 order_by,
+                descending,
                 limit,
                 offset,
                 locking,
@@ -339,10 +549,25 @@ impl Interpreter {
                 } else {
                     None
                 };
+                let join_condition = if let Some(cond) = join_condition {
+                    Some(Box::new(self.reify_up_variable(cond)?))
+                } else {
+                    None
+                };
+                let having = if let Some(having) = having {
+                    Some(Box::new(self.reify_up_variable(having)?))
+                } else {
+                    None
+                };
                 Ok(SqlExpression::Select {
                     columns: columns.clone(),
                     from: from.clone(),
+                    join_table: join_table.clone(),
+                    join_condition,
+                    group_by: group_by.clone(),
+                    having,
                     order_by: order_by.clone(),
+                    descending: *descending,
                     limit: *limit,
                     offset: *offset,
                     condition,
@@ -353,6 +578,7 @@ impl Interpreter {
                 relation,
                 updates,
                 condition,
+                returning,
             } => {
                 let condition = if let Some(cond) = condition {
                     Some(Box::new(self.reify_up_variable(cond)?))
@@ -367,21 +593,45 @@ impl Interpreter {
                     relation: relation.clone(),
                     updates: res,
                     condition,
+                    returning: returning.clone(),
                 })
             }
             SqlExpression::Insert {
                 relation,
                 columns,
-                values,
+                source,
+                on_conflict,
             } => {
-                let mut res = vec![];
-                for value in values {
-                    res.push(self.reify_up_variable(value)?);
-                }
+                let on_conflict = match on_conflict {
+                    Some(OnConflict::Update { columns, updates }) => {
+                        let mut res = vec![];
+                        for update in updates {
+                            res.push(self.reify_up_variable(update)?);
+                        }
+                        Some(OnConflict::Update {
+                            columns: columns.clone(),
+                            updates: res,
+                        })
+                    }
+                    on_conflict => on_conflict.clone(),
+                };
+                let source = match source {
+                    InsertSource::Values(values) => {
+                        let mut res = vec![];
+                        for value in values {
+                            res.push(self.reify_up_variable(value)?);
+                        }
+                        InsertSource::Values(res)
+                    }
+                    InsertSource::Select(select) => {
+                        InsertSource::Select(Box::new(self.reify_up_variable(select)?))
+                    }
+                };
                 Ok(SqlExpression::Insert {
                     relation: relation.clone(),
                     columns: columns.clone(),
-                    values: res,
+                    source,
+                    on_conflict,
                 })
             }
             SqlExpression::Binary {
@@ -404,18 +654,50 @@ impl Interpreter {
                 var.clone(),
                 Box::new(self.reify_up_variable(expr)?),
             )),
+            SqlExpression::Not(expr) => {
+                Ok(SqlExpression::Not(Box::new(self.reify_up_variable(expr)?)))
+            }
+            SqlExpression::Subquery(select) => Ok(SqlExpression::Subquery(Box::new(
+                self.reify_up_variable(select)?,
+            ))),
             SqlExpression::UpVariable(variable) => Ok(SqlExpression::Value(
-                self.state
-                    .borrow()
-                    .locals
-                    .get(&variable.name)
-                    .cloned()
-                    .unwrap_or(Value::Nil),
+                self.lookup(&variable.name).unwrap_or(Value::Nil),
             )),
             expr => Ok(expr.clone()),
         }
     }
 
+    fn assign(&mut self, name: String, value: Value) {
+        if let Some(global) = self.next_state.globals.get_mut(&name) {
+            *global = value;
+        } else {
+            self.next_state.locals[self.idx].insert(name, value);
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Option<Value> {
+        if let Some(value) = self.bindings.get(name) {
+            Some(value.clone())
+        } else if let Some(value) = self.constants.get(name) {
+            Some(value.clone())
+        } else {
+            let state = self.state.borrow();
+            state
+                .globals
+                .get(name)
+                .or_else(|| state.locals.get(self.idx)?.get(name))
+                .or_else(|| {
+                    // A process only reads its own locals, properties read those of every process
+                    if self.checking {
+                        state.locals.iter().find_map(|locals| locals.get(name))
+                    } else {
+                        None
+                    }
+                })
+                .cloned()
+        }
+    }
+
     fn running_tx(&self) -> Option<TransactionId> {
         if self.checking {
             None
@@ -428,3 +710,10 @@ impl Interpreter {
         }
     }
 }
+
+fn is_query(sql_expr: &SqlExpression) -> bool {
+    matches!(
+        sql_expr,
+        SqlExpression::Select { .. } | SqlExpression::Union { .. }
+    )
+}
@@ -0,0 +1,305 @@
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::engine::{model_checker, UniqueViolationPolicy};
+use crate::interpreter::InterpreterError;
+use crate::parser::{Parser, ParserErrorKind};
+use crate::sql_interpreter::SqlEngineError;
+use crate::validation::ValidationError;
+
+pub use crate::engine::{Bound, CheckerError, Completeness, Options, Report, Violation};
+pub use crate::parser::{Mets, ParserError};
+pub use crate::reporter::{diagnostic, dot, history, interactive, json, summary, Format};
+pub use crate::scanner::Position;
+pub use crate::tla::tla;
+
+mod engine;
+mod format;
+mod interpreter;
+mod parser;
+mod reduction;
+mod reporter;
+mod scanner;
+mod sql_interpreter;
+mod state;
+mod tla;
+mod validation;
+
+#[derive(Debug)]
+pub enum CheckError {
+    Parser(Box<ParserError>),
+    Checker(CheckerError),
+    Validation(ValidationError),
+}
+
+impl CheckError {
+    pub fn message(&self, file: &str) -> String {
+        match self {
+            CheckError::Checker(CheckerError::InterpreterError(err, position)) => {
+                let message = match err {
+                    InterpreterError::Unexpected(expr) => format!("Unexpected: {expr}"),
+                    InterpreterError::TypeError(x, y, z) => {
+                        format!("Expected '{x}' to be a {z}, was {y} ")
+                    }
+                    InterpreterError::ArithmeticOverflow(expr) => {
+                        format!("Arithmetic overflow in {expr}")
+                    }
+                    InterpreterError::DivisionByZero(expr) => format!("Division by zero in {expr}"),
+                    InterpreterError::SqlEngineError(SqlEngineError::ArithmeticOverflow(expr)) => {
+                        format!("Arithmetic overflow in {expr}")
+                    }
+                    InterpreterError::SqlEngineError(SqlEngineError::DivisionByZero(expr)) => {
+                        format!("Division by zero in {expr}")
+                    }
+                    InterpreterError::SqlEngineError(SqlEngineError::NotNullViolation(column)) => {
+                        format!("Null value in primary key column {column}")
+                    }
+                    InterpreterError::SqlEngineError(w) => format!("Sql Engine Error: {w:?}"),
+                };
+                match position {
+                    Some(position) => format!(
+                        "Error at {file}:{}:{}: {message}",
+                        position.start_line, position.start_col
+                    ),
+                    None => message,
+                }
+            }
+            CheckError::Parser(message) => {
+                let line = message.current.position.start_line;
+                let col = message.current.position.start_col;
+                let lexeme = &message.current.lexeme;
+                match &message.kind {
+                    ParserErrorKind::ParseInt(err) => format!(
+                        "Error at {file}:{line}:{col}: Could not parse integer from lexeme {lexeme:?}, integers range from {} to {}: {err:?}",
+                        i64::MIN,
+                        i64::MAX
+                    ),
+                    ParserErrorKind::Scanner(err) => format!(
+                        "Error at {file}:{line}:{col}: Could not parse token {lexeme:?}: {err:?}"
+                    ),
+                    ParserErrorKind::Unexpected(err) => {
+                        format!("Error at {file}:{line}:{col}: Unexpected token {lexeme:?}: {err}")
+                    }
+                    ParserErrorKind::AggregateError(item) => {
+                        format!("Error at {file}:{line}:{col}: Column {item} must appear in group by")
+                    }
+                    ParserErrorKind::UnknownTable(table) => {
+                        format!("Error at {file}:{line}:{col}: Unknown table {table}")
+                    }
+                    ParserErrorKind::UnknownColumn(table, column) => format!(
+                        "Error at {file}:{line}:{col}: Unknown column {column} in table {table}"
+                    ),
+                    ParserErrorKind::AmbiguousColumn(column) => {
+                        format!("Error at {file}:{line}:{col}: Ambiguous column {column}")
+                    }
+                    ParserErrorKind::Include(included, _, error) => {
+                        CheckError::Parser(error.clone()).message(included)
+                    }
+                }
+            }
+            CheckError::Checker(CheckerError::Included(included, _, error)) => {
+                CheckError::Checker(*error.clone()).message(included)
+            }
+            CheckError::Validation(ValidationError::UndeclaredVariable(name, position)) => format!(
+                "Error at {file}:{}:{}: Undeclared variable {name}",
+                position.start_line, position.start_col
+            ),
+            CheckError::Validation(ValidationError::Included(included, _, error)) => {
+                CheckError::Validation(*error.clone()).message(included)
+            }
+        }
+    }
+
+    pub fn report(&self, file: &str, source: &str) -> String {
+        match self {
+            CheckError::Parser(error) => {
+                if let ParserErrorKind::Include(included, source, error) = &error.kind {
+                    return CheckError::Parser(error.clone()).report(included, source);
+                }
+                let position = match &error.kind {
+                    ParserErrorKind::Scanner(err) => &err.position,
+                    _ => &error.current.position,
+                };
+                format!("{}\n{}", self.message(file), diagnostic(source, position))
+            }
+            CheckError::Checker(CheckerError::Included(included, source, error)) => {
+                CheckError::Checker(*error.clone()).report(included, source)
+            }
+            CheckError::Checker(CheckerError::InterpreterError(_, Some(position))) => {
+                format!("{}\n{}", self.message(file), diagnostic(source, position))
+            }
+            CheckError::Checker(_) => self.message(file),
+            CheckError::Validation(ValidationError::UndeclaredVariable(_, position)) => {
+                format!("{}\n{}", self.message(file), diagnostic(source, position))
+            }
+            CheckError::Validation(ValidationError::Included(included, source, error)) => {
+                CheckError::Validation(*error.clone()).report(included, source)
+            }
+        }
+    }
+}
+
+pub fn compile(source: &str) -> Result<Mets, CheckError> {
+    Parser::new(source.to_string())
+        .compile()
+        .map_err(CheckError::Parser)
+}
+
+pub fn compile_file(source: &str, file: &str) -> Result<Mets, CheckError> {
+    Parser::with_path(source.to_string(), Path::new(file))
+        .compile()
+        .map_err(CheckError::Parser)
+}
+
+pub fn check(mets: &Mets, options: &Options) -> Result<Report, CheckError> {
+    check_with_progress(mets, options, |_, _| {})
+}
+
+// The progress is called with the depth about to be explored and the number of states explored
+pub fn check_with_progress(
+    mets: &Mets,
+    options: &Options,
+    mut progress: impl FnMut(usize, usize),
+) -> Result<Report, CheckError> {
+    model_checker(mets, options, &mut progress).map_err(CheckError::Checker)
+}
+
+pub fn validate(mets: &Mets) -> Result<(), CheckError> {
+    validation::validate(mets).map_err(CheckError::Validation)
+}
+
+pub fn check_model(source: &str) -> Result<Report, CheckError> {
+    let mets = compile(source)?;
+    validate(&mets)?;
+    check(&mets, &Options::default())
+}
+
+pub fn render(mets: &Mets, report: &Report, options: &Options) -> String {
+    match options.format {
+        Format::Text => summary(mets, report, options),
+        Format::Json => json(mets, report),
+        Format::Dot => dot(report),
+        Format::History => history(mets, report),
+    }
+}
+
+fn parse_arg<T: FromStr>(flag: &str, value: Option<String>) -> Result<T, String> {
+    value
+        .as_deref()
+        .and_then(|v| v.parse().ok())
+        .ok_or_else(|| format!("Expected a positive number after {flag}"))
+}
+
+pub fn parse_args(
+    args: impl IntoIterator<Item = String>,
+) -> Result<(Option<String>, Options), String> {
+    let mut file = None;
+    let mut options = Options::default();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--unique-violation" => match args.next().as_deref() {
+                Some("abort") => options.unique_violation = UniqueViolationPolicy::Abort,
+                Some("error") => options.unique_violation = UniqueViolationPolicy::Error,
+                _ => return Err("Expected abort or error after --unique-violation".to_string()),
+            },
+            "--format" => match args.next().as_deref() {
+                Some("text") => options.format = Format::Text,
+                Some("json") => options.format = Format::Json,
+                Some("dot") => options.format = Format::Dot,
+                Some("history") => options.format = Format::History,
+                _ => return Err("Expected text, json, dot or history after --format".to_string()),
+            },
+            "--verbose" => options.verbose = true,
+            "--interactive" => options.interactive = true,
+            "--symmetry" => options.symmetry = true,
+            "--partial-order" => options.partial_order = true,
+            "--max-states" => options.max_states = Some(parse_arg(&arg, args.next())?),
+            "--max-depth" => options.max_depth = Some(parse_arg(&arg, args.next())?),
+            "--jobs" => options.jobs = parse_arg(&arg, args.next())?,
+            "--timeout" => {
+                let timeout = Duration::try_from_secs_f64(parse_arg(&arg, args.next())?)
+                    .map_err(|err| format!("Invalid timeout: {err}"))?;
+                options.timeout = Some(timeout);
+            }
+            flag if flag.starts_with("--") => return Err(format!("Unknown option {flag}")),
+            _ if file.is_some() => return Err(format!("Unexpected argument {arg}")),
+            _ => file = Some(arg),
+        }
+    }
+    Ok((file, options))
+}
+
+pub const USAGE: &str = "Usage: entremets [check] [<file>] [options]
+
+Checks the model in <file>, ./model.mets by default, - reads the model from stdin.
+
+Options:
+    --format <text|json|dot|history>  output format of the report
+    --verbose                         print the tables after each step of the trace
+    --interactive                     step through the counter example
+    --unique-violation <abort|error>  behavior of a statement violating a unique index
+    --max-states <n>                  stop after exploring n states
+    --max-depth <n>                   do not explore steps past n statements
+    --timeout <seconds>               stop after the given duration
+    --jobs <n>                        explore states with n threads
+    --symmetry                        merge states differing by a permutation of processes
+    --partial-order                   skip interleavings of independent steps
+    --emit tla                        print a TLA+ specification of the model instead of checking it
+    --check-only                      validate the model without checking it
+    -h, --help                        print this message";
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Check(Option<String>, Options),
+    Emit(Option<String>, Emit),
+    Validate(Option<String>),
+    Help,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Emit {
+    Tla,
+}
+
+pub fn parse_command(args: impl IntoIterator<Item = String>) -> Result<Command, String> {
+    let mut args = args.into_iter().peekable();
+    match args.peek().map(String::as_str) {
+        Some("help") => return Ok(Command::Help),
+        Some("check") => {
+            args.next();
+        }
+        _ => {}
+    }
+    let mut args: Vec<_> = args.collect();
+    if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        return Ok(Command::Help);
+    }
+    let emit = match args.iter().position(|arg| arg == "--emit") {
+        Some(index) => match args
+            .drain(index..(index + 2).min(args.len()))
+            .nth(1)
+            .as_deref()
+        {
+            Some("tla") => Some(Emit::Tla),
+            _ => return Err("Expected tla after --emit".to_string()),
+        },
+        None => None,
+    };
+    let check_only = match args.iter().position(|arg| arg == "--check-only") {
+        Some(index) => {
+            args.remove(index);
+            true
+        }
+        None => false,
+    };
+    let (file, options) = parse_args(args)?;
+    if check_only {
+        return Ok(Command::Validate(file));
+    }
+    match emit {
+        Some(emit) => Ok(Command::Emit(file, emit)),
+        None => Ok(Command::Check(file, options)),
+    }
+}
@@ -1,64 +1,98 @@
-use crate::engine::{model_checker, CheckerError};
-use crate::interpreter::InterpreterError;
-use crate::parser::{Parser, ParserErrorKind};
-use crate::reporter::summary;
+use entremets::{
+    check_with_progress, compile_file, interactive, parse_command, render, tla, validate, Command,
+    Emit, USAGE,
+};
 use std::env;
 use std::fs::read_to_string;
+use std::io::{stdin, stdout, Read};
+use std::path::Path;
+use std::process::ExitCode;
 
-mod engine;
-mod format;
-mod interpreter;
-mod parser;
-mod reporter;
-mod scanner;
-mod sql_interpreter;
-mod state;
+fn main() -> ExitCode {
+    let (file, options, emit, check_only) = match parse_command(env::args().skip(1)) {
+        Ok(Command::Check(file, options)) => (file, options, None, false),
+        Ok(Command::Emit(file, emit)) => (file, Default::default(), Some(emit), false),
+        Ok(Command::Validate(file)) => (file, Default::default(), None, true),
+        Ok(Command::Help) => {
+            println!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Err(message) => {
+            println!("{message}\n\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+    let file = file.unwrap_or_else(|| "./model.mets".to_string());
+    let (file, source) = match read_source(file) {
+        Ok(read) => read,
+        Err(message) => {
+            println!("{message}");
+            return ExitCode::from(2);
+        }
+    };
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
-    let default_file = "./model.mets".to_string();
-    let file = args.get(1).unwrap_or(&default_file);
-    let source = read_to_string(file).unwrap_or_else(|_| format!("Could not open {file}"));
-    let parser = Parser::new(source);
+    if let Some(Emit::Tla) = emit {
+        return match compile_file(&source, &file) {
+            Ok(mets) => {
+                let name = Path::new(&file).file_stem().and_then(|stem| stem.to_str());
+                println!("{}", tla(&mets, name.unwrap_or("model")));
+                ExitCode::SUCCESS
+            }
+            Err(err) => {
+                println!("{}", err.report(&file, &source));
+                ExitCode::from(2)
+            }
+        };
+    }
 
-    let res = parser.compile();
+    let res = compile_file(&source, &file).and_then(|mets| {
+        validate(&mets)?;
+        if check_only {
+            return Ok(None);
+        }
+        let report = check_with_progress(&mets, &options, |depth, states| {
+            eprintln!("exploring depth {depth}, states so far: {states}");
+        })?;
+        Ok(Some((report, mets)))
+    });
 
     match res {
-        Ok(mets) => match model_checker(&mets) {
-            Ok(report) => println!("{}", summary(&mets, &report)),
-            Err(err) => match err {
-                CheckerError::InterpreterError(err) => match err {
-                    InterpreterError::Unexpected(expr) => println!("Unexpected: {expr}"),
-                    InterpreterError::TypeError(x, y, z) => {
-                        println!("Expected '{x}' to be a {z}, was {y} ")
-                    }
-                    InterpreterError::SqlEngineError(w) => println!("Sql Engine Error: {w:?}"),
-                },
-            },
-        },
-        Err(message) => match message.kind {
-            ParserErrorKind::ParseInt(err) => println!(
-                "Error at {file}:{}:{}: Could not parse integer from lexeme {:?}: {err:?}",
-                message.current.position.start_line,
-                message.current.position.start_col,
-                message.current.lexeme
-            ),
-            ParserErrorKind::Scanner(err) => println!(
-                "Error at {file}:{}:{}: Could not parse token {:?}: {err:?}",
-                message.current.position.start_line,
-                message.current.position.start_col,
-                message.current.lexeme
-            ),
-            ParserErrorKind::Unexpected(err) => println!(
-                "Error at {file}:{}:{}: Unexpected token {:?}: {err}",
-                message.current.position.start_line,
-                message.current.position.start_col,
-                message.current.lexeme
-            ),
-            ParserErrorKind::AggregateError(item) => println!(
-                "Error at {file}:{}:{}: Column {item} must appear in group by",
-                message.current.position.start_line, message.current.position.start_col
-            ),
-        },
+        Ok(None) => {
+            println!("No error found in {file}");
+            ExitCode::SUCCESS
+        }
+        Ok(Some((report, mets))) => {
+            println!("{}", render(&mets, &report, &options));
+            if options.interactive {
+                if let Err(err) = interactive(&mets, &report, stdin().lock(), &mut stdout()) {
+                    println!("Could not run the interactive mode: {err}");
+                }
+            }
+            if report.violation.is_some() {
+                ExitCode::from(1)
+            } else {
+                ExitCode::SUCCESS
+            }
+        }
+        Err(err) => {
+            println!("{}", err.report(&file, &source));
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn read_source(file: String) -> Result<(String, String), String> {
+    if file != "-" {
+        let source =
+            read_to_string(&file).map_err(|err| format!("Could not open {file}: {err}"))?;
+        return Ok((file, source));
+    }
+    let mut source = String::new();
+    stdin()
+        .read_to_string(&mut source)
+        .map_err(|err| format!("Could not read stdin: {err}"))?;
+    if source.trim().is_empty() {
+        return Err("No model read from stdin".to_string());
     }
+    Ok(("<stdin>".to_string(), source))
 }
@@ -1,45 +1,133 @@
-use std::cell::Cell;
+use std::collections::{HashMap, HashSet};
 use std::fmt::Formatter;
 use std::fmt::{Debug, Write};
 use std::mem;
 use std::num::ParseIntError;
-use std::rc::Rc;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 
 use crate::engine::Value;
 use crate::format::intersperse;
-use crate::scanner::{Scanner, ScannerError, Token, TokenKind};
+use crate::scanner::{Position, Scanner, ScannerError, Token, TokenKind};
+
+// Offsets of if and either blocks are backpatched once their body is parsed
+#[derive(Debug, Clone, Default)]
+pub struct Offset(Arc<AtomicUsize>);
+
+impl Offset {
+    pub fn get(&self) -> usize {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    fn set(&self, offset: usize) {
+        self.0.store(offset, Ordering::Relaxed)
+    }
+}
+
+impl PartialEq for Offset {
+    fn eq(&self, other: &Self) -> bool {
+        self.get() == other.get()
+    }
+}
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Statement {
-    Begin(IsolationLevel, Option<Variable>),
+    Begin(IsolationLevel, Option<Variable>, bool),
     Commit,
     Abort,
+    Savepoint(Variable),
+    RollbackTo(Variable),
     Expression(Expression),
     Latch,
+    Await(Expression),
 
-    If(Expression, Rc<Cell<usize>>),
-    Else(Rc<Cell<usize>>),
+    If(Expression, Offset),
+    Else(Offset),
+    Either(Offset),
+    Or(Offset),
+    While(Expression, Offset),
+    EndWhile(Expression, Offset),
 
     Always(Expression),
     Never(Expression),
     Eventually(Expression),
+    LeadsTo(Expression, Expression),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum LockMode {
+    Update,
+    Share,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum LockWait {
+    Wait,
+    NoWait,
+    SkipLocked,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Locking {
+    pub mode: LockMode,
+    pub wait: LockWait,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum IsolationLevel {
     ReadCommitted,
+    Serializable,
+    SnapshotIsolation,
 }
 
 impl std::fmt::Display for IsolationLevel {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
             IsolationLevel::ReadCommitted => f.write_str("read committed"),
+            IsolationLevel::Serializable => f.write_str("serializable"),
+            IsolationLevel::SnapshotIsolation => f.write_str("snapshot isolation"),
+        }
+    }
+}
+
+fn parse_isolation_level(lexeme: &str) -> Option<IsolationLevel> {
+    match lexeme {
+        "read_committed" => Some(IsolationLevel::ReadCommitted),
+        "serializable" => Some(IsolationLevel::Serializable),
+        "snapshot_isolation" => Some(IsolationLevel::SnapshotIsolation),
+        _ => None,
+    }
+}
+
+#[derive(PartialEq, Eq, Hash, PartialOrd, Ord, Debug, Clone, Copy)]
+pub enum Type {
+    Integer,
+    String,
+    Bool,
+}
+
+impl std::fmt::Display for Type {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Type::Integer => f.write_str("integer"),
+            Type::String => f.write_str("string"),
+            Type::Bool => f.write_str("bool"),
         }
     }
 }
 
-#[derive(PartialEq, Debug, Clone)]
+fn parse_type(lexeme: &str) -> Option<Type> {
+    match lexeme {
+        "int" | "integer" => Some(Type::Integer),
+        "string" | "text" => Some(Type::String),
+        "bool" | "boolean" => Some(Type::Bool),
+        _ => None,
+    }
+}
+
+#[derive(PartialEq, Eq, Hash, PartialOrd, Ord, Debug, Clone)]
 pub struct Variable {
     pub name: String,
 }
@@ -50,10 +138,21 @@ impl std::fmt::Display for Variable {
     }
 }
 
-#[derive(PartialEq, Debug, Clone)]
+#[derive(PartialEq, Eq, Hash, PartialOrd, Ord, Debug, Clone)]
 pub enum SelectItem {
     Column(Item),
     Count(Item),
+    CountDistinct(Item),
+    Sum(Item),
+    Min(Item),
+    Max(Item),
+    Avg(Item),
+}
+
+impl SelectItem {
+    pub fn is_aggregate(&self) -> bool {
+        !matches!(self, SelectItem::Column(_))
+    }
 }
 
 impl std::fmt::Display for SelectItem {
@@ -61,11 +160,72 @@ impl std::fmt::Display for SelectItem {
         match self {
             SelectItem::Column(item) => std::fmt::Display::fmt(item, f),
             SelectItem::Count(item) => f.write_fmt(format_args!("count({item})")),
+            SelectItem::CountDistinct(item) => f.write_fmt(format_args!("count(distinct {item})")),
+            SelectItem::Sum(item) => f.write_fmt(format_args!("sum({item})")),
+            SelectItem::Min(item) => f.write_fmt(format_args!("min({item})")),
+            SelectItem::Max(item) => f.write_fmt(format_args!("max({item})")),
+            SelectItem::Avg(item) => f.write_fmt(format_args!("avg({item})")),
         }
     }
 }
 
-#[derive(PartialEq, Debug, Clone)]
+#[derive(PartialEq, Eq, Hash, PartialOrd, Ord, Debug, Clone)]
+pub enum ReturningItem {
+    Old(Item),
+    New(Item),
+}
+
+impl std::fmt::Display for ReturningItem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReturningItem::Old(item) => f.write_fmt(format_args!("old.{item}")),
+            ReturningItem::New(item) => f.write_fmt(format_args!("new.{item}")),
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Hash, PartialOrd, Ord, Debug, Clone)]
+pub enum InsertSource {
+    Values(Vec<SqlExpression>),
+    Select(Box<SqlExpression>),
+}
+
+impl std::fmt::Display for InsertSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InsertSource::Values(values) => {
+                f.write_str("values ")?;
+                intersperse(f, values, ",")
+            }
+            InsertSource::Select(select) => f.write_fmt(format_args!("{select}")),
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Hash, PartialOrd, Ord, Debug, Clone)]
+pub enum OnConflict {
+    Nothing,
+    Update {
+        columns: Vec<Variable>,
+        updates: Vec<SqlExpression>,
+    },
+}
+
+impl std::fmt::Display for OnConflict {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OnConflict::Nothing => f.write_str("on conflict do nothing"),
+            OnConflict::Update { columns, updates } => {
+                f.write_str("on conflict (")?;
+                intersperse(f, columns, ",")?;
+                f.write_str(") do update set ")?;
+                intersperse(f, updates, ",")
+            }
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Hash, PartialOrd, Ord, Debug, Clone)]
 pub enum Item {
     Wildcard,
     Column(String),
@@ -80,21 +240,53 @@ impl std::fmt::Display for Item {
     }
 }
 
-#[derive(PartialEq, Debug, Clone)]
+#[derive(PartialEq, Eq, Hash, PartialOrd, Ord, Debug, Clone)]
+pub struct ColumnDefinition {
+    pub name: Variable,
+    pub kind: Type,
+    pub default: Option<SqlExpression>,
+    pub check: Option<SqlExpression>,
+}
+
+impl std::fmt::Display for ColumnDefinition {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("{} {}", self.name, self.kind))?;
+        if let Some(default) = &self.default {
+            f.write_fmt(format_args!(" default {default}"))?;
+        }
+        if let Some(check) = &self.check {
+            f.write_fmt(format_args!(" check ({check})"))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(PartialEq, Eq, Hash, PartialOrd, Ord, Debug, Clone)]
 pub enum SqlExpression {
     Select {
         columns: Vec<SelectItem>,
         from: Variable,
+        join_table: Option<Variable>,
+        join_condition: Option<Box<SqlExpression>>,
         condition: Option<Box<SqlExpression>>,
+        group_by: Vec<Item>,
+        having: Option<Box<SqlExpression>>,
         order_by: Option<Box<SqlExpression>>,
-        limit: Option<i16>,
-        offset: Option<i16>,
-        locking: bool,
+        descending: bool,
+        limit: Option<i64>,
+        offset: Option<i64>,
+        locking: Option<Locking>,
+    },
+    Union {
+        left: Box<SqlExpression>,
+        right: Box<SqlExpression>,
+        all: bool,
     },
     Update {
         relation: Variable,
         updates: Vec<SqlExpression>,
         condition: Option<Box<SqlExpression>>,
+        returning: Vec<ReturningItem>,
     },
     Delete {
         relation: Variable,
@@ -103,38 +295,52 @@ pub enum SqlExpression {
     Insert {
         relation: Variable,
         columns: Vec<Variable>,
-        values: Vec<SqlExpression>,
+        source: InsertSource,
+        on_conflict: Option<OnConflict>,
     },
     Create {
         relation: Variable,
         columns: Vec<Variable>,
+        primary: bool,
+    },
+    CreateTable {
+        relation: Variable,
+        columns: Vec<ColumnDefinition>,
     },
+    LockTable(Variable),
+    Drop(Variable),
+    Truncate(Variable),
     Alter {
         constraint_name: Variable,
         relation: Variable,
         columns: Vec<Variable>,
         reference_relation: Variable,
         reference_columns: Vec<Variable>,
+        on_delete_cascade: bool,
     },
     Binary {
         left: Box<SqlExpression>,
         operator: SqlOperator,
         right: Box<SqlExpression>,
     },
+    Not(Box<SqlExpression>),
+    Subquery(Box<SqlExpression>),
     Scalar(Box<SqlExpression>),
     Tuple(Vec<SqlExpression>),
     Assignment(Variable, Box<SqlExpression>),
     Set(Vec<SqlExpression>),
     Var(Variable),
-    Integer(i16),
+    Integer(i64),
     String(String),
+    Null,
     Bool(bool),
+    Aggregate(SelectItem),
     UpVariable(Variable),
     // UpVariables are translated to value
     Value(Value),
 }
 
-#[derive(PartialEq, Debug, Clone)]
+#[derive(PartialEq, Eq, Hash, PartialOrd, Ord, Debug, Clone)]
 pub enum Expression {
     Sql(SqlExpression),
     Binary {
@@ -148,14 +354,18 @@ pub enum Expression {
     },
     Assignment(Variable, Box<Expression>),
     Var(Variable),
-    Integer(i16),
+    Integer(i64),
     String(String),
+    Null,
     Set(Vec<Expression>),
     Tuple(Vec<Expression>),
     Scalar(Box<Expression>),
+    ToSet(Box<Expression>),
+    Forall(Variable, Box<Expression>, Box<Expression>),
+    Exists(Variable, Box<Expression>, Box<Expression>),
 }
 
-#[derive(PartialEq, Debug, Clone)]
+#[derive(PartialEq, Eq, Hash, PartialOrd, Ord, Debug, Clone)]
 pub enum Operator {
     Add,
     Subtract,
@@ -173,7 +383,7 @@ pub enum Operator {
     Or,
 }
 
-#[derive(PartialEq, Debug, Clone)]
+#[derive(PartialEq, Eq, Hash, PartialOrd, Ord, Debug, Clone)]
 pub enum SqlOperator {
     Add,
     Subtract,
@@ -187,24 +397,41 @@ pub enum SqlOperator {
     Greater,
     GreaterEqual,
     In,
+    NotIn,
     Between,
+    Is,
+    IsNot,
+    Like,
     And,
+    Or,
 }
 
 pub struct Parser {
     scanner: Scanner,
-    manual_commit: bool,
+    schema: HashMap<String, HashSet<String>>,
+    references: Vec<(Vec<Token>, Vec<Token>, Option<usize>)>,
+    column_references: Vec<Token>,
     previous: Token,
     current: Token,
+    positions: Vec<Option<Position>>,
+    path: PathBuf,
+    includes: Vec<PathBuf>,
+    // Files are included once, a file included again by another file is skipped
+    included: HashSet<PathBuf>,
+    file: Option<usize>,
     result: Mets,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum ParserErrorKind {
     AggregateError(SelectItem),
+    UnknownTable(String),
+    UnknownColumn(String, String),
+    AmbiguousColumn(String),
     ParseInt(ParseIntError),
     Scanner(ScannerError),
     Unexpected(String),
+    Include(String, String, Box<ParserError>),
 }
 
 impl From<ScannerError> for ParserErrorKind {
@@ -227,9 +454,42 @@ pub struct ParserError {
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Mets {
+    pub constants: Vec<(Variable, Expression)>,
+    pub globals: Vec<Statement>,
     pub init: Vec<Statement>,
     pub processes: Vec<Vec<Statement>>,
+    pub fair_processes: Vec<bool>,
     pub properties: Vec<Statement>,
+    pub deadlocks: Deadlocks,
+    pub positions: Positions,
+}
+
+// Deadlocks are violations unless the model declares them expected
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Deadlocks {
+    #[default]
+    Undeclared,
+    Forbidden,
+    Expected,
+}
+
+// Where each declaration starts in the source, to locate runtime errors
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Positions {
+    pub constants: Vec<Location>,
+    pub globals: Vec<Location>,
+    pub init: Vec<Location>,
+    pub processes: Vec<Vec<Location>>,
+    pub properties: Vec<Location>,
+    // Path and source of the included files, references are validated once all files are parsed
+    pub files: Vec<(String, String)>,
+}
+
+// A position in the model file, or in the included file of that index
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Location {
+    pub position: Position,
+    pub file: Option<usize>,
 }
 
 pub type Res<T> = Result<T, ParserErrorKind>;
@@ -239,17 +499,37 @@ impl Parser {
     pub fn new(source: String) -> Self {
         Parser {
             scanner: Scanner::new(source),
-            manual_commit: false,
+            schema: HashMap::new(),
+            references: vec![],
+            column_references: vec![],
             previous: Token::uninitialized(),
             current: Token::uninitialized(),
+            positions: vec![],
+            path: PathBuf::new(),
+            includes: vec![],
+            included: HashSet::new(),
+            file: None,
             result: Mets {
+                constants: vec![],
+                globals: vec![],
                 init: vec![],
                 processes: vec![],
+                fair_processes: vec![],
                 properties: vec![],
+                deadlocks: Deadlocks::Undeclared,
+                positions: Positions::default(),
             },
         }
     }
 
+    // Included files are resolved against the directory of the parsed file
+    pub fn with_path(source: String, path: &Path) -> Self {
+        let mut parser = Parser::new(source);
+        parser.path = path.to_path_buf();
+        parser.includes = path.canonicalize().into_iter().collect();
+        parser
+    }
+
     pub fn compile(mut self) -> Result<Mets, Box<ParserError>> {
         match self.private_compile() {
             Ok(_) => Ok(self.result),
@@ -266,7 +546,97 @@ impl Parser {
         while !self.matches(TokenKind::Eof)? {
             self.declaration()?;
         }
-        self.consume(TokenKind::Eof, "Expected end of expression")
+        self.consume(TokenKind::Eof, "Expected end of expression")?;
+        self.validate_references()
+    }
+
+    fn validate_references(&mut self) -> Unit {
+        for (tables, columns, file) in &self.references {
+            if let Err(error) = self.validate_reference(tables, columns) {
+                let Some(file) = file else {
+                    self.current = error.current;
+                    return Err(error.kind);
+                };
+                let (path, source) = &self.result.positions.files[*file];
+                return Err(ParserErrorKind::Include(
+                    path.clone(),
+                    source.clone(),
+                    error,
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_reference(
+        &self,
+        tables: &[Token],
+        columns: &[Token],
+    ) -> Result<(), Box<ParserError>> {
+        if let Some(table) = tables.iter().find(|t| !self.schema.contains_key(&t.lexeme)) {
+            return Err(Box::new(ParserError {
+                current: table.clone(),
+                kind: ParserErrorKind::UnknownTable(table.lexeme.clone()),
+            }));
+        }
+        for column in columns {
+            let (candidates, name) = match column.lexeme.split_once('.') {
+                Some((table, name)) => {
+                    let Some(table) = tables.iter().find(|t| t.lexeme == table) else {
+                        return Err(Box::new(ParserError {
+                            current: column.clone(),
+                            kind: ParserErrorKind::UnknownTable(table.to_string()),
+                        }));
+                    };
+                    (vec![table], name)
+                }
+                None => (tables.iter().collect(), column.lexeme.as_str()),
+            };
+            let matching: Vec<_> = candidates
+                .iter()
+                .filter(|t| self.schema[&t.lexeme].contains(name))
+                .collect();
+            if matching.is_empty() {
+                return Err(Box::new(ParserError {
+                    current: column.clone(),
+                    kind: ParserErrorKind::UnknownColumn(
+                        candidates[0].lexeme.clone(),
+                        name.to_string(),
+                    ),
+                }));
+            } else if matching.len() > 1 {
+                return Err(Box::new(ParserError {
+                    current: column.clone(),
+                    kind: ParserErrorKind::AmbiguousColumn(name.to_string()),
+                }));
+            }
+        }
+        Ok(())
+    }
+
+    fn declare_columns(&mut self, relation: &Variable, columns: &[Variable]) {
+        self.schema
+            .entry(relation.name.clone())
+            .or_default()
+            .extend(columns.iter().map(|c| c.name.clone()));
+    }
+
+    fn reference_columns(&mut self, relations: Vec<Token>, enclosing: Vec<Token>) {
+        let columns = mem::replace(&mut self.column_references, enclosing);
+        self.references.push((relations, columns, self.file));
+    }
+
+    fn column_reference(&mut self) -> Res<Variable> {
+        let mut token = self.previous.clone();
+        if self.matches(TokenKind::Dot)? {
+            self.consume(TokenKind::Identifier, "Expected column after table name")?;
+            token.lexeme = format!("{}.{}", token.lexeme, self.previous.lexeme);
+        }
+        let variable = Variable {
+            name: token.lexeme.clone(),
+        };
+        self.column_references.push(token);
+        Ok(variable)
     }
 
     fn advance(&mut self) -> Unit {
@@ -320,6 +690,29 @@ impl Parser {
         }
     }
 
+    // An operator ending its line, like the or of an either block, is not part of the expression
+    fn matches_forward_operator(&mut self, kind: TokenKind) -> Res<bool> {
+        let mut clone = self.scanner.clone();
+        let mut advances = 1;
+        let mut current = self.current.clone();
+        loop {
+            let Token {
+                kind: next_kind, ..
+            } = current;
+            if next_kind == TokenKind::Newline {
+                advances += 1;
+            } else if next_kind == kind && clone.scan_token()?.kind != TokenKind::Newline {
+                for _ in 0..advances {
+                    self.advance()?;
+                }
+                return Ok(true);
+            } else {
+                return Ok(false);
+            }
+            current = clone.scan_token()?;
+        }
+    }
+
     fn matches_forward_within(&mut self, kinds: &[TokenKind]) -> Res<bool> {
         let mut clone = self.scanner.clone();
         let mut advances = 1;
@@ -352,20 +745,131 @@ impl Parser {
 
     fn declaration(&mut self) -> Unit {
         if self.matches(TokenKind::Process)? {
-            self.process_declaration()
+            self.process_declaration(false)
+        } else if self.matches(TokenKind::Fair)? {
+            self.consume(TokenKind::Process, "Expected process after fair")?;
+            self.process_declaration(true)
         } else if self.matches(TokenKind::Init)? {
             self.init_declaration()
+        } else if self.matches(TokenKind::Global)? {
+            self.global_declaration()
         } else if self.matches(TokenKind::Property)? {
             self.property_declaration()
+        } else if self.matches(TokenKind::Const)? {
+            self.const_declaration()
+        } else if self.matches(TokenKind::Include)? {
+            self.include_declaration()
         } else {
             Err(ParserErrorKind::Unexpected(format!(
-                "Expected either process, init or property. Parsed {:?} instead",
+                "Expected either process, fair process, init, global, property, const or include. Parsed {:?} instead",
                 self.current.kind
             )))
         }
     }
 
+    fn const_declaration(&mut self) -> Unit {
+        let name = self.parse_variable("Expected constant name after const")?;
+        if self.is_constant(&name) {
+            return Err(ParserErrorKind::Unexpected(format!(
+                "Constant {name} is already declared"
+            )));
+        }
+        self.consume(TokenKind::Equal, "Expected = after constant name")?;
+
+        let position = self.current.position.clone();
+        let expr = self.expression()?;
+        self.result.constants.push((name, expr));
+        self.result.positions.constants.push(Location {
+            position,
+            file: self.file,
+        });
+
+        self.end_line()
+    }
+
+    fn include_declaration(&mut self) -> Unit {
+        let include = self.current.clone();
+        self.consume(TokenKind::String, "Expected file path after include")?;
+        let file = include.lexeme.trim_matches('\'').to_string();
+        let path = match self.path.parent() {
+            Some(dir) => dir.join(&file),
+            None => PathBuf::from(&file),
+        };
+        let source = std::fs::read_to_string(&path).map_err(|err| {
+            self.current = include.clone();
+            ParserErrorKind::Unexpected(format!("Could not read {}: {err}", path.display()))
+        })?;
+        let canonical = path.canonicalize().unwrap_or_else(|_| path.clone());
+        if self.includes.contains(&canonical) {
+            self.current = include;
+            return Err(ParserErrorKind::Unexpected(format!(
+                "Cyclic include of {}",
+                path.display()
+            )));
+        }
+        if !self.included.insert(canonical.clone()) {
+            return self.end_line();
+        }
+
+        // Declarations of the included file are parsed in place, then the current file resumes
+        self.result
+            .positions
+            .files
+            .push((path.display().to_string(), source.clone()));
+        let scanner = mem::replace(&mut self.scanner, Scanner::new(source.clone()));
+        let path = mem::replace(&mut self.path, path);
+        let previous = self.previous.clone();
+        let current = mem::replace(&mut self.current, Token::uninitialized());
+        self.includes.push(canonical);
+        let file = self.file.replace(self.result.positions.files.len() - 1);
+        let included = self.included_declarations();
+        self.file = file;
+        self.includes.pop();
+        let included_path = mem::replace(&mut self.path, path);
+        self.scanner = scanner;
+        self.previous = previous;
+        match included {
+            Ok(()) => {
+                self.current = current;
+                self.end_line()
+            }
+            Err(kind) => {
+                let error = ParserError {
+                    current: mem::replace(&mut self.current, include),
+                    kind,
+                };
+                Err(ParserErrorKind::Include(
+                    included_path.display().to_string(),
+                    source,
+                    Box::new(error),
+                ))
+            }
+        }
+    }
+
+    fn included_declarations(&mut self) -> Unit {
+        self.advance()?;
+        self.skip_newlines()?;
+        while !self.matches(TokenKind::Eof)? {
+            self.declaration()?;
+        }
+        Ok(())
+    }
+
+    fn is_constant(&self, variable: &Variable) -> bool {
+        self.result
+            .constants
+            .iter()
+            .any(|(name, _)| name.name == variable.name)
+    }
+
     fn init_declaration(&mut self) -> Unit {
+        if !self.result.init.is_empty() {
+            self.current = self.previous.clone();
+            return Err(ParserErrorKind::Unexpected(
+                "Init block is already declared".to_string(),
+            ));
+        }
         self.consume(TokenKind::Do, "Expected do after init declaration")?;
         self.consume(
             TokenKind::Newline,
@@ -377,6 +881,7 @@ impl Parser {
             self.statement(&mut statements)?;
         }
         self.result.init = statements;
+        self.result.positions.init = self.statement_positions();
 
         self.consume(
             TokenKind::End,
@@ -386,7 +891,39 @@ impl Parser {
         self.end_line()
     }
 
-    fn process_declaration(&mut self) -> Unit {
+    fn global_declaration(&mut self) -> Unit {
+        self.consume(TokenKind::Do, "Expected do after global declaration")?;
+        self.consume(
+            TokenKind::Newline,
+            "Expected newline after global declaration",
+        )?;
+
+        let mut statements = vec![];
+        while self.current.kind != TokenKind::End {
+            self.statement(&mut statements)?;
+        }
+        self.result.globals = statements;
+        self.result.positions.globals = self.statement_positions();
+
+        self.consume(
+            TokenKind::End,
+            "Expected end at the end of global declaration",
+        )?;
+
+        self.end_line()
+    }
+
+    fn process_declaration(&mut self, fair: bool) -> Unit {
+        let mut count = 1;
+        if self.matches(TokenKind::Number)? {
+            count = self.integer()?;
+            if count < 1 {
+                self.current = self.previous.clone();
+                return Err(ParserErrorKind::Unexpected(
+                    "Expected at least one process replica".to_string(),
+                ));
+            }
+        }
         self.consume(TokenKind::Do, "Expected do after process declaration")?;
         self.consume(
             TokenKind::Newline,
@@ -397,7 +934,12 @@ impl Parser {
         while self.current.kind != TokenKind::End {
             self.statement(&mut statements)?;
         }
-        self.result.processes.push(statements);
+        let positions = self.statement_positions();
+        for _ in 0..count {
+            self.result.processes.push(statements.clone());
+            self.result.fair_processes.push(fair);
+            self.result.positions.processes.push(positions.clone());
+        }
 
         self.consume(
             TokenKind::End,
@@ -407,22 +949,64 @@ impl Parser {
         self.end_line()
     }
 
+    // A semicolon separates statements written on the same line
     fn end_line(&mut self) -> Unit {
-        if self.current.kind != TokenKind::Eof {
+        if self.current.kind != TokenKind::Eof && !self.matches(TokenKind::Semicolon)? {
             self.consume(TokenKind::Newline, "Expected newline after declaration")?;
         }
         self.skip_newlines()
     }
 
     fn property_declaration(&mut self) -> Unit {
+        let deadlocks = match self.current.kind {
+            TokenKind::DeadlockFree => Some(Deadlocks::Forbidden),
+            TokenKind::DeadlockExpected => Some(Deadlocks::Expected),
+            _ => None,
+        };
+        if let Some(deadlocks) = deadlocks {
+            if self.result.deadlocks != Deadlocks::Undeclared {
+                return Err(ParserErrorKind::Unexpected(
+                    "Deadlock property is already declared".to_string(),
+                ));
+            }
+            self.result.deadlocks = deadlocks;
+            self.advance()?;
+            return self.end_line();
+        }
+
         let mut statements = vec![];
         self.statement(&mut statements)?;
         self.result.properties.push(statements.remove(0));
+        let position = self.statement_positions().remove(0);
+        self.result.positions.properties.push(position);
 
         Ok(())
     }
 
+    fn statement_positions(&mut self) -> Vec<Location> {
+        mem::take(&mut self.positions)
+            .into_iter()
+            .map(|position| Location {
+                position: position.unwrap_or_default(),
+                file: self.file,
+            })
+            .collect()
+    }
+
     fn statement(&mut self, writer: &mut Vec<Statement>) -> Unit {
+        let position = self.current.position.clone();
+        let start = writer.len();
+        self.parse_statement(writer)?;
+
+        // Nested statements already recorded their position, markers like else get the enclosing one
+        self.positions.resize(writer.len(), None);
+        for recorded in &mut self.positions[start..] {
+            recorded.get_or_insert_with(|| position.clone());
+        }
+        Ok(())
+    }
+
+    fn parse_statement(&mut self, writer: &mut Vec<Statement>) -> Unit {
         if self.matches(TokenKind::Let)? {
             self.assignment_statement(writer)?
         } else if self.matches(TokenKind::Transaction)? {
@@ -435,10 +1019,20 @@ impl Parser {
             self.if_statement(writer)?
         } else if self.matches(TokenKind::Else)? {
             self.else_statement(writer)?
+        } else if self.matches(TokenKind::Either)? {
+            self.either_statement(writer)?
+        } else if self.matches(TokenKind::While)? {
+            self.while_statement(writer)?
         } else if self.matches(TokenKind::Abort)? {
             self.abort_statement(writer)?
+        } else if self.matches(TokenKind::Savepoint)? {
+            self.savepoint_statement(writer)?
+        } else if self.matches(TokenKind::Rollback)? {
+            self.rollback_statement(writer)?
         } else if self.matches(TokenKind::Latch)? {
             self.latch_statement(writer)?
+        } else if self.matches(TokenKind::Await)? {
+            self.await_statement(writer)?
         } else if self.matches(TokenKind::Always)? {
             self.always_statement(writer)?
         } else if self.matches(TokenKind::Never)? {
@@ -464,7 +1058,7 @@ impl Parser {
             self.parse_variable("Expected transaction name or transaction level")?;
         let mut tx_name = None;
 
-        if first_tx_param.name != "read_committed" {
+        if parse_isolation_level(&first_tx_param.name).is_none() {
             tx_name = Some(first_tx_param.clone());
             self.consume(
                 TokenKind::Identifier,
@@ -472,28 +1066,41 @@ impl Parser {
             )?;
         }
 
-        match self.previous.lexeme.as_str() {
-            "read_committed" => {
-                self.consume(TokenKind::Do, "Expected block after transaction statement")?;
-                self.end_line()?;
+        let isolation = self.isolation_level()?;
+        let read_only = self.read_only()?;
+        self.consume(TokenKind::Do, "Expected block after transaction statement")?;
+        self.end_line()?;
 
-                writer.push(Statement::Begin(IsolationLevel::ReadCommitted, tx_name));
-                self.manual_commit = false;
+        writer.push(Statement::Begin(isolation, tx_name, read_only));
 
-                while self.current.kind != TokenKind::End {
-                    self.statement(writer)?;
-                }
+        let mut last = None;
+        while self.current.kind != TokenKind::End {
+            let index = writer.len();
+            self.statement(writer)?;
+            last = writer.get(index).cloned();
+        }
 
-                self.consume(TokenKind::End, "Expected to close transaction block")?;
+        self.consume(TokenKind::End, "Expected to close transaction block")?;
 
-                if !self.manual_commit {
-                    writer.push(Statement::Commit);
-                }
-                Ok(())
-            }
-            _ => Err(ParserErrorKind::Unexpected(
-                "Expected following isolation level: read_committed".to_string(),
-            )),
+        if !matches!(last, Some(Statement::Commit | Statement::Abort)) {
+            writer.push(Statement::Commit);
+        }
+        Ok(())
+    }
+
+    fn isolation_level(&self) -> Res<IsolationLevel> {
+        parse_isolation_level(&self.previous.lexeme).ok_or(ParserErrorKind::Unexpected(
+            "Expected following isolation level: read_committed, serializable, snapshot_isolation"
+                .to_string(),
+        ))
+    }
+
+    fn read_only(&mut self) -> Res<bool> {
+        if self.matches(TokenKind::Read)? {
+            self.consume(TokenKind::Only, "Expected only after read")?;
+            Ok(true)
+        } else {
+            Ok(false)
         }
     }
 
@@ -509,20 +1116,14 @@ impl Parser {
             "Expected isolation level after begin",
         )?;
 
-        match self.previous.lexeme.as_str() {
-            "read_committed" => {
-                writer.push(Statement::Begin(IsolationLevel::ReadCommitted, None));
-                Ok(())
-            }
-            _ => Err(ParserErrorKind::Unexpected(
-                "Expected following isolation level: read_committed".to_string(),
-            )),
-        }
+        let isolation = self.isolation_level()?;
+        let read_only = self.read_only()?;
+        writer.push(Statement::Begin(isolation, None, read_only));
+        Ok(())
     }
 
     fn commit_statement(&mut self, writer: &mut Vec<Statement>) -> Unit {
         writer.push(Statement::Commit);
-        self.manual_commit = true;
         Ok(())
     }
 
@@ -531,24 +1132,79 @@ impl Parser {
         self.consume(TokenKind::Do, "Expected do token after if condition")?;
         self.end_line()?;
 
-        let if_offset = Rc::new(Cell::new(0));
+        let if_offset = Offset::default();
+        let if_index = writer.len();
         writer.push(Statement::If(expr, if_offset.clone()));
 
-        while !self.matches_forward(TokenKind::Else)? {
+        while !self.matches_forward_within(&[TokenKind::Else, TokenKind::End])? {
             self.statement(writer)?;
-            if_offset.set(if_offset.get() + 1);
         }
 
-        let else_offset = Rc::new(Cell::new(0));
+        let else_offset = Offset::default();
+        let else_index = writer.len();
         writer.push(Statement::Else(else_offset.clone()));
-        if_offset.set(if_offset.get() + 1);
+        if_offset.set(else_index + 1 - if_index);
+
+        if self.previous.kind == TokenKind::Else {
+            // else if chains nest the next if in the else block, sharing the same end
+            if self.matches(TokenKind::If)? {
+                self.if_statement(writer)?;
+            } else {
+                self.end_line()?;
+                while !self.matches_forward(TokenKind::End)? {
+                    self.statement(writer)?;
+                }
+            }
+        }
+        else_offset.set(writer.len() - else_index);
+
+        Ok(())
+    }
+
+    fn either_statement(&mut self, writer: &mut Vec<Statement>) -> Unit {
+        self.end_line()?;
+
+        let either_offset = Offset::default();
+        let either_index = writer.len();
+        writer.push(Statement::Either(either_offset.clone()));
+
+        while !self.matches_forward(TokenKind::Or)? {
+            self.statement(writer)?;
+        }
+
+        let or_offset = Offset::default();
+        let or_index = writer.len();
+        writer.push(Statement::Or(or_offset.clone()));
+        either_offset.set(or_index + 1 - either_index);
+        self.end_line()?;
+
+        while !self.matches_forward(TokenKind::End)? {
+            self.statement(writer)?;
+        }
+        or_offset.set(writer.len() - or_index);
+
+        Ok(())
+    }
+
+    fn while_statement(&mut self, writer: &mut Vec<Statement>) -> Unit {
+        let expr = self.expression()?;
+        self.consume(TokenKind::Do, "Expected do token after while condition")?;
         self.end_line()?;
 
+        let while_offset = Offset::default();
+        let while_index = writer.len();
+        writer.push(Statement::While(expr.clone(), while_offset.clone()));
+
         while !self.matches_forward(TokenKind::End)? {
             self.statement(writer)?;
-            else_offset.set(else_offset.get() + 1);
         }
 
+        let end_offset = Offset::default();
+        let end_index = writer.len();
+        writer.push(Statement::EndWhile(expr, end_offset.clone()));
+        while_offset.set(end_index + 1 - while_index);
+        end_offset.set(end_index - while_index - 1);
+
         Ok(())
     }
 
@@ -558,7 +1214,19 @@ impl Parser {
 
     fn abort_statement(&mut self, writer: &mut Vec<Statement>) -> Unit {
         writer.push(Statement::Abort);
-        self.manual_commit = true;
+        Ok(())
+    }
+
+    fn savepoint_statement(&mut self, writer: &mut Vec<Statement>) -> Unit {
+        let name = self.parse_variable("Expected savepoint name")?;
+        writer.push(Statement::Savepoint(name));
+        Ok(())
+    }
+
+    fn rollback_statement(&mut self, writer: &mut Vec<Statement>) -> Unit {
+        self.consume(TokenKind::To, "Expected to after rollback")?;
+        let name = self.parse_variable("Expected savepoint name after rollback to")?;
+        writer.push(Statement::RollbackTo(name));
         Ok(())
     }
 
@@ -567,17 +1235,36 @@ impl Parser {
         Ok(())
     }
 
+    fn await_statement(&mut self, writer: &mut Vec<Statement>) -> Unit {
+        self.consume(TokenKind::LeftParen, "Expected ( to open await statement")?;
+
+        let expr = self.expression()?;
+
+        self.consume(TokenKind::RightParen, "Expected ) to close await statement")?;
+
+        writer.push(Statement::Await(expr));
+        Ok(())
+    }
+
     fn always_statement(&mut self, writer: &mut Vec<Statement>) -> Unit {
         self.consume(TokenKind::LeftParen, "Expected ( to open always statement")?;
 
         let expr = self.expression()?;
+        let leads_to = if self.matches(TokenKind::LeadsTo)? {
+            Some(self.expression()?)
+        } else {
+            None
+        };
 
         self.consume(
             TokenKind::RightParen,
             "Expected ) to close always statement",
         )?;
 
-        writer.push(Statement::Always(expr));
+        writer.push(match leads_to {
+            Some(consequent) => Statement::LeadsTo(expr, consequent),
+            None => Statement::Always(expr),
+        });
         Ok(())
     }
 
@@ -630,6 +1317,11 @@ impl Parser {
                     self.previous
                 )));
             };
+            if self.is_constant(&name) {
+                return Err(ParserErrorKind::Unexpected(format!(
+                    "Cannot assign to constant {name}"
+                )));
+            }
             let value = self.assignment()?;
             expr = Expression::Assignment(name, Box::new(value));
         }
@@ -640,7 +1332,7 @@ impl Parser {
     fn or(&mut self) -> Res<Expression> {
         let mut expr = self.and()?;
 
-        while self.matches_forward(TokenKind::Or)? {
+        while self.matches_forward_operator(TokenKind::Or)? {
             let right = self.and()?;
             expr = Expression::Binary {
                 left: Box::new(expr),
@@ -668,7 +1360,7 @@ impl Parser {
     }
 
     fn sql_assignment(&mut self) -> Res<SqlExpression> {
-        let mut expr = self.sql_and()?;
+        let mut expr = self.sql_or()?;
 
         if self.matches(TokenKind::ColonEqual)? {
             let name = if let SqlExpression::Var(name) = expr {
@@ -686,8 +1378,23 @@ impl Parser {
         Ok(expr)
     }
 
+    fn sql_or(&mut self) -> Res<SqlExpression> {
+        let mut expr = self.sql_and()?;
+
+        if self.matches_forward(TokenKind::Or)? {
+            let right = self.sql_or()?;
+            expr = SqlExpression::Binary {
+                left: Box::new(expr),
+                operator: SqlOperator::Or,
+                right: Box::new(right),
+            };
+        }
+
+        Ok(expr)
+    }
+
     fn sql_and(&mut self) -> Res<SqlExpression> {
-        let mut expr = self.sql_equality()?;
+        let mut expr = self.sql_not()?;
 
         if self.matches_forward(TokenKind::And)? {
             let right = self.sql_and()?;
@@ -701,6 +1408,14 @@ impl Parser {
         Ok(expr)
     }
 
+    fn sql_not(&mut self) -> Res<SqlExpression> {
+        if self.matches(TokenKind::Not)? {
+            Ok(SqlExpression::Not(Box::new(self.sql_not()?)))
+        } else {
+            self.sql_equality()
+        }
+    }
+
     fn sql_equality(&mut self) -> Res<SqlExpression> {
         let mut expr = self.sql_comparison()?;
 
@@ -729,12 +1444,14 @@ impl Parser {
             TokenKind::LessEqual,
             TokenKind::GreaterEqual,
             TokenKind::RightCarret,
+            TokenKind::Like,
         ])? {
             let operator = match self.previous.kind {
                 TokenKind::LeftCarret => SqlOperator::Less,
                 TokenKind::LessEqual => SqlOperator::LessEqual,
                 TokenKind::GreaterEqual => SqlOperator::GreaterEqual,
                 TokenKind::RightCarret => SqlOperator::Greater,
+                TokenKind::Like => SqlOperator::Like,
                 _ => unreachable!(),
             };
             let right = self.sql_in()?;
@@ -745,17 +1462,39 @@ impl Parser {
             };
         }
 
+        if self.matches_forward(TokenKind::Is)? {
+            let operator = if self.matches(TokenKind::Not)? {
+                SqlOperator::IsNot
+            } else {
+                SqlOperator::Is
+            };
+            self.consume(TokenKind::Null, "Expected null after is")?;
+            expr = SqlExpression::Binary {
+                left: Box::new(expr),
+                operator,
+                right: Box::new(SqlExpression::Null),
+            };
+        }
+
         Ok(expr)
     }
 
     fn sql_in(&mut self) -> Res<SqlExpression> {
         let mut expr = self.sql_between()?;
 
-        if self.matches_forward(TokenKind::In)? {
+        let operator = if self.matches_forward(TokenKind::In)? {
+            Some(SqlOperator::In)
+        } else if self.matches_forward(TokenKind::Not)? {
+            self.consume(TokenKind::In, "Expected in after not")?;
+            Some(SqlOperator::NotIn)
+        } else {
+            None
+        };
+        if let Some(operator) = operator {
             let right = self.sql_between()?;
             expr = SqlExpression::Binary {
                 left: Box::new(expr),
-                operator: SqlOperator::In,
+                operator,
                 right: Box::new(right),
             };
         }
@@ -830,18 +1569,38 @@ impl Parser {
 
     fn sql_primary(&mut self) -> Res<SqlExpression> {
         if self.matches(TokenKind::Number)? {
-            let i = i16::from_str(&self.previous.lexeme)?;
+            let i = self.integer()?;
             Ok(SqlExpression::Integer(i))
+        } else if self.matches(TokenKind::Minus)? {
+            Ok(SqlExpression::Integer(self.negative_integer()?))
         } else if self.matches(TokenKind::String)? {
             let s = self.previous.lexeme.clone();
             Ok(SqlExpression::String(s))
+        } else if self.matches(TokenKind::Null)? {
+            Ok(SqlExpression::Null)
         } else if self.matches(TokenKind::Dollar)? {
             self.consume(TokenKind::Identifier, "Expect identifier after $")?;
             Ok(SqlExpression::UpVariable(self.make_variable()))
         } else if self.matches(TokenKind::Identifier)? {
-            Ok(SqlExpression::Var(self.make_variable()))
+            Ok(SqlExpression::Var(self.column_reference()?))
+        } else if [
+            TokenKind::Count,
+            TokenKind::Sum,
+            TokenKind::Min,
+            TokenKind::Max,
+            TokenKind::Avg,
+        ]
+        .contains(&self.current.kind)
+        {
+            Ok(SqlExpression::Aggregate(self.select_clause()?))
         } else if self.matches(TokenKind::LeftParen)? {
-            self.sql_set()
+            if self.matches(TokenKind::Select)? {
+                let select = self.union()?;
+                self.consume(TokenKind::RightParen, "Expected ) to close a subquery")?;
+                Ok(SqlExpression::Subquery(Box::new(select)))
+            } else {
+                self.sql_set()
+            }
         } else {
             Err(ParserErrorKind::Unexpected(format!(
                 "Expected sql expression, got a {:?}",
@@ -1005,16 +1764,28 @@ impl Parser {
     fn primary(&mut self) -> Res<Expression> {
         if self.matches(TokenKind::Number)? {
             self.number()
+        } else if self.matches(TokenKind::Minus)? {
+            Ok(Expression::Integer(self.negative_integer()?))
         } else if self.matches(TokenKind::String)? {
             self.string()
+        } else if self.matches(TokenKind::Null)? {
+            Ok(Expression::Null)
         } else if self.matches(TokenKind::LeftBrace)? {
             self.set()
         } else if self.matches(TokenKind::LeftParen)? {
             self.tuple()
         } else if self.matches(TokenKind::Identifier)? {
             self.variable()
+        } else if self.matches(TokenKind::Forall)? {
+            let (variable, domain, body) = self.quantifier()?;
+            Ok(Expression::Forall(variable, domain, body))
+        } else if self.matches(TokenKind::Exists)? {
+            let (variable, domain, body) = self.quantifier()?;
+            Ok(Expression::Exists(variable, domain, body))
         } else if self.matches(TokenKind::Backtick)? {
             self.sql_expression()
+        } else if self.matches(TokenKind::Set)? {
+            self.set_cast()
         } else if self.matches(TokenKind::Newline)? {
             self.expression()
         } else {
@@ -1025,12 +1796,38 @@ impl Parser {
         }
     }
 
+    fn quantifier(&mut self) -> Res<(Variable, Box<Expression>, Box<Expression>)> {
+        let variable = self.parse_variable("Expected variable name after quantifier")?;
+        self.consume(TokenKind::In, "Expected in after quantified variable")?;
+        let domain = self.primary()?;
+        self.consume(TokenKind::Do, "Expected do after quantifier domain")?;
+        self.consume(TokenKind::LeftParen, "Expected ( to open quantifier body")?;
+        let body = self.expression()?;
+        self.consume(TokenKind::RightParen, "Expected ) to close quantifier body")?;
+        Ok((variable, Box::new(domain), Box::new(body)))
+    }
+
     fn variable(&mut self) -> Res<Expression> {
         Ok(Expression::Var(self.make_variable()))
     }
 
+    fn integer(&mut self) -> Res<i64> {
+        i64::from_str(&self.previous.lexeme).map_err(|err| {
+            self.current = self.previous.clone();
+            err.into()
+        })
+    }
+
+    fn negative_integer(&mut self) -> Res<i64> {
+        self.consume(TokenKind::Number, "Expected number after -")?;
+        i64::from_str(&format!("-{}", self.previous.lexeme)).map_err(|err| {
+            self.current = self.previous.clone();
+            err.into()
+        })
+    }
+
     fn number(&mut self) -> Res<Expression> {
-        let i = i16::from_str(&self.previous.lexeme)?;
+        let i = self.integer()?;
         Ok(Expression::Integer(i))
     }
 
@@ -1062,6 +1859,14 @@ impl Parser {
         Ok(Expression::Set(members))
     }
 
+    fn set_cast(&mut self) -> Res<Expression> {
+        self.consume(TokenKind::LeftParen, "Expected ( after set")?;
+        let expr = self.expression()?;
+        self.consume(TokenKind::RightParen, "Expected ) to close set cast")?;
+
+        Ok(Expression::ToSet(Box::new(expr)))
+    }
+
     fn sql_tuple(&mut self) -> Res<SqlExpression> {
         let mut members = vec![];
         loop {
@@ -1104,7 +1909,7 @@ impl Parser {
 
     fn sql_expression(&mut self) -> Res<Expression> {
         let sql = if self.matches(TokenKind::Select)? {
-            self.select()
+            self.union()
         } else if self.matches(TokenKind::Insert)? {
             self.insert()
         } else if self.matches(TokenKind::Update)? {
@@ -1115,6 +1920,12 @@ impl Parser {
             self.create()
         } else if self.matches(TokenKind::Alter)? {
             self.alter()
+        } else if self.matches(TokenKind::Lock)? {
+            self.lock_table()
+        } else if self.matches(TokenKind::Drop)? {
+            self.drop()
+        } else if self.matches(TokenKind::Truncate)? {
+            self.truncate()
         } else {
             Err(ParserErrorKind::Unexpected(format!(
                 "Expected sql expression, got a {:?}",
@@ -1130,8 +1941,40 @@ impl Parser {
         Ok(Expression::Sql(sql))
     }
 
+    fn union(&mut self) -> Res<SqlExpression> {
+        let left = self.select()?;
+        if !self.matches(TokenKind::Union)? {
+            return Ok(left);
+        }
+        let all = self.matches(TokenKind::All)?;
+        self.consume(TokenKind::Select, "Expected select after union")?;
+        let right = self.union()?;
+
+        let arity = |select: &SqlExpression| match select {
+            SqlExpression::Select { columns, .. }
+                if !columns.contains(&SelectItem::Column(Item::Wildcard)) =>
+            {
+                Some(columns.len())
+            }
+            _ => None,
+        };
+        if let (Some(l), Some(r)) = (arity(&left), arity(&right)) {
+            if l != r {
+                return Err(ParserErrorKind::Unexpected(format!(
+                    "Expected both sides of union to select the same number of columns, got {l} and {r}"
+                )));
+            }
+        }
+        Ok(SqlExpression::Union {
+            left: Box::new(left),
+            right: Box::new(right),
+            all,
+        })
+    }
+
     fn select(&mut self) -> Res<SqlExpression> {
-        let mut locking = false;
+        let enclosing = mem::take(&mut self.column_references);
+        let mut locking = None;
         let mut columns = vec![];
         while self.current.kind != TokenKind::From {
             columns.push(self.select_clause()?);
@@ -1141,19 +1984,23 @@ impl Parser {
             }
         }
 
-        if columns
-            .iter()
-            .any(|col| matches!(col, SelectItem::Count(_)))
-        {
-            if let Some(item) = columns.iter().find(|x| !matches!(x, SelectItem::Count(_))) {
-                return Err(ParserErrorKind::AggregateError(item.clone()));
-            }
-        }
-
+        let select_end = self.current.clone();
         self.consume(TokenKind::From, "Expected from clause")?;
 
         self.consume(TokenKind::Identifier, "Expected relation for select from")?;
         let from = self.make_variable();
+        let mut relations = vec![self.previous.clone()];
+
+        let mut join = None;
+        if self.matches(TokenKind::Inner)? || self.check(TokenKind::Join) {
+            self.consume(TokenKind::Join, "Expected join after inner")?;
+            self.consume(TokenKind::Identifier, "Expected relation to join")?;
+            let join_table = self.make_variable();
+            relations.push(self.previous.clone());
+            self.consume(TokenKind::On, "Expected on condition after join")?;
+            join = Some((join_table, Box::new(self.sql_assignment()?)));
+        }
+        let (join_table, join_condition) = join.unzip();
 
         let mut condition = None;
         if self.matches(TokenKind::Where)? {
@@ -1161,40 +2008,89 @@ impl Parser {
             condition = Some(Box::new(expr));
         }
 
+        let mut group_by = vec![];
+        if self.matches(TokenKind::Group)? {
+            self.consume(TokenKind::By, "Expected by after group in select")?;
+            loop {
+                self.consume(TokenKind::Identifier, "Expected column in group by")?;
+                group_by.push(Item::Column(self.column_reference()?.name));
+                if !self.matches(TokenKind::Comma)? {
+                    break;
+                }
+            }
+        }
+
+        let mut having = None;
+        if self.matches(TokenKind::Having)? {
+            having = Some(Box::new(self.sql_assignment()?));
+        }
+
+        if !group_by.is_empty() || columns.iter().any(SelectItem::is_aggregate) {
+            if let Some(item) = columns.iter().find(|x| match x {
+                SelectItem::Column(item) => !group_by.contains(item),
+                _ => false,
+            }) {
+                self.current = select_end;
+                return Err(ParserErrorKind::AggregateError(item.clone()));
+            }
+        }
+
         let mut order_by = None;
+        let mut descending = false;
         if self.matches(TokenKind::Order)? {
             self.consume(TokenKind::By, "Expected by after order in select")?;
 
             order_by = Some(Box::new(self.sql_multiplicative()?));
+            descending = self.matches(TokenKind::Desc)?;
         }
 
         let mut limit = None;
         if self.matches(TokenKind::Limit)? {
             self.consume(TokenKind::Number, "Expected number after limit")?;
-            let i = i16::from_str(&self.previous.lexeme)?;
+            let i = self.integer()?;
             limit = Some(i);
         }
 
         let mut offset = None;
         if self.matches(TokenKind::Offset)? {
-            self.consume(TokenKind::Number, "Expected number after limit")?;
-            let i = i16::from_str(&self.previous.lexeme)?;
+            self.consume(TokenKind::Number, "Expected number after offset")?;
+            let i = self.integer()?;
             offset = Some(i);
         }
 
         if self.matches(TokenKind::For)? {
-            self.consume(
-                TokenKind::Update,
-                "Expected update after lock condition in select",
-            )?;
-            locking = true
+            let mode = if self.matches(TokenKind::Share)? {
+                LockMode::Share
+            } else {
+                self.consume(
+                    TokenKind::Update,
+                    "Expected update or share after lock condition in select",
+                )?;
+                LockMode::Update
+            };
+            let wait = if self.matches(TokenKind::Nowait)? {
+                LockWait::NoWait
+            } else if self.matches(TokenKind::Skip)? {
+                self.consume(TokenKind::Locked, "Expected locked after skip")?;
+                LockWait::SkipLocked
+            } else {
+                LockWait::Wait
+            };
+            locking = Some(Locking { mode, wait })
         }
 
+        self.reference_columns(relations, enclosing);
+
         Ok(SqlExpression::Select {
             columns,
             from,
+            join_table,
+            join_condition,
             condition,
+            group_by,
+            having,
             order_by,
+            descending,
             limit,
             offset,
             locking,
@@ -1203,20 +2099,60 @@ impl Parser {
 
     fn select_clause(&mut self) -> Res<SelectItem> {
         if self.matches(TokenKind::Count)? {
-            self.consume(TokenKind::LeftParen, "Expected ( after count")?;
-            let item = self.parse_select_item()?;
-            self.consume(TokenKind::RightParen, "Expected ) after count")?;
-            Ok(SelectItem::Count(item))
+            self.count_clause()
+        } else if self.matches(TokenKind::Sum)? {
+            Ok(SelectItem::Sum(self.aggregate_item("sum")?))
+        } else if self.matches(TokenKind::Min)? {
+            Ok(SelectItem::Min(self.aggregate_item("min")?))
+        } else if self.matches(TokenKind::Max)? {
+            Ok(SelectItem::Max(self.aggregate_item("max")?))
+        } else if self.matches(TokenKind::Avg)? {
+            Ok(SelectItem::Avg(self.aggregate_item("avg")?))
         } else {
             Ok(SelectItem::Column(self.parse_select_item()?))
         }
     }
 
+    fn count_clause(&mut self) -> Res<SelectItem> {
+        self.consume(TokenKind::LeftParen, "Expected ( after count")?;
+        if !self.matches(TokenKind::Distinct)? {
+            let item = self.parse_select_item()?;
+            self.consume(TokenKind::RightParen, "Expected ) after count")?;
+            return Ok(SelectItem::Count(item));
+        }
+        let item = self.parse_select_item()?;
+        if item == Item::Wildcard {
+            return Err(ParserErrorKind::Unexpected(
+                "Expected column in count distinct".to_string(),
+            ));
+        }
+        self.consume(TokenKind::RightParen, "Expected ) after count")?;
+        Ok(SelectItem::CountDistinct(item))
+    }
+
+    fn aggregate_item(&mut self, aggregate: &str) -> Res<Item> {
+        self.consume(
+            TokenKind::LeftParen,
+            &format!("Expected ( after {aggregate}"),
+        )?;
+        let item = self.parse_select_item()?;
+        if item == Item::Wildcard {
+            return Err(ParserErrorKind::Unexpected(format!(
+                "Expected column in {aggregate}"
+            )));
+        }
+        self.consume(
+            TokenKind::RightParen,
+            &format!("Expected ) after {aggregate}"),
+        )?;
+        Ok(item)
+    }
+
     fn parse_select_item(&mut self) -> Res<Item> {
         if self.matches(TokenKind::Star)? {
             Ok(Item::Wildcard)
         } else if self.matches(TokenKind::Identifier)? {
-            Ok(Item::Column(self.make_variable().name))
+            Ok(Item::Column(self.column_reference()?.name))
         } else {
             Err(ParserErrorKind::Unexpected(format!(
                 "Expected select clause, got a {:?} instead",
@@ -1226,8 +2162,10 @@ impl Parser {
     }
 
     fn update(&mut self) -> Res<SqlExpression> {
+        let enclosing = mem::take(&mut self.column_references);
         self.consume(TokenKind::Identifier, "expected relation for update")?;
         let relation = self.make_variable();
+        let relation_token = self.previous.clone();
 
         self.consume(TokenKind::Set, "Expected set for update expression")?;
 
@@ -1244,23 +2182,62 @@ impl Parser {
             condition = Some(Box::new(self.sql_assignment()?));
         }
 
+        let mut returning = vec![];
+        if self.matches(TokenKind::Returning)? {
+            loop {
+                returning.push(self.returning_item()?);
+
+                if !self.matches(TokenKind::Comma)? {
+                    break;
+                }
+            }
+        }
+
+        self.reference_columns(vec![relation_token], enclosing);
+
         Ok(SqlExpression::Update {
             relation,
             updates,
             condition,
+            returning,
         })
     }
 
+    fn returning_item(&mut self) -> Res<ReturningItem> {
+        if self.check(TokenKind::Identifier)
+            && ["old", "new"].contains(&self.current.lexeme.as_str())
+        {
+            self.advance()?;
+            let version = self.previous.lexeme.clone();
+            self.consume(
+                TokenKind::Dot,
+                &format!("Expected . after {version} in returning clause"),
+            )?;
+            let item = self.parse_select_item()?;
+            if version == "old" {
+                Ok(ReturningItem::Old(item))
+            } else {
+                Ok(ReturningItem::New(item))
+            }
+        } else {
+            Ok(ReturningItem::New(self.parse_select_item()?))
+        }
+    }
+
     fn delete(&mut self) -> Res<SqlExpression> {
         self.consume(TokenKind::From, "Expected from clause")?;
+        let enclosing = mem::take(&mut self.column_references);
         self.consume(TokenKind::Identifier, "expect relation for update")?;
         let relation = self.make_variable();
+        let relation_token = self.previous.clone();
 
         let mut condition = None;
         if self.matches(TokenKind::Where)? {
             condition = Some(Box::new(self.sql_assignment()?));
         }
 
+        self.reference_columns(vec![relation_token], enclosing);
+
         Ok(SqlExpression::Delete {
             relation,
             condition,
@@ -1268,7 +2245,10 @@ impl Parser {
     }
 
     fn create(&mut self) -> Res<SqlExpression> {
-        self.consume(TokenKind::Unique, "Expected unique after create")?;
+        if self.matches(TokenKind::Table)? {
+            return self.create_table();
+        }
+        self.consume(TokenKind::Unique, "Expected unique or table after create")?;
         self.consume(TokenKind::Index, "Expected index after create unique")?;
         self.consume(TokenKind::On, "Expected on after create unique index")?;
 
@@ -1283,6 +2263,69 @@ impl Parser {
             "Expected column declaration after relation in insert into",
         )?;
 
+        let columns = self.index_columns(&relation)?;
+        Ok(SqlExpression::Create {
+            relation,
+            columns,
+            primary: false,
+        })
+    }
+
+    fn create_table(&mut self) -> Res<SqlExpression> {
+        self.consume(TokenKind::Identifier, "Expected table name to create")?;
+        let relation = self.make_variable();
+        let table = self.previous.clone();
+
+        self.consume(TokenKind::LeftParen, "Expected ( after table name")?;
+        let mut columns = vec![];
+        while self.matches(TokenKind::Identifier)? {
+            let column = self.make_variable();
+            let Some(kind) = parse_type(&self.current.lexeme) else {
+                return Err(ParserErrorKind::Unexpected(
+                    "Expected column type: int, string or bool".to_string(),
+                ));
+            };
+            self.advance()?;
+            let mut default = None;
+            if self.matches(TokenKind::Default)? {
+                default = Some(self.sql_additive()?);
+                if !self.column_references.is_empty() {
+                    self.column_references.clear();
+                    return Err(ParserErrorKind::Unexpected(
+                        "Default value cannot reference a column".to_string(),
+                    ));
+                }
+            }
+            let mut check = None;
+            if self.matches(TokenKind::Check)? {
+                self.consume(TokenKind::LeftParen, "Expected ( after check")?;
+                let enclosing = mem::take(&mut self.column_references);
+                check = Some(self.sql_assignment()?);
+                self.reference_columns(vec![table.clone()], enclosing);
+                self.consume(TokenKind::RightParen, "Expected ) closing check")?;
+            }
+            columns.push(ColumnDefinition {
+                name: column,
+                kind,
+                default,
+                check,
+            });
+
+            if !self.matches(TokenKind::Comma)? {
+                break;
+            }
+        }
+        self.consume(
+            TokenKind::RightParen,
+            "Expected ) closing columns declaration",
+        )?;
+
+        let names: Vec<_> = columns.iter().map(|column| column.name.clone()).collect();
+        self.declare_columns(&relation, &names);
+        Ok(SqlExpression::CreateTable { relation, columns })
+    }
+
+    fn index_columns(&mut self, relation: &Variable) -> Res<Vec<Variable>> {
         let mut columns = vec![];
         while self.matches(TokenKind::Identifier)? {
             columns.push(self.make_variable());
@@ -1296,7 +2339,35 @@ impl Parser {
             "Expected ) closing columns declaration",
         )?;
 
-        Ok(SqlExpression::Create { relation, columns })
+        self.declare_columns(relation, &columns);
+        Ok(columns)
+    }
+
+    fn lock_table(&mut self) -> Res<SqlExpression> {
+        self.consume(TokenKind::Table, "Expected table after lock")?;
+
+        self.consume(TokenKind::Identifier, "Expected table name to lock")?;
+        let relation = self.make_variable();
+
+        self.consume(TokenKind::In, "Expected in after lock table name")?;
+        self.consume(TokenKind::Exclusive, "Expected exclusive lock mode")?;
+        self.consume(TokenKind::Mode, "Expected mode after lock mode")?;
+
+        Ok(SqlExpression::LockTable(relation))
+    }
+
+    fn drop(&mut self) -> Res<SqlExpression> {
+        self.consume(TokenKind::Table, "Expected table after drop")?;
+
+        self.consume(TokenKind::Identifier, "Expected table name to drop")?;
+        Ok(SqlExpression::Drop(self.make_variable()))
+    }
+
+    fn truncate(&mut self) -> Res<SqlExpression> {
+        self.matches(TokenKind::Table)?;
+
+        self.consume(TokenKind::Identifier, "Expected table name to truncate")?;
+        Ok(SqlExpression::Truncate(self.make_variable()))
     }
 
     fn alter(&mut self) -> Res<SqlExpression> {
@@ -1306,7 +2377,23 @@ impl Parser {
         let relation = self.make_variable();
 
         self.consume(TokenKind::Add, "Expected add after alter table name")?;
-        self.consume(TokenKind::Constraint, "Expected constraint after add")?;
+        if self.matches(TokenKind::Primary)? {
+            self.consume(TokenKind::Key, "Expected key after primary")?;
+            self.consume(
+                TokenKind::LeftParen,
+                "Expected column declaration after primary key",
+            )?;
+            let columns = self.index_columns(&relation)?;
+            return Ok(SqlExpression::Create {
+                relation,
+                columns,
+                primary: true,
+            });
+        }
+        self.consume(
+            TokenKind::Constraint,
+            "Expected constraint or primary key after add",
+        )?;
 
         self.consume(TokenKind::Identifier, "Expected constraint name to alter")?;
         let constraint_name = self.make_variable();
@@ -1358,12 +2445,22 @@ impl Parser {
             "Expected ) closing columns declaration",
         )?;
 
+        let on_delete_cascade = self.matches(TokenKind::On)?;
+        if on_delete_cascade {
+            self.consume(TokenKind::Delete, "Expected delete after on")?;
+            self.consume(TokenKind::Cascade, "Expected cascade after on delete")?;
+        }
+
+        self.declare_columns(&relation, &columns);
+        self.declare_columns(&reference_relation, &reference_columns);
+
         Ok(SqlExpression::Alter {
             constraint_name,
             relation,
             columns,
             reference_relation,
             reference_columns,
+            on_delete_cascade,
         })
     }
 
@@ -1372,6 +2469,7 @@ impl Parser {
 
         self.consume(TokenKind::Identifier, "Expected relation after insert into")?;
         let relation = self.make_variable();
+        let relation_token = self.previous.clone();
 
         self.consume(
             TokenKind::LeftParen,
@@ -1391,27 +2489,77 @@ impl Parser {
             TokenKind::RightParen,
             "Expected ) closing columns declaration",
         )?;
-        self.consume(
-            TokenKind::Values,
-            "Expected values after relation declaration",
-        )?;
 
-        let mut values = vec![];
-        while self.matches_forward(TokenKind::LeftParen)? {
-            values.push(self.sql_tuple()?);
+        let source = if self.matches(TokenKind::Select)? {
+            InsertSource::Select(Box::new(self.union()?))
+        } else {
+            self.consume(
+                TokenKind::Values,
+                "Expected values or select after relation declaration",
+            )?;
 
-            if !self.matches(TokenKind::Comma)? {
-                break;
+            let enclosing = mem::take(&mut self.column_references);
+            let mut values = vec![];
+            while self.matches_forward(TokenKind::LeftParen)? {
+                values.push(self.sql_tuple()?);
+
+                if !self.matches(TokenKind::Comma)? {
+                    break;
+                }
             }
-        }
+            self.reference_columns(vec![relation_token.clone()], enclosing);
+            InsertSource::Values(values)
+        };
+        self.declare_columns(&relation, &columns);
+
+        let on_conflict = if self.matches(TokenKind::On)? {
+            self.consume(TokenKind::Conflict, "Expected conflict after on")?;
+            Some(self.on_conflict(relation_token)?)
+        } else {
+            None
+        };
 
         Ok(SqlExpression::Insert {
             relation,
             columns,
-            values,
+            source,
+            on_conflict,
         })
     }
 
+    fn on_conflict(&mut self, relation_token: Token) -> Res<OnConflict> {
+        if !self.matches(TokenKind::LeftParen)? {
+            self.consume(TokenKind::Do, "Expected do after on conflict")?;
+            self.consume(TokenKind::Nothing, "Expected nothing after on conflict do")?;
+            return Ok(OnConflict::Nothing);
+        }
+
+        let enclosing = mem::take(&mut self.column_references);
+        let mut columns = vec![];
+        while self.matches(TokenKind::Identifier)? {
+            columns.push(self.column_reference()?);
+
+            if !self.matches(TokenKind::Comma)? {
+                break;
+            }
+        }
+        self.consume(TokenKind::RightParen, "Expected ) closing conflict columns")?;
+        self.consume(TokenKind::Do, "Expected do after conflict columns")?;
+        self.consume(TokenKind::Update, "Expected update after on conflict do")?;
+        self.consume(TokenKind::Set, "Expected set after do update")?;
+
+        let mut updates = vec![];
+        loop {
+            updates.push(self.sql_assignment()?);
+            if !self.matches(TokenKind::Comma)? {
+                break;
+            }
+        }
+        self.reference_columns(vec![relation_token], enclosing);
+
+        Ok(OnConflict::Update { columns, updates })
+    }
+
     fn make_variable(&mut self) -> Variable {
         Variable {
             name: self.previous.lexeme.clone(),
@@ -1419,7 +2567,7 @@ impl Parser {
     }
 
     fn skip_newlines(&mut self) -> Unit {
-        while self.current.kind == TokenKind::Newline {
+        while matches!(self.current.kind, TokenKind::Newline | TokenKind::Semicolon) {
             self.advance()?;
         }
 
@@ -1433,8 +2581,13 @@ impl std::fmt::Display for SqlExpression {
             SqlExpression::Select {
                 columns,
                 from,
+                join_table,
+                join_condition,
                 condition,
+                group_by,
+                having,
                 order_by,
+                descending,
                 limit,
                 offset,
                 locking,
@@ -1451,12 +2604,34 @@ impl std::fmt::Display for SqlExpression {
 
                 f.write_fmt(format_args!(" from {}", from.name))?;
 
+                if let (Some(table), Some(cond)) = (join_table, join_condition) {
+                    f.write_fmt(format_args!(" join {} on {cond}", table.name))?;
+                }
+
                 if let Some(cond) = condition {
                     f.write_fmt(format_args!(" where {cond}"))?;
                 }
 
+                if !group_by.is_empty() {
+                    f.write_str(" group by ")?;
+                    let mut iter = group_by.iter().peekable();
+                    while let Some(item) = iter.next() {
+                        std::fmt::Display::fmt(item, f)?;
+                        if iter.peek().is_some() {
+                            f.write_str(", ")?;
+                        }
+                    }
+                }
+
+                if let Some(having) = having {
+                    f.write_fmt(format_args!(" having {having}"))?;
+                }
+
                 if let Some(order) = order_by {
                     f.write_fmt(format_args!(" order by {order}"))?;
+                    if *descending {
+                        f.write_str(" desc")?;
+                    }
                 }
 
                 if let Some(lim) = limit {
@@ -1467,8 +2642,16 @@ impl std::fmt::Display for SqlExpression {
                     f.write_fmt(format_args!(" offset {off}"))?;
                 }
 
-                if *locking {
-                    f.write_str(" for update")?;
+                if let Some(Locking { mode, wait }) = locking {
+                    match mode {
+                        LockMode::Update => f.write_str(" for update")?,
+                        LockMode::Share => f.write_str(" for share")?,
+                    }
+                    match wait {
+                        LockWait::Wait => {}
+                        LockWait::NoWait => f.write_str(" nowait")?,
+                        LockWait::SkipLocked => f.write_str(" skip locked")?,
+                    }
                 }
 
                 Ok(())
@@ -1477,6 +2660,7 @@ impl std::fmt::Display for SqlExpression {
                 relation,
                 updates,
                 condition,
+                returning,
             } => {
                 f.write_fmt(format_args!("update {} set ", relation.name))?;
 
@@ -1486,20 +2670,28 @@ impl std::fmt::Display for SqlExpression {
                     f.write_fmt(format_args!(" where {cond}"))?;
                 }
 
+                if !returning.is_empty() {
+                    f.write_str(" returning ")?;
+                    intersperse(f, returning, ",")?;
+                }
+
                 Ok(())
             }
             SqlExpression::Insert {
                 relation,
                 columns,
-                values,
+                source,
+                on_conflict,
             } => {
                 f.write_fmt(format_args!("insert {} (", relation.name))?;
 
                 intersperse(f, columns, ",")?;
 
-                f.write_str(") values ")?;
+                f.write_fmt(format_args!(") {source}"))?;
 
-                intersperse(f, values, ",")?;
+                if let Some(on_conflict) = on_conflict {
+                    f.write_fmt(format_args!(" {on_conflict}"))?;
+                }
 
                 Ok(())
             }
@@ -1515,19 +2707,51 @@ impl std::fmt::Display for SqlExpression {
 
                 Ok(())
             }
-            SqlExpression::Create { relation, columns } => {
-                f.write_fmt(format_args!("create unique index on {}(", relation.name))?;
+            SqlExpression::Create {
+                relation,
+                columns,
+                primary,
+            } => {
+                if *primary {
+                    f.write_fmt(format_args!(
+                        "alter table {} add primary key(",
+                        relation.name
+                    ))?;
+                } else {
+                    f.write_fmt(format_args!("create unique index on {}(", relation.name))?;
+                }
 
                 intersperse(f, columns, ",")?;
 
                 f.write_str(")")
             }
+            SqlExpression::Union { left, right, all } => {
+                let union = if *all { "union all" } else { "union" };
+                f.write_fmt(format_args!("{left} {union} {right}"))
+            }
+            SqlExpression::CreateTable { relation, columns } => {
+                f.write_fmt(format_args!("create table {} (", relation.name))?;
+                let columns: Vec<_> = columns.iter().map(ColumnDefinition::to_string).collect();
+                intersperse(f, &columns, ",")?;
+                f.write_str(")")
+            }
+            SqlExpression::LockTable(relation) => f.write_fmt(format_args!(
+                "lock table {} in exclusive mode",
+                relation.name
+            )),
+            SqlExpression::Drop(relation) => {
+                f.write_fmt(format_args!("drop table {}", relation.name))
+            }
+            SqlExpression::Truncate(relation) => {
+                f.write_fmt(format_args!("truncate {}", relation.name))
+            }
             SqlExpression::Alter {
                 constraint_name,
                 relation,
                 columns,
                 reference_relation,
                 reference_columns,
+                on_delete_cascade,
             } => {
                 f.write_fmt(format_args!(
                     "alter table {} add constraint {} foreign key(",
@@ -1536,11 +2760,17 @@ impl std::fmt::Display for SqlExpression {
 
                 intersperse(f, columns, ",")?;
 
-                f.write_fmt(format_args!(") references {}", reference_relation.name))?;
+                f.write_fmt(format_args!(") references {}(", reference_relation.name))?;
 
                 intersperse(f, reference_columns, ",")?;
 
-                f.write_char(')')
+                f.write_char(')')?;
+
+                if *on_delete_cascade {
+                    f.write_str(" on delete cascade")?;
+                }
+
+                Ok(())
             }
             SqlExpression::Binary {
                 left,
@@ -1555,12 +2785,17 @@ impl std::fmt::Display for SqlExpression {
                     SqlOperator::Rem => "%",
                     SqlOperator::Equal => "=",
                     SqlOperator::And => "and",
+                    SqlOperator::Or => "or",
                     SqlOperator::In => "in",
+                    SqlOperator::NotIn => "not in",
                     SqlOperator::NotEqual => "<>",
                     SqlOperator::Less => "<",
                     SqlOperator::LessEqual => "<=",
                     SqlOperator::Greater => ">",
                     SqlOperator::GreaterEqual => ">=",
+                    SqlOperator::Is => "is",
+                    SqlOperator::IsNot => "is not",
+                    SqlOperator::Like => "like",
                     SqlOperator::Between => {
                         if let SqlExpression::Tuple(tuples) = right.as_ref() {
                             return f.write_fmt(format_args!(
@@ -1577,6 +2812,8 @@ impl std::fmt::Display for SqlExpression {
             SqlExpression::Assignment(var, expr) => {
                 f.write_fmt(format_args!("{} := {expr}", var.name))
             }
+            SqlExpression::Not(expr) => f.write_fmt(format_args!("not {expr}")),
+            SqlExpression::Subquery(select) => f.write_fmt(format_args!("({select})")),
             SqlExpression::Integer(i) => std::fmt::Display::fmt(&i, f),
             SqlExpression::Tuple(values) => {
                 f.write_str("(")?;
@@ -1586,6 +2823,7 @@ impl std::fmt::Display for SqlExpression {
                 f.write_str(")")
             }
             SqlExpression::Var(v) => std::fmt::Display::fmt(&v.name, f),
+            SqlExpression::Aggregate(item) => std::fmt::Display::fmt(item, f),
             SqlExpression::UpVariable(v) => f.write_fmt(format_args!("${}", v.name)),
             SqlExpression::Value(_) => panic!("no value formatting"),
             SqlExpression::Set(members) => {
@@ -1596,6 +2834,7 @@ impl std::fmt::Display for SqlExpression {
                 f.write_str(")")
             }
             SqlExpression::String(s) => f.write_str(s),
+            SqlExpression::Null => f.write_str("null"),
             SqlExpression::Bool(_) => panic!(),
             SqlExpression::Scalar(expr) => {
                 f.write_str("(")?;
@@ -1652,11 +2891,21 @@ impl std::fmt::Display for Expression {
                 f.write_fmt(format_args!("{}.{}", call_site, member.name))
             }
             Expression::String(s) => f.write_str(s),
+            Expression::Null => f.write_str("null"),
             Expression::Scalar(expr) => {
                 f.write_str("(")?;
                 std::fmt::Display::fmt(expr, f)?;
                 f.write_str(")")
             }
+            Expression::ToSet(expr) => f.write_fmt(format_args!("set({expr})")),
+            Expression::Forall(variable, domain, body) => f.write_fmt(format_args!(
+                "forall {} in {domain} do ({body})",
+                variable.name
+            )),
+            Expression::Exists(variable, domain, body) => f.write_fmt(format_args!(
+                "exists {} in {domain} do ({body})",
+                variable.name
+            )),
         }
     }
 }
@@ -1664,19 +2913,35 @@ impl std::fmt::Display for Expression {
 impl std::fmt::Display for Statement {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
-            Statement::Begin(level, Some(tx_name)) => {
-                f.write_fmt(format_args!("begin {level} ({})", tx_name.name))
+            Statement::Begin(level, tx_name, read_only) => {
+                f.write_fmt(format_args!("begin {level}"))?;
+                if *read_only {
+                    f.write_str(" read only")?;
+                }
+                match tx_name {
+                    Some(tx_name) => f.write_fmt(format_args!(" ({})", tx_name.name)),
+                    None => Ok(()),
+                }
             }
-            Statement::Begin(level, None) => f.write_fmt(format_args!("begin {level}")),
             Statement::Commit => f.write_str("commit"),
             Statement::Abort => f.write_str("abort"),
+            Statement::Savepoint(name) => f.write_fmt(format_args!("savepoint {name}")),
+            Statement::RollbackTo(name) => f.write_fmt(format_args!("rollback to {name}")),
             Statement::Expression(expr) => std::fmt::Display::fmt(&expr, f),
             Statement::Latch => f.write_str("latch"),
+            Statement::Await(expr) => f.write_fmt(format_args!("await({expr})")),
             Statement::Always(expr) => f.write_fmt(format_args!("always({expr})")),
             Statement::Never(expr) => f.write_fmt(format_args!("never({expr})")),
             Statement::Eventually(expr) => f.write_fmt(format_args!("eventually({expr})")),
+            Statement::LeadsTo(expr, consequent) => {
+                f.write_fmt(format_args!("always({expr} ~> {consequent})"))
+            }
             Statement::If(expr, _) => f.write_fmt(format_args!("if {expr} do")),
             Statement::Else(_) => f.write_str("else"),
+            Statement::Either(_) => f.write_str("either"),
+            Statement::Or(_) => f.write_str("or"),
+            Statement::While(expr, _) => f.write_fmt(format_args!("while {expr} do")),
+            Statement::EndWhile(_, _) => f.write_str("end"),
         }
     }
 }
@@ -1684,9 +2949,37 @@ impl std::fmt::Display for Statement {
 #[cfg(test)]
 mod test {
     use crate::parser::{
-        Expression, Operator, Parser, SqlExpression, SqlOperator, Statement, Variable,
+        ColumnDefinition, Expression, Offset, Operator, Parser, SqlExpression, SqlOperator,
+        Statement, Type, Variable,
     };
 
+    fn offset(offset: usize) -> Offset {
+        let res = Offset::default();
+        res.set(offset);
+        res
+    }
+
+    fn variable(name: &str) -> Variable {
+        Variable {
+            name: name.to_string(),
+        }
+    }
+
+    fn equals(name: &str, value: i64) -> Expression {
+        Expression::Binary {
+            left: Box::new(Expression::Var(variable(name))),
+            operator: Operator::Equal,
+            right: Box::new(Expression::Integer(value)),
+        }
+    }
+
+    fn assign(name: &str, value: i64) -> Statement {
+        Statement::Expression(Expression::Assignment(
+            variable(name),
+            Box::new(Expression::Integer(value)),
+        ))
+    }
+
     #[test]
     fn parse_sql_query() {
         let mut parser = Parser::new(
@@ -1732,6 +3025,7 @@ mod test {
                         })),
                     }),
                 })),
+                returning: vec![],
             })),
             statements[0]
         );
@@ -1778,4 +3072,202 @@ mod test {
             statements[0]
         );
     }
+
+    #[test]
+    fn parse_sql_or_and_not() {
+        let mut parser =
+            Parser::new("`delete from users where id = 1 or not id = 2 and id = 3`\n".to_string());
+        parser.advance().unwrap();
+
+        let equals = |i| SqlExpression::Binary {
+            left: Box::new(SqlExpression::Var(Variable {
+                name: "id".to_string(),
+            })),
+            operator: SqlOperator::Equal,
+            right: Box::new(SqlExpression::Integer(i)),
+        };
+        let mut statements = vec![];
+        parser.statement(&mut statements).unwrap();
+        assert_eq!(
+            Statement::Expression(Expression::Sql(SqlExpression::Delete {
+                relation: Variable {
+                    name: "users".to_string()
+                },
+                condition: Some(Box::new(SqlExpression::Binary {
+                    left: Box::new(equals(1)),
+                    operator: SqlOperator::Or,
+                    right: Box::new(SqlExpression::Binary {
+                        left: Box::new(SqlExpression::Not(Box::new(equals(2)))),
+                        operator: SqlOperator::And,
+                        right: Box::new(equals(3)),
+                    }),
+                })),
+            })),
+            statements[0]
+        );
+    }
+
+    #[test]
+    fn parse_negative_integers() {
+        let mut parser = Parser::new("a - -2 * -3\n".to_string());
+        parser.advance().unwrap();
+
+        let mut statements = vec![];
+        parser.statement(&mut statements).unwrap();
+        assert_eq!(
+            Statement::Expression(Expression::Binary {
+                left: Box::new(Expression::Var(Variable {
+                    name: "a".to_string()
+                })),
+                operator: Operator::Subtract,
+                right: Box::new(Expression::Binary {
+                    left: Box::new(Expression::Integer(-2)),
+                    operator: Operator::Multiply,
+                    right: Box::new(Expression::Integer(-3)),
+                }),
+            }),
+            statements[0]
+        );
+    }
+
+    #[test]
+    fn parse_else_if_chain() {
+        let mut parser = Parser::new(
+            "if x = 1 do\n    a := 1\nelse if x = 2 do\n    a := 2\nelse\n    a := 3\nend\n"
+                .to_string(),
+        );
+        parser.advance().unwrap();
+
+        let mut statements = vec![];
+        parser.statement(&mut statements).unwrap();
+        assert_eq!(
+            vec![
+                Statement::If(equals("x", 1), offset(3)),
+                assign("a", 1),
+                Statement::Else(offset(5)),
+                Statement::If(equals("x", 2), offset(3)),
+                assign("a", 2),
+                Statement::Else(offset(2)),
+                assign("a", 3),
+            ],
+            statements
+        );
+    }
+
+    #[test]
+    fn parse_else_if_without_else() {
+        let mut parser =
+            Parser::new("if x = 1 do\n    a := 1\nelse if x = 2 do\n    a := 2\nend\n".to_string());
+        parser.advance().unwrap();
+
+        let mut statements = vec![];
+        parser.statement(&mut statements).unwrap();
+        assert_eq!(
+            vec![
+                Statement::If(equals("x", 1), offset(3)),
+                assign("a", 1),
+                Statement::Else(offset(4)),
+                Statement::If(equals("x", 2), offset(3)),
+                assign("a", 2),
+                Statement::Else(offset(1)),
+            ],
+            statements
+        );
+    }
+
+    #[test]
+    fn parse_semicolon_separated_statements() {
+        let mets = Parser::new(
+            "process do\n    a := 1; b := 2\n    if a = 1 do; c := 3; end\nend\n".to_string(),
+        )
+        .compile()
+        .unwrap();
+        assert_eq!(
+            vec![vec![
+                assign("a", 1),
+                assign("b", 2),
+                Statement::If(equals("a", 1), offset(3)),
+                assign("c", 3),
+                Statement::Else(offset(1)),
+            ]],
+            mets.processes
+        );
+    }
+
+    #[test]
+    fn parse_create_table() {
+        let mets = Parser::new(
+            "init do
+    `create table users (id int check (id > 0), name text default 'anonymous')`
+end
+"
+            .to_string(),
+        )
+        .compile()
+        .unwrap();
+        assert_eq!(
+            vec![Statement::Expression(Expression::Sql(
+                SqlExpression::CreateTable {
+                    relation: Variable {
+                        name: "users".to_string()
+                    },
+                    columns: vec![
+                        ColumnDefinition {
+                            name: Variable {
+                                name: "id".to_string()
+                            },
+                            kind: Type::Integer,
+                            default: None,
+                            check: Some(SqlExpression::Binary {
+                                left: Box::new(SqlExpression::Var(Variable {
+                                    name: "id".to_string()
+                                })),
+                                operator: SqlOperator::Greater,
+                                right: Box::new(SqlExpression::Integer(0)),
+                            }),
+                        },
+                        ColumnDefinition {
+                            name: Variable {
+                                name: "name".to_string()
+                            },
+                            kind: Type::String,
+                            default: Some(SqlExpression::String("'anonymous'".to_string())),
+                            check: None,
+                        },
+                    ],
+                }
+            ))],
+            mets.init
+        );
+    }
+
+    #[test]
+    fn parse_either_branch_ending_with_expression() {
+        let mets = Parser::new(
+            "init do
+    `insert into users (id, age) values (1, 10)`
+end
+
+process do
+    either
+        `update users set age := 11 where id = 1`
+    or
+        `update users set age := 12 where id = 1`
+    end
+end
+"
+            .to_string(),
+        )
+        .compile()
+        .unwrap();
+        assert!(matches!(
+            mets.processes[0][..],
+            [
+                Statement::Either(_),
+                Statement::Expression(_),
+                Statement::Or(_),
+                Statement::Expression(_),
+            ]
+        ));
+    }
 }
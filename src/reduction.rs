@@ -0,0 +1,354 @@
+use crate::parser::{Expression, InsertSource, Mets, OnConflict, SqlExpression, Statement};
+use std::collections::HashSet;
+
+#[derive(Default, Debug)]
+struct Footprint {
+    tables: HashSet<String>,
+    written: HashSet<String>,
+    variables: HashSet<String>,
+    defined: HashSet<String>,
+    selected: HashSet<String>,
+    all: bool,
+}
+
+impl Footprint {
+    fn of<'a>(statements: impl IntoIterator<Item = &'a Statement>) -> Footprint {
+        let mut footprint = Footprint::default();
+        for statement in statements {
+            footprint.statement(statement);
+        }
+        footprint
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Begin(_, name, _) => {
+                if let Some(name) = name {
+                    self.define(&name.name);
+                }
+            }
+            Statement::Latch => self.all = true,
+            Statement::Expression(expr)
+            | Statement::Await(expr)
+            | Statement::If(expr, _)
+            | Statement::While(expr, _)
+            | Statement::EndWhile(expr, _)
+            | Statement::Always(expr)
+            | Statement::Never(expr)
+            | Statement::Eventually(expr) => self.expression(expr),
+            Statement::LeadsTo(cause, consequence) => {
+                self.expression(cause);
+                self.expression(consequence);
+            }
+            Statement::Commit
+            | Statement::Abort
+            | Statement::Savepoint(_)
+            | Statement::RollbackTo(_)
+            | Statement::Else(_)
+            | Statement::Either(_)
+            | Statement::Or(_) => {}
+        }
+    }
+
+    fn write(&mut self, table: &str) {
+        self.written.insert(table.to_string());
+        self.tables.insert(table.to_string());
+    }
+
+    fn define(&mut self, name: &str) {
+        self.defined.insert(name.to_string());
+        self.variables.insert(name.to_string());
+    }
+
+    fn expression(&mut self, expr: &Expression) {
+        match expr {
+            Expression::Sql(sql) => self.sql(sql),
+            Expression::Binary { left, right, .. } => {
+                self.expression(left);
+                self.expression(right);
+            }
+            Expression::Member { call_site, .. } => self.expression(call_site),
+            Expression::Assignment(variable, expr) => {
+                self.define(&variable.name);
+                self.expression(expr);
+            }
+            Expression::Var(variable) => {
+                self.variables.insert(variable.name.clone());
+            }
+            Expression::Set(members) | Expression::Tuple(members) => {
+                for member in members {
+                    self.expression(member);
+                }
+            }
+            Expression::Scalar(expr) | Expression::ToSet(expr) => self.expression(expr),
+            Expression::Forall(variable, set, condition)
+            | Expression::Exists(variable, set, condition) => {
+                self.define(&variable.name);
+                self.expression(set);
+                self.expression(condition);
+            }
+            Expression::Integer(_) | Expression::String(_) | Expression::Null => {}
+        }
+    }
+
+    fn sql(&mut self, sql: &SqlExpression) {
+        match sql {
+            SqlExpression::Select {
+                from,
+                join_table,
+                join_condition,
+                condition,
+                having,
+                order_by,
+                ..
+            } => {
+                self.tables.insert(from.name.clone());
+                self.selected.insert(from.name.clone());
+                if let Some(join_table) = join_table {
+                    self.tables.insert(join_table.name.clone());
+                    self.selected.insert(join_table.name.clone());
+                }
+                for expr in [join_condition, condition, having, order_by]
+                    .into_iter()
+                    .flatten()
+                {
+                    self.sql(expr);
+                }
+            }
+            SqlExpression::Update {
+                relation,
+                updates,
+                condition,
+                ..
+            } => {
+                self.write(&relation.name);
+                for update in updates {
+                    self.sql(update);
+                }
+                if let Some(condition) = condition {
+                    self.sql(condition);
+                }
+            }
+            SqlExpression::Delete {
+                relation,
+                condition,
+            } => {
+                self.write(&relation.name);
+                if let Some(condition) = condition {
+                    self.sql(condition);
+                }
+            }
+            SqlExpression::Insert {
+                relation,
+                source,
+                on_conflict,
+                ..
+            } => {
+                self.write(&relation.name);
+                match source {
+                    InsertSource::Values(values) => {
+                        for value in values {
+                            self.sql(value);
+                        }
+                    }
+                    InsertSource::Select(select) => self.sql(select),
+                }
+                if let Some(OnConflict::Update { updates, .. }) = on_conflict {
+                    for update in updates {
+                        self.sql(update);
+                    }
+                }
+            }
+            SqlExpression::Create { relation, .. }
+            | SqlExpression::CreateTable { relation, .. }
+            | SqlExpression::LockTable(relation)
+            | SqlExpression::Drop(relation)
+            | SqlExpression::Truncate(relation) => self.write(&relation.name),
+            SqlExpression::Alter {
+                relation,
+                reference_relation,
+                ..
+            } => {
+                self.write(&relation.name);
+                self.write(&reference_relation.name);
+            }
+            SqlExpression::Binary { left, right, .. }
+            | SqlExpression::Union { left, right, .. } => {
+                self.sql(left);
+                self.sql(right);
+            }
+            SqlExpression::Not(expr)
+            | SqlExpression::Subquery(expr)
+            | SqlExpression::Scalar(expr)
+            | SqlExpression::Assignment(_, expr) => self.sql(expr),
+            SqlExpression::Tuple(members) | SqlExpression::Set(members) => {
+                for member in members {
+                    self.sql(member);
+                }
+            }
+            SqlExpression::UpVariable(variable) => {
+                self.variables.insert(variable.name.clone());
+            }
+            SqlExpression::Var(_)
+            | SqlExpression::Integer(_)
+            | SqlExpression::String(_)
+            | SqlExpression::Null
+            | SqlExpression::Bool(_)
+            | SqlExpression::Aggregate(_)
+            | SqlExpression::Value(_) => {}
+        }
+    }
+}
+
+// Tables read by the selects of a statement, including subqueries
+pub fn selected_tables(statement: &Statement) -> HashSet<String> {
+    Footprint::of([statement]).selected
+}
+
+pub fn accessed_tables<'a>(statements: impl IntoIterator<Item = &'a Statement>) -> HashSet<String> {
+    Footprint::of(statements).tables
+}
+
+// Variables used by the statements, and the ones they define
+pub fn variables<'a>(
+    statements: impl IntoIterator<Item = &'a Statement>,
+) -> (HashSet<String>, HashSet<String>) {
+    let footprint = Footprint::of(statements);
+    (footprint.variables, footprint.defined)
+}
+
+// Tables linked by a foreign key are accessed together by inserts, updates and deletes
+fn linked_tables(mets: &Mets) -> Vec<(String, String)> {
+    let mut links = vec![];
+    for statement in mets.init.iter().chain(mets.processes.iter().flatten()) {
+        if let Statement::Expression(Expression::Sql(SqlExpression::Alter {
+            relation,
+            reference_relation,
+            ..
+        })) = statement
+        {
+            links.push((relation.name.clone(), reference_relation.name.clone()));
+        }
+    }
+    links
+}
+
+fn close_tables(tables: &mut HashSet<String>, links: &[(String, String)]) {
+    loop {
+        let mut changed = false;
+        for (a, b) in links {
+            if tables.contains(a) || tables.contains(b) {
+                changed |= tables.insert(a.clone());
+                changed |= tables.insert(b.clone());
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+}
+
+pub struct Reduction {
+    independent: Vec<Vec<bool>>,
+    visible: Vec<Vec<bool>>,
+}
+
+impl Reduction {
+    pub fn new(mets: &Mets) -> Reduction {
+        let links = linked_tables(mets);
+        let constants: HashSet<_> = mets.constants.iter().map(|(v, _)| &v.name).collect();
+
+        let mut shared = Footprint::of(mets.globals.iter().chain(&mets.init)).defined;
+        let mut codes: Vec<_> = mets.processes.iter().map(Footprint::of).collect();
+        for code in &mut codes {
+            close_tables(&mut code.tables, &links);
+            // Variables read but not defined by a process belong to another process
+            for name in code.variables.difference(&code.defined) {
+                if !constants.contains(name) {
+                    shared.insert(name.clone());
+                }
+            }
+        }
+
+        // Tables and variables read by the properties and the conditions of the processes
+        let conditions = mets.processes.iter().flatten().filter(|statement| {
+            matches!(
+                statement,
+                Statement::Await(_)
+                    | Statement::If(_, _)
+                    | Statement::While(_, _)
+                    | Statement::EndWhile(_, _)
+            )
+        });
+        let observed = Footprint::of(mets.properties.iter().chain(conditions));
+        let visible = mets
+            .processes
+            .iter()
+            .map(|code| {
+                code.iter()
+                    .map(|statement| {
+                        let mut step = Footprint::default();
+                        step.statement(statement);
+                        close_tables(&mut step.written, &links);
+                        step.all
+                            || !step.written.is_disjoint(&observed.tables)
+                            || !step.defined.is_disjoint(&observed.variables)
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let independent = mets
+            .processes
+            .iter()
+            .enumerate()
+            .map(|(idx, code)| {
+                code.iter()
+                    .map(|statement| {
+                        let mut step = Footprint::default();
+                        step.statement(statement);
+                        close_tables(&mut step.tables, &links);
+                        is_local(statement)
+                            && !step.all
+                            && codes.iter().enumerate().all(|(other, code)| {
+                                other == idx
+                                    || (!code.all
+                                        && step.tables.is_disjoint(&code.tables)
+                                        && step.variables.iter().all(|v| {
+                                            !shared.contains(v) || !code.variables.contains(v)
+                                        }))
+                            })
+                    })
+                    .collect()
+            })
+            .collect();
+        Reduction {
+            independent,
+            visible,
+        }
+    }
+
+    pub fn is_independent(&self, process: usize, pc: usize) -> bool {
+        self.independent[process][pc]
+    }
+
+    // A visible step can change the value of a property or of a condition
+    pub fn is_visible(&self, process: usize, pc: usize) -> bool {
+        self.visible[process][pc]
+    }
+}
+
+// Transaction boundaries, latches and awaits interact with other processes
+fn is_local(statement: &Statement) -> bool {
+    matches!(
+        statement,
+        Statement::Expression(_)
+            | Statement::If(_, _)
+            | Statement::While(_, _)
+            | Statement::EndWhile(_, _)
+            | Statement::Else(_)
+            | Statement::Either(_)
+            | Statement::Or(_)
+            | Statement::Savepoint(_)
+    )
+}
@@ -1,65 +1,822 @@
-use crate::engine::{Report, Violation};
-use crate::parser::Mets;
+use crate::engine::{Completeness, Graph, Options, Report, TransactionState, Value, Violation};
+use crate::parser::{Deadlocks, Expression, IsolationLevel, Mets, SqlExpression, Statement};
+use crate::reduction::selected_tables;
+use crate::scanner::Position;
+use crate::sql_interpreter::{Lock, Row, RowId};
+use crate::state::{ProcessState, RcState, State};
+use std::collections::{HashMap, HashSet};
+use std::io::{self, BufRead, Write};
 
-pub fn summary(mets: &Mets, report: &Report) -> String {
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub enum Format {
+    #[default]
+    Text,
+    Json,
+    Dot,
+    History,
+}
+
+pub struct Step<'a> {
+    pub process: usize,
+    pub statement: &'a Statement,
+    pub previous: RcState,
+    pub state: RcState,
+}
+
+pub fn trace<'a>(mets: &'a Mets, violation: &Violation) -> Vec<Step<'a>> {
+    let state = match violation {
+        Violation::PropertyViolation { state, .. }
+        | Violation::Deadlock { state, .. }
+        | Violation::Lasso { state, .. } => state,
+    };
+
+    let mut traces = vec![];
+    let mut current = state.clone();
+    loop {
+        traces.push(current.clone());
+        let parent = current.borrow().ancestors.first().cloned();
+        match parent {
+            Some(parent) => current = parent,
+            None => break,
+        }
+    }
+    traces.reverse();
+
+    let mut steps = vec![];
+    for (previous, state) in traces.iter().zip(&traces[1..]) {
+        if let Some((process, pc)) = state.borrow().transition {
+            steps.push(Step {
+                process,
+                statement: &mets.processes[process][pc],
+                previous: previous.clone(),
+                state: state.clone(),
+            });
+        }
+    }
+    steps
+}
+
+pub fn cycle<'a>(mets: &'a Mets, violation: &Violation) -> Vec<Step<'a>> {
+    match violation {
+        Violation::Lasso { cycle, .. } => cycle
+            .iter()
+            .enumerate()
+            .map(|(i, (process, from))| Step {
+                process: *process,
+                statement: &mets.processes[*process][from.borrow().pc[*process]],
+                previous: from.clone(),
+                state: cycle[(i + 1) % cycle.len()].1.clone(),
+            })
+            .collect(),
+        _ => vec![],
+    }
+}
+
+#[derive(Default)]
+struct Transaction {
+    reads: Vec<(usize, HashSet<String>)>,
+    writes: HashSet<(String, RowId)>,
+    commit: Option<usize>,
+}
+
+// Names the anomaly of a trace from the anti-dependencies of its transactions: a transaction
+// reading a table before another one commits a write to it did not see that write
+pub fn anomaly(steps: &[Step]) -> Option<&'static str> {
+    let mut transactions: Vec<Transaction> = vec![];
+    let mut open = HashMap::new();
+    for (i, step) in steps.iter().enumerate() {
+        let (index, snapshot) = match (step.statement, open.get(&step.process)) {
+            (Statement::Begin(level, _, _), _) => {
+                transactions.push(Transaction::default());
+                let snapshot = (*level != IsolationLevel::ReadCommitted).then_some(i);
+                open.insert(step.process, (transactions.len() - 1, snapshot));
+                continue;
+            }
+            (_, Some(open)) => *open,
+            (_, None) => {
+                transactions.push(Transaction::default());
+                (transactions.len() - 1, None)
+            }
+        };
+        let transaction = &mut transactions[index];
+        let tables = selected_tables(step.statement);
+        if !tables.is_empty() {
+            transaction.reads.push((snapshot.unwrap_or(i), tables));
+        }
+        let state = step.state.borrow();
+        let tx = &state.txs[step.process];
+        if open.contains_key(&step.process) {
+            if let Some(context) = tx.id.and_then(|id| state.sql.transactions.get(&id)) {
+                transaction.writes.extend(context.changed_rows());
+            }
+            match tx.state {
+                TransactionState::Running => continue,
+                TransactionState::Committed => transaction.commit = Some(i),
+                _ => transaction.writes.clear(),
+            }
+            open.remove(&step.process);
+        } else {
+            // Statements outside of a transaction commit right away
+            transaction.writes = step.previous.borrow().sql.changed_rows(&state.sql);
+            transaction.commit = Some(i);
+        }
+    }
+    // Transactions still running at the end of the trace are about to commit their writes
+    for (index, _) in open.into_values() {
+        transactions[index].commit = Some(steps.len());
+    }
+
+    let anti_dependent = |reader: usize, writer: usize| {
+        let (r, w) = (&transactions[reader], &transactions[writer]);
+        reader != writer
+            && w.commit.is_some_and(|commit| {
+                r.reads.iter().any(|(read, tables)| {
+                    *read < commit && w.writes.iter().any(|(table, _)| tables.contains(table))
+                })
+            })
+    };
+    let n = transactions.len();
+    let pairs: Vec<_> = (0..n)
+        .flat_map(|a| (a + 1..n).map(move |b| (a, b)))
+        .filter(|(a, b)| anti_dependent(*a, *b) && anti_dependent(*b, *a))
+        .collect();
+    if pairs.iter().any(|(a, b)| {
+        !transactions[*a]
+            .writes
+            .is_disjoint(&transactions[*b].writes)
+    }) {
+        return Some("lost update");
+    }
+    if !pairs.is_empty() {
+        return Some("write skew");
+    }
+
+    let edges: Vec<Vec<usize>> = (0..n)
+        .map(|a| (0..n).filter(|b| anti_dependent(a, *b)).collect())
+        .collect();
+    let mut visited = vec![false; n];
+    (0..n)
+        .any(|start| cyclic(&edges, start, &mut vec![], &mut visited))
+        .then_some("G2 anti-dependency cycle")
+}
+
+fn cyclic(edges: &[Vec<usize>], node: usize, path: &mut Vec<usize>, visited: &mut [bool]) -> bool {
+    if path.contains(&node) {
+        return true;
+    }
+    if visited[node] {
+        return false;
+    }
+    visited[node] = true;
+    path.push(node);
+    let res = edges[node]
+        .iter()
+        .any(|next| cyclic(edges, *next, path, visited));
+    path.pop();
+    res
+}
+
+pub fn summary(mets: &Mets, report: &Report, options: &Options) -> String {
     let mut base = if let Some(violation) = &report.violation {
         let mut x = String::new();
-        let state = match violation {
-            Violation::PropertyViolation { property, state } => {
+        let steps = trace(mets, violation);
+        match violation {
+            Violation::PropertyViolation { property, .. } | Violation::Lasso { property, .. } => {
                 x.push_str(&format!("Following property was violated: {property}\n"));
+                if let Some(anomaly) = anomaly(&steps) {
+                    x.push_str(&format!("Anomaly: {anomaly}\n"));
+                }
                 x.push_str("The following counter example was found:\n");
-                state.clone()
             }
             Violation::Deadlock { cycle, state } => {
-                x.push_str("System ran into a deadlock:\n");
-                for p in cycle {
-                    let borrowed_state = state.borrow();
-                    let tid = &borrowed_state.txs[*p].id.unwrap();
-                    let context = borrowed_state.sql.transactions.get(tid).unwrap();
-
-                    x.push_str(&format!(
-                        "Process {p} holds lock on {:?} and waits for {:?}\n",
-                        context.locks, borrowed_state.processes[*p]
-                    ));
+                if mets.deadlocks == Deadlocks::Forbidden {
+                    x.push_str("Following property was violated: deadlock_free\n");
                 }
-                state.clone()
+                x.push_str("System ran into a deadlock:\n");
+                x.push_str(&wait_for(&state.borrow(), cycle));
             }
         };
 
-        let mut traces = vec![];
-        let mut current = state;
-        loop {
-            traces.push(current.clone());
-            let x = if let Some(x) = current.borrow().ancestors.get(0) {
-                x.clone()
-            } else {
-                break;
-            };
-            current = x;
-        }
-        traces.reverse();
-
-        let mut last_trace = traces[0].borrow();
-
-        for trace in &traces[1..] {
-            let trace = trace.borrow();
-            if let Some((index, _)) = (trace.pc.iter().zip(&last_trace.pc))
-                .enumerate()
-                .find(|(_i, (a, b))| a != b)
-            {
-                x.push_str(&format!(
-                    "Process {}: {}\n",
-                    index,
-                    mets.processes[index][trace.pc[index] - 1]
-                ));
+        for step in &steps {
+            x.push_str(&format!("Process {}: {}\n", step.process, step.statement));
+            x.push_str(&locals(step));
+            x.push_str(&waiting(step));
+            if options.verbose {
+                x.push_str(&tables(&step.state));
+            }
+        }
+        let schedule = schedule(&steps);
+        if !schedule.is_empty() {
+            x.push_str("Schedule:\n");
+            for entry in schedule {
+                x.push_str(&format!("    {entry}\n"));
+            }
+        }
+
+        let cycle = cycle(mets, violation);
+        if !cycle.is_empty() {
+            x.push_str("Then the following steps repeat forever:\n");
+            for step in cycle {
+                x.push_str(&format!("Process {}: {}\n", step.process, step.statement));
+                x.push_str(&locals(&step));
+                x.push_str(&waiting(&step));
+                if options.verbose {
+                    x.push_str(&tables(&step.state));
+                }
             }
-            last_trace = trace;
         }
         x
+    } else if let Completeness::Incomplete { reason } = &report.completeness {
+        format!("Search incomplete: {reason}\nNo violation found in the explored states")
     } else {
         "No counter example found".to_string()
     };
 
     base.push_str(&format!("\nStates explored: {}", report.states_explored));
+    base.push_str(&format!(
+        "\nElapsed: {:.3}s ({:.0} states/s, {} distinct states)",
+        report.duration.as_secs_f64(),
+        report.states_per_second(),
+        report.visited_states
+    ));
     base
 }
+
+// Walks the counter example step by step, reading commands from the input
+pub fn interactive(
+    mets: &Mets,
+    report: &Report,
+    input: impl BufRead,
+    output: &mut impl Write,
+) -> io::Result<()> {
+    let Some(violation) = &report.violation else {
+        return Ok(());
+    };
+    let steps = trace(mets, violation);
+    let Some(first) = steps.first() else {
+        return Ok(());
+    };
+    let mut current = 0;
+    writeln!(output, "{}", interactive_step(&steps, first, current))?;
+    write!(output, "> ")?;
+    output.flush()?;
+    for line in input.lines() {
+        match line?.trim() {
+            "n" if current < steps.len() => current += 1,
+            "n" => writeln!(output, "End of the counter example")?,
+            "p" if current > 0 => current -= 1,
+            "p" => writeln!(output, "Start of the counter example")?,
+            "q" => return Ok(()),
+            _ => writeln!(
+                output,
+                "Unknown command, use n (next), p (previous) or q (quit)"
+            )?,
+        }
+        writeln!(output, "{}", interactive_step(&steps, first, current))?;
+        write!(output, "> ")?;
+        output.flush()?;
+    }
+    Ok(())
+}
+
+fn interactive_step(steps: &[Step], first: &Step, current: usize) -> String {
+    let (mut x, state) = match current {
+        0 => ("Initial state\n".to_string(), &first.previous),
+        _ => {
+            let step = &steps[current - 1];
+            (
+                format!(
+                    "Step {current}/{}: Process {}: {}\n",
+                    steps.len(),
+                    step.process,
+                    step.statement
+                ),
+                &step.state,
+            )
+        }
+    };
+    x.push_str(&tables(state));
+    let state = state.borrow();
+    let mut globals: Vec<_> = state.globals.iter().collect();
+    globals.sort();
+    if !globals.is_empty() {
+        x.push_str(&format!("    globals: {}\n", bindings(&globals)));
+    }
+    for (process, locals) in state.locals.iter().enumerate() {
+        let mut locals: Vec<_> = locals.iter().collect();
+        locals.sort();
+        if !locals.is_empty() {
+            x.push_str(&format!(
+                "    Process {process} locals: {}\n",
+                bindings(&locals)
+            ));
+        }
+    }
+    x.pop();
+    x
+}
+
+fn bindings(values: &[(&String, &Value)]) -> String {
+    let values: Vec<_> = values
+        .iter()
+        .map(|(name, value)| format!("{name}: {value}"))
+        .collect();
+    values.join(", ")
+}
+
+// Follows the processes of a deadlock from the lowest one, each waiting for the next
+fn wait_for(state: &State, cycle: &HashSet<usize>) -> String {
+    let locks = |p: usize| {
+        let tid = state.txs[p].id.unwrap();
+        &state.sql.transactions[&tid].locks
+    };
+    let waiting = |p: usize| match &state.processes[p] {
+        ProcessState::Locked(lock) => Some(lock),
+        _ => None,
+    };
+    let mut processes: Vec<_> = cycle.iter().copied().collect();
+    processes.sort();
+
+    let mut x = String::new();
+    let mut visited = vec![];
+    for start in processes.clone() {
+        let mut p = start;
+        while !visited.contains(&p) {
+            visited.push(p);
+            let held: Vec<_> = locks(p)
+                .iter()
+                .filter(|held| {
+                    processes
+                        .iter()
+                        .any(|q| *q != p && waiting(*q).is_some_and(|lock| lock.waits_for(held)))
+                })
+                .map(|held| lock(state, held))
+                .collect();
+            let next = processes.iter().copied().find(|q| {
+                *q != p
+                    && waiting(p).is_some_and(|lock| locks(*q).iter().any(|l| lock.waits_for(l)))
+            });
+            x.push_str(&format!("Process {p} holds {}", held.join(", ")));
+            match next {
+                Some(next) => {
+                    x.push_str(&format!(", waits for Process {next}\n"));
+                    p = next;
+                }
+                None => x.push('\n'),
+            }
+        }
+    }
+    x
+}
+
+fn lock(state: &State, lock: &Lock) -> String {
+    let on_row = |kind: &str, rid: &RowId| match state.sql.find_row(*rid) {
+        Some((table, r)) => format!("{kind} on {table} {}", row(r)),
+        None => format!("{kind} on a new row"),
+    };
+    match lock {
+        Lock::RowUpdate(rid) => on_row("RowUpdate", rid),
+        Lock::RowShare(rid) => on_row("RowShare", rid),
+        Lock::RowForKeyShare(rid) => on_row("RowForKeyShare", rid),
+        Lock::Unique(table, _, value) => format!("Unique on {table} {value}"),
+        Lock::Predicate(table, condition) => format!("Predicate on {table} where {condition}"),
+        Lock::Table(table) => format!("Table on {table}"),
+        Lock::TableWrite(table) => format!("TableWrite on {table}"),
+    }
+}
+
+fn waiting(step: &Step) -> String {
+    let state = step.state.borrow();
+    match &state.processes[step.process] {
+        ProcessState::Locked(held) => format!("    waits for {}\n", lock(&state, held)),
+        _ => String::new(),
+    }
+}
+
+// Orders the transaction boundaries, reads and writes of the trace, a statement blocked on a
+// lock appears once it runs
+fn schedule(steps: &[Step]) -> Vec<String> {
+    // Processes running transactions of the same name are told apart
+    let mut names: HashMap<&str, HashSet<usize>> = HashMap::new();
+    for step in steps {
+        if let Statement::Begin(_, Some(name), _) = step.statement {
+            names.entry(&name.name).or_default().insert(step.process);
+        }
+    }
+    let mut schedule = vec![];
+    for step in steps {
+        let previous = step.previous.borrow();
+        let state = step.state.borrow();
+        let (before, after) = (&previous.txs[step.process], &state.txs[step.process]);
+        let tx = match (before.id.or(after.id), &after.name) {
+            (Some(_), Some(name)) if names.get(name.as_str()).is_some_and(|p| p.len() > 1) => {
+                format!("{name}/p{}", step.process)
+            }
+            (Some(_), Some(name)) => name.clone(),
+            (Some(id), None) => format!("t{}", id.0),
+            (None, _) => format!("p{}", step.process),
+        };
+        let mut entry = format!("{tx}: {}", step.statement);
+        if let Some(result) = &state.result {
+            entry.push_str(&format!(" -> {result}"));
+        }
+        // A transaction aborted by another statement than abort failed, like a rejected write
+        let aborted = before.state == TransactionState::Running
+            && after.state == TransactionState::Aborted
+            && !matches!(step.statement, Statement::Abort);
+        if let ProcessState::Locked(_) = state.processes[step.process] {
+            entry.push_str(" (blocked)");
+        } else if aborted
+            || (after.failed && !before.failed)
+            || matches!(state.result, Some(Value::Error(_)))
+        {
+            entry.push_str(" (failed)");
+        }
+        schedule.push(entry);
+    }
+    schedule
+}
+
+fn locals(step: &Step) -> String {
+    let previous = step.previous.borrow();
+    let state = step.state.borrow();
+    let mut x = changes(&previous.globals, &state.globals);
+    x.push_str(&changes(
+        &previous.locals[step.process],
+        &state.locals[step.process],
+    ));
+    x
+}
+
+fn changes(previous: &HashMap<String, Value>, current: &HashMap<String, Value>) -> String {
+    let mut names: Vec<_> = current.keys().collect();
+    names.sort();
+    let mut x = String::new();
+    for name in names {
+        let value = &current[name];
+        match previous.get(name) {
+            Some(old) if old == value => {}
+            Some(old) => x.push_str(&format!("    {name}: {old} -> {value}\n")),
+            None => x.push_str(&format!("    {name}: {value}\n")),
+        }
+    }
+    x
+}
+
+fn tables(state: &RcState) -> String {
+    let state = state.borrow();
+    let mut x = String::new();
+    let mut names: Vec<_> = state.sql.tables.keys().collect();
+    names.sort();
+    for name in names {
+        let rows: Vec<_> = state.sql.tables[name].rows.iter().map(row).collect();
+        x.push_str(&format!("    {name}: {}\n", rows.join(", ")));
+    }
+    for (process, tx) in state.txs.iter().enumerate() {
+        let Some(context) = tx.id.and_then(|id| state.sql.transactions.get(&id)) else {
+            continue;
+        };
+        let changes: Vec<_> = context
+            .pending_changes()
+            .into_iter()
+            .map(|(kind, table, r)| match r {
+                Some(r) => format!("{kind} {table} {}", row(r)),
+                None => format!("{kind} {table}"),
+            })
+            .collect();
+        if !changes.is_empty() {
+            x.push_str(&format!(
+                "    Process {process} pending: {}\n",
+                changes.join(", ")
+            ));
+        }
+    }
+    x
+}
+
+fn row(row: &Row) -> String {
+    let mut columns: Vec<_> = row.tuples.iter().collect();
+    columns.sort_by(|a, b| a.0.cmp(b.0));
+    let columns: Vec<_> = columns
+        .into_iter()
+        .map(|(column, value)| format!("{column}: {value}"))
+        .collect();
+    format!("{{{}}}", columns.join(", "))
+}
+
+// Shows the source line of an error and underlines its token, like rustc
+pub fn diagnostic(source: &str, position: &Position) -> String {
+    let line = source.lines().nth(position.start_line - 1).unwrap_or("");
+    let start = position.start_col - 1;
+    let end = if position.end_line == position.start_line {
+        position.end_col - 1
+    } else {
+        line.chars().count()
+    };
+    let padding: String = line
+        .chars()
+        .take(start)
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    let number = position.start_line.to_string();
+    let gutter = " ".repeat(number.len());
+    format!(
+        "{gutter} |\n{number} | {line}\n{gutter} | {padding}{}",
+        "^".repeat(end.saturating_sub(start).max(1))
+    )
+}
+
+pub fn json(mets: &Mets, report: &Report) -> String {
+    let completeness = match &report.completeness {
+        Completeness::Complete => "null".to_string(),
+        Completeness::Incomplete { reason } => json_string(&reason.to_string()),
+    };
+    let violation = match &report.violation {
+        None => "null".to_string(),
+        Some(violation) => {
+            let (kind, detail, state) = match violation {
+                Violation::PropertyViolation { property, state } => (
+                    "property",
+                    format!("\"property\":{}", json_string(&property.to_string())),
+                    state,
+                ),
+                Violation::Lasso {
+                    property, state, ..
+                } => (
+                    "lasso",
+                    format!("\"property\":{}", json_string(&property.to_string())),
+                    state,
+                ),
+                Violation::Deadlock { cycle, state } => {
+                    let mut processes: Vec<_> = cycle.iter().collect();
+                    processes.sort();
+                    let processes: Vec<_> = processes.iter().map(|p| p.to_string()).collect();
+                    (
+                        "deadlock",
+                        format!("\"processes\":[{}]", processes.join(",")),
+                        state,
+                    )
+                }
+            };
+            format!(
+                "{{\"kind\":\"{kind}\",{detail},\"trace\":{},\"cycle\":{},\"tables\":{}}}",
+                json_steps(&trace(mets, violation)),
+                json_steps(&cycle(mets, violation)),
+                json_tables(state)
+            )
+        }
+    };
+    format!(
+        "{{\"states_explored\":{},\"incomplete\":{completeness},\"violation\":{violation}}}",
+        report.states_explored
+    )
+}
+
+struct Operation {
+    process: usize,
+    tx: Option<String>,
+    ops: Vec<(&'static str, String, String)>,
+}
+
+// Lists the transactions of the counter example as invoke and completion events, one for each
+// transaction or statement run outside of a transaction, in the shape of a Jepsen history
+pub fn history(mets: &Mets, report: &Report) -> String {
+    let Some(violation) = &report.violation else {
+        return "[]".to_string();
+    };
+    let mut events: Vec<Option<String>> = vec![];
+    let mut open: HashMap<usize, (usize, Operation)> = HashMap::new();
+    let complete = |events: &mut Vec<Option<String>>, invoke: usize, operation: Operation, kind| {
+        let value = |values: bool| {
+            let ops: Vec<_> = operation
+                .ops
+                .iter()
+                .map(|(f, sql, value)| {
+                    let value = if values { value.as_str() } else { "null" };
+                    format!("[\"{f}\",{},{value}]", json_string(sql))
+                })
+                .collect();
+            format!("[{}]", ops.join(","))
+        };
+        let event =
+            |kind: &str, value: String| {
+                format!(
+                "{{\"type\":\"{kind}\",\"process\":{},\"tx\":{},\"f\":\"txn\",\"value\":{value}}}",
+                operation.process,
+                operation.tx.as_deref().map_or("null".to_string(), json_string)
+            )
+            };
+        events[invoke] = Some(event("invoke", value(false)));
+        events.push(Some(event(kind, value(kind == "ok"))));
+    };
+
+    for step in trace(mets, violation) {
+        let state = step.state.borrow();
+        let tx = &state.txs[step.process];
+        if let Statement::Begin(..) = step.statement {
+            events.push(None);
+            let operation = Operation {
+                process: step.process,
+                tx: tx.name.clone(),
+                ops: vec![],
+            };
+            open.insert(step.process, (events.len() - 1, operation));
+            continue;
+        }
+        let sql = match step.statement {
+            Statement::Expression(Expression::Sql(sql)) => Some(sql),
+            Statement::Expression(Expression::Assignment(_, expr)) => match expr.as_ref() {
+                Expression::Sql(sql) => Some(sql),
+                _ => None,
+            },
+            _ => None,
+        };
+        let op = sql.map(|sql| {
+            let f = match sql {
+                SqlExpression::Select { .. } | SqlExpression::Union { .. } => "r",
+                _ => "w",
+            };
+            let value = state.result.as_ref().map_or("null".to_string(), json_value);
+            (f, sql.to_string(), value)
+        });
+        let failed = matches!(state.result, Some(Value::Error(_)));
+        match open.remove(&step.process) {
+            Some((invoke, mut operation)) => {
+                operation.ops.extend(op);
+                match tx.state {
+                    TransactionState::Running => {
+                        open.insert(step.process, (invoke, operation));
+                    }
+                    TransactionState::Committed => complete(&mut events, invoke, operation, "ok"),
+                    _ => complete(&mut events, invoke, operation, "fail"),
+                }
+            }
+            None => {
+                if let Some(op) = op {
+                    events.push(None);
+                    let invoke = events.len() - 1;
+                    let operation = Operation {
+                        process: step.process,
+                        tx: None,
+                        ops: vec![op],
+                    };
+                    let kind = if failed || state.result.is_none() {
+                        "fail"
+                    } else {
+                        "ok"
+                    };
+                    complete(&mut events, invoke, operation, kind);
+                }
+            }
+        }
+    }
+    // Transactions still running at the end of the trace have an unknown outcome
+    let mut running: Vec<_> = open.into_values().collect();
+    running.sort_by_key(|(invoke, _)| *invoke);
+    for (invoke, operation) in running {
+        complete(&mut events, invoke, operation, "info");
+    }
+
+    let events: Vec<_> = events
+        .into_iter()
+        .flatten()
+        .enumerate()
+        .map(|(index, event)| format!("{{\"index\":{index},{}", &event[1..]))
+        .collect();
+    format!("[{}]", events.join(",\n "))
+}
+
+pub fn dot(report: &Report) -> String {
+    let Some(Graph { states, edges }) = &report.graph else {
+        return "digraph {}".to_string();
+    };
+    let violation = report.violation.as_ref().map(|violation| match violation {
+        Violation::PropertyViolation { state, .. }
+        | Violation::Deadlock { state, .. }
+        | Violation::Lasso { state, .. } => state,
+    });
+
+    let mut x = String::from("digraph {\n");
+    let mut highlighted = false;
+    for (index, state) in states.iter().enumerate() {
+        let style = if violation.is_some_and(|v| v.ptr_eq(state)) {
+            highlighted = true;
+            ", color=red, style=filled"
+        } else {
+            ""
+        };
+        x.push_str(&format!("  {index} [label={}{style}];\n", dot_label(state)));
+    }
+    for (from, successors) in edges.iter().enumerate() {
+        for (process, to) in successors {
+            x.push_str(&format!("  {from} -> {to} [label=\"p{process}\"];\n"));
+        }
+    }
+    if let Some(state) = violation.filter(|_| !highlighted) {
+        let index = states.len();
+        x.push_str(&format!(
+            "  {index} [label={}, color=red, style=filled];\n",
+            dot_label(state)
+        ));
+        if let Some(parent) = state.borrow().ancestors.first() {
+            if let Some(from) = states.iter().position(|s| s.ptr_eq(parent)) {
+                x.push_str(&format!("  {from} -> {index};\n"));
+            }
+        }
+    }
+    x.push('}');
+    x
+}
+
+fn dot_label(state: &RcState) -> String {
+    let state = state.borrow();
+    let processes: Vec<_> = state
+        .pc
+        .iter()
+        .zip(&state.processes)
+        .enumerate()
+        .map(|(i, (pc, process))| format!("p{i}: pc {pc} {process:?}"))
+        .collect();
+    json_string(&processes.join("\n"))
+}
+
+fn json_steps(steps: &[Step]) -> String {
+    let steps: Vec<_> = steps
+        .iter()
+        .map(|step| {
+            format!(
+                "{{\"process\":{},\"statement\":{}}}",
+                step.process,
+                json_string(&step.statement.to_string())
+            )
+        })
+        .collect();
+    format!("[{}]", steps.join(","))
+}
+
+fn json_tables(state: &RcState) -> String {
+    let state = state.borrow();
+    let mut names: Vec<_> = state.sql.tables.keys().collect();
+    names.sort();
+    let tables: Vec<_> = names
+        .into_iter()
+        .map(|name| {
+            let table = &state.sql.tables[name];
+            let rows: Vec<_> = table
+                .rows
+                .iter()
+                .map(|row| {
+                    let columns: Vec<_> = table
+                        .columns
+                        .iter()
+                        .map(|column| {
+                            format!(
+                                "{}:{}",
+                                json_string(column),
+                                json_value(row.tuples.get(column).unwrap_or(&Value::Null))
+                            )
+                        })
+                        .collect();
+                    format!("{{{}}}", columns.join(","))
+                })
+                .collect();
+            format!("{}:[{}]", json_string(name), rows.join(","))
+        })
+        .collect();
+    format!("{{{}}}", tables.join(","))
+}
+
+fn json_value(value: &Value) -> String {
+    match value {
+        Value::Nil | Value::Null => "null".to_string(),
+        Value::Bool(b) => b.to_string(),
+        Value::Integer(i) => i.to_string(),
+        Value::String(s) => json_string(
+            s.strip_prefix('\'')
+                .and_then(|s| s.strip_suffix('\''))
+                .unwrap_or(s),
+        ),
+        Value::Set(members) | Value::Tuple(members) => {
+            let members: Vec<_> = members.iter().map(json_value).collect();
+            format!("[{}]", members.join(","))
+        }
+        Value::Scalar(value) => json_value(value),
+        Value::Tx(_) | Value::Error(_) => json_string(&value.to_string()),
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut res = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => res.push_str("\\\""),
+            '\\' => res.push_str("\\\\"),
+            '\n' => res.push_str("\\n"),
+            '\t' => res.push_str("\\t"),
+            c if (c as u32) < 0x20 => res.push_str(&format!("\\u{:04x}", c as u32)),
+            c => res.push(c),
+        }
+    }
+    res.push('"');
+    res
+}
@@ -1,4 +1,4 @@
-#[derive(PartialEq, Debug, Clone)]
+#[derive(PartialEq, Debug, Clone, Default)]
 pub struct Position {
     pub start_line: usize,
     pub start_col: usize,
@@ -38,6 +38,7 @@ impl Token {
 #[derive(PartialEq, Eq, Debug, Clone)]
 pub enum TokenKind {
     Newline,
+    Semicolon,
     ColonEqual,
     Comma,
     Dot,
@@ -49,6 +50,7 @@ pub enum TokenKind {
     Equal,
     Different,
     LeftArrow,
+    LeadsTo,
     Backtick,
     Dollar,
     LessEqual,
@@ -62,7 +64,9 @@ pub enum TokenKind {
     LeftCarret,
     RightCarret,
     If,
+    Either,
     Else,
+    Exclusive,
     Do,
     End,
     Transaction,
@@ -70,42 +74,88 @@ pub enum TokenKind {
     Commit,
     Abort,
     Count,
+    Sum,
+    Min,
+    Max,
+    Mode,
+    Avg,
+    Await,
     Create,
     Unique,
+    Union,
+    All,
     Index,
     On,
+    Only,
+    Savepoint,
     Select,
     From,
     Where,
+    While,
+    Global,
+    Group,
+    Having,
     Order,
     By,
     Limit,
     Offset,
+    Desc,
+    Default,
+    Check,
     Insert,
     Delete,
+    Drop,
+    Distinct,
+    Truncate,
     Into,
     Values,
     Update,
     For,
     Set,
+    Share,
+    Skip,
     Between,
+    Cascade,
     Alter,
     Table,
+    To,
     Add,
+    Const,
+    Conflict,
     Constraint,
     Foreign,
     Key,
+    Read,
     References,
+    Returning,
+    Rollback,
     In,
+    Inner,
+    Join,
+    Is,
+    Like,
+    Not,
+    Nothing,
+    Nowait,
     And,
     Or,
     Always,
     Never,
     Eventually,
+    DeadlockFree,
+    DeadlockExpected,
+    Exists,
+    Fair,
+    Forall,
+    Null,
     Property,
     Process,
+    Primary,
     Latch,
+    Lock,
+    Locked,
     Init,
+    Include,
     Let,
     Identifier,
     Number,
@@ -146,12 +196,12 @@ impl Cursor {
 pub struct ScannerError {
     expected: String,
     lexeme: String,
-    position: Position,
+    pub position: Position,
 }
 
 #[derive(Clone)]
 pub struct Scanner {
-    source: String,
+    source: Vec<char>,
     start: Cursor,
     current: Cursor,
 }
@@ -159,14 +209,14 @@ pub struct Scanner {
 impl Scanner {
     pub fn new(source: String) -> Scanner {
         Scanner {
-            source,
+            source: source.chars().collect(),
             start: Cursor::new(),
             current: Cursor::new(),
         }
     }
 
     pub fn scan_token(&mut self) -> Result<Token, ScannerError> {
-        self.skip_whitespace();
+        self.skip_whitespace()?;
         self.start = self.current;
         if self.is_at_end() {
             self.make_token(TokenKind::Eof)
@@ -195,6 +245,7 @@ impl Scanner {
                 '{' => self.make_token(TokenKind::LeftBrace),
                 '}' => self.make_token(TokenKind::RightBrace),
                 ',' => self.make_token(TokenKind::Comma),
+                ';' => self.make_token(TokenKind::Semicolon),
                 '+' => self.make_token(TokenKind::Plus),
                 '-' => self.make_token(TokenKind::Minus),
                 '/' => self.make_token(TokenKind::Slash),
@@ -208,6 +259,13 @@ impl Scanner {
                         self.make_error("Expected =")
                     }
                 }
+                '~' => {
+                    if self.matches('>') {
+                        self.make_token(TokenKind::LeadsTo)
+                    } else {
+                        self.make_error("Expected >")
+                    }
+                }
                 '>' => {
                     if self.matches('=') {
                         self.make_token(TokenKind::GreaterEqual)
@@ -243,14 +301,17 @@ impl Scanner {
     }
 
     fn identifier_type(&self) -> TokenKind {
-        match self.source.chars().nth(self.start.index).unwrap() {
+        match self.keyword_char(0) {
             'a' => {
                 if self.current.index - self.start.index > 2 {
-                    match self.source.chars().nth(self.start.index + 1).unwrap() {
+                    match self.keyword_char(1) {
                         'b' => self.check_keyword(2, "ort", TokenKind::Abort),
                         'd' => self.check_keyword(2, "d", TokenKind::Add),
                         'n' => self.check_keyword(2, "d", TokenKind::And),
-                        'l' => match self.source.chars().nth(self.start.index + 2).unwrap() {
+                        'v' => self.check_keyword(2, "g", TokenKind::Avg),
+                        'w' => self.check_keyword(2, "ait", TokenKind::Await),
+                        'l' => match self.keyword_char(2) {
+                            'l' => self.check_keyword(3, "", TokenKind::All),
                             'w' => self.check_keyword(3, "ays", TokenKind::Always),
                             't' => self.check_keyword(3, "er", TokenKind::Alter),
                             _ => TokenKind::Identifier,
@@ -262,16 +323,13 @@ impl Scanner {
                 }
             }
             'b' => {
-                if self.current.index - self.start.index > 3
-                    && self.source.chars().nth(self.start.index + 1).unwrap() == 'e'
-                {
-                    match self.source.chars().nth(self.start.index + 2).unwrap() {
+                if self.current.index - self.start.index > 3 && self.keyword_char(1) == 'e' {
+                    match self.keyword_char(2) {
                         'g' => self.check_keyword(3, "gin", TokenKind::Begin),
                         't' => self.check_keyword(3, "ween", TokenKind::Between),
                         _ => TokenKind::Identifier,
                     }
-                } else if self.current.index - self.start.index == 2
-                    && self.source.chars().nth(self.start.index + 1).unwrap() == 'y'
+                } else if self.current.index - self.start.index == 2 && self.keyword_char(1) == 'y'
                 {
                     TokenKind::By
                 } else {
@@ -280,13 +338,21 @@ impl Scanner {
             }
             'c' => {
                 if self.current.index - self.start.index > 2 {
-                    match self.source.chars().nth(self.start.index + 1).unwrap() {
-                        'o' => match self.source.chars().nth(self.start.index + 2).unwrap() {
+                    match self.keyword_char(1) {
+                        'o' => match self.keyword_char(2) {
                             'm' => self.check_keyword(3, "mit", TokenKind::Commit),
+                            'n' if self.current.index - self.start.index == 5 => {
+                                self.check_keyword(3, "st", TokenKind::Const)
+                            }
+                            'n' if self.current.index - self.start.index == 8 => {
+                                self.check_keyword(3, "flict", TokenKind::Conflict)
+                            }
                             'n' => self.check_keyword(3, "straint", TokenKind::Constraint),
                             'u' => self.check_keyword(3, "nt", TokenKind::Count),
                             _ => TokenKind::Identifier,
                         },
+                        'a' => self.check_keyword(2, "scade", TokenKind::Cascade),
+                        'h' => self.check_keyword(2, "eck", TokenKind::Check),
                         'r' => self.check_keyword(2, "eate", TokenKind::Create),
                         _ => TokenKind::Identifier,
                     }
@@ -296,9 +362,28 @@ impl Scanner {
             }
             'd' => {
                 if self.current.index - self.start.index > 1 {
-                    match self.source.chars().nth(self.start.index + 1).unwrap() {
+                    match self.keyword_char(1) {
                         'o' => self.check_keyword(2, "", TokenKind::Do),
-                        'e' => self.check_keyword(2, "lete", TokenKind::Delete),
+                        'r' => self.check_keyword(2, "op", TokenKind::Drop),
+                        'i' => self.check_keyword(2, "stinct", TokenKind::Distinct),
+                        'e' if self.current.index - self.start.index > 2 => {
+                            match self.keyword_char(2) {
+                                'a' => match self.current.index - self.start.index {
+                                    13 => {
+                                        self.check_keyword(3, "dlock_free", TokenKind::DeadlockFree)
+                                    }
+                                    _ => self.check_keyword(
+                                        3,
+                                        "dlock_expected",
+                                        TokenKind::DeadlockExpected,
+                                    ),
+                                },
+                                'l' => self.check_keyword(3, "ete", TokenKind::Delete),
+                                's' => self.check_keyword(3, "c", TokenKind::Desc),
+                                'f' => self.check_keyword(3, "ault", TokenKind::Default),
+                                _ => TokenKind::Identifier,
+                            }
+                        }
                         _ => TokenKind::Identifier,
                     }
                 } else {
@@ -307,10 +392,16 @@ impl Scanner {
             }
             'e' => {
                 if self.current.index - self.start.index > 2 {
-                    match self.source.chars().nth(self.start.index + 1).unwrap() {
+                    match self.keyword_char(1) {
+                        'i' => self.check_keyword(2, "ther", TokenKind::Either),
                         'l' => self.check_keyword(2, "se", TokenKind::Else),
                         'n' => self.check_keyword(2, "d", TokenKind::End),
                         'v' => self.check_keyword(2, "entually", TokenKind::Eventually),
+                        'x' => match self.keyword_char(2) {
+                            'c' => self.check_keyword(3, "lusive", TokenKind::Exclusive),
+                            'i' => self.check_keyword(3, "sts", TokenKind::Exists),
+                            _ => TokenKind::Identifier,
+                        },
                         _ => TokenKind::Identifier,
                     }
                 } else {
@@ -319,14 +410,19 @@ impl Scanner {
             }
             'f' => {
                 if self.current.index - self.start.index > 2 {
-                    match self.source.chars().nth(self.start.index + 1).unwrap() {
+                    match self.keyword_char(1) {
                         'o' if self.current.index - self.start.index == 3 => {
                             self.check_keyword(2, "r", TokenKind::For)
                         }
                         'o' if self.current.index - self.start.index > 3 => {
-                            self.check_keyword(2, "reign", TokenKind::Foreign)
+                            match self.keyword_char(3) {
+                                'e' => self.check_keyword(2, "reign", TokenKind::Foreign),
+                                'a' => self.check_keyword(2, "rall", TokenKind::Forall),
+                                _ => TokenKind::Identifier,
+                            }
                         }
                         'r' => self.check_keyword(2, "om", TokenKind::From),
+                        'a' => self.check_keyword(2, "ir", TokenKind::Fair),
                         _ => TokenKind::Identifier,
                     }
                 } else {
@@ -334,14 +430,17 @@ impl Scanner {
                 }
             }
             'i' => match self.current.index - self.start.index {
-                2 => match self.source.chars().nth(self.start.index + 1).unwrap() {
+                2 => match self.keyword_char(1) {
                     'n' => self.check_keyword(2, "", TokenKind::In),
                     'f' => self.check_keyword(2, "", TokenKind::If),
+                    's' => self.check_keyword(2, "", TokenKind::Is),
                     _ => TokenKind::Identifier,
                 },
-                x if x > 2 => match self.source.chars().nth(self.start.index + 1).unwrap() {
-                    'n' => match self.source.chars().nth(self.start.index + 2).unwrap() {
+                x if x > 2 => match self.keyword_char(1) {
+                    'n' => match self.keyword_char(2) {
                         'd' => self.check_keyword(3, "ex", TokenKind::Index),
+                        'c' => self.check_keyword(3, "lude", TokenKind::Include),
+                        'n' => self.check_keyword(3, "er", TokenKind::Inner),
                         'i' => self.check_keyword(3, "t", TokenKind::Init),
                         's' => self.check_keyword(3, "ert", TokenKind::Insert),
                         't' => self.check_keyword(3, "o", TokenKind::Into),
@@ -351,14 +450,66 @@ impl Scanner {
                 },
                 _ => TokenKind::Identifier,
             },
+            'g' => {
+                if self.current.index - self.start.index > 1 {
+                    match self.keyword_char(1) {
+                        'l' => self.check_keyword(2, "obal", TokenKind::Global),
+                        'r' => self.check_keyword(2, "oup", TokenKind::Group),
+                        _ => TokenKind::Identifier,
+                    }
+                } else {
+                    TokenKind::Identifier
+                }
+            }
+            'h' => self.check_keyword(1, "aving", TokenKind::Having),
+            'j' => self.check_keyword(1, "oin", TokenKind::Join),
             'k' => self.check_keyword(1, "ey", TokenKind::Key),
-            'n' => self.check_keyword(1, "ever", TokenKind::Never),
+            'm' => {
+                if self.current.index - self.start.index > 1 {
+                    match self.keyword_char(1) {
+                        'a' => self.check_keyword(2, "x", TokenKind::Max),
+                        'i' => self.check_keyword(2, "n", TokenKind::Min),
+                        'o' => self.check_keyword(2, "de", TokenKind::Mode),
+                        _ => TokenKind::Identifier,
+                    }
+                } else {
+                    TokenKind::Identifier
+                }
+            }
+            'n' => {
+                if self.current.index - self.start.index > 1 {
+                    match self.keyword_char(1) {
+                        'e' => self.check_keyword(2, "ver", TokenKind::Never),
+                        'o' if self.current.index - self.start.index == 3 => {
+                            self.check_keyword(2, "t", TokenKind::Not)
+                        }
+                        'o' if self.current.index - self.start.index == 6 => {
+                            self.check_keyword(2, "wait", TokenKind::Nowait)
+                        }
+                        'o' => self.check_keyword(2, "thing", TokenKind::Nothing),
+                        'u' => self.check_keyword(2, "ll", TokenKind::Null),
+                        _ => TokenKind::Identifier,
+                    }
+                } else {
+                    TokenKind::Identifier
+                }
+            }
             'l' => {
                 if self.current.index - self.start.index > 1 {
-                    match self.source.chars().nth(self.start.index + 1).unwrap() {
+                    match self.keyword_char(1) {
                         'a' => self.check_keyword(2, "tch", TokenKind::Latch),
                         'e' => self.check_keyword(2, "t", TokenKind::Let),
-                        'i' => self.check_keyword(2, "mit", TokenKind::Limit),
+                        'o' if self.current.index - self.start.index == 4 => {
+                            self.check_keyword(2, "ck", TokenKind::Lock)
+                        }
+                        'o' => self.check_keyword(2, "cked", TokenKind::Locked),
+                        'i' if self.current.index - self.start.index > 2 => {
+                            match self.keyword_char(2) {
+                                'm' => self.check_keyword(3, "it", TokenKind::Limit),
+                                'k' => self.check_keyword(3, "e", TokenKind::Like),
+                                _ => TokenKind::Identifier,
+                            }
+                        }
                         _ => TokenKind::Identifier,
                     }
                 } else {
@@ -367,11 +518,12 @@ impl Scanner {
             }
             'o' => {
                 if self.current.index - self.start.index > 1 {
-                    match self.source.chars().nth(self.start.index + 1).unwrap() {
+                    match self.keyword_char(1) {
                         'f' => self.check_keyword(2, "fset", TokenKind::Offset),
                         'r' if self.current.index - self.start.index == 2 => TokenKind::Or,
                         'r' => self.check_keyword(2, "der", TokenKind::Order),
                         'n' if self.current.index - self.start.index == 2 => TokenKind::On,
+                        'n' => self.check_keyword(2, "ly", TokenKind::Only),
                         _ => TokenKind::Identifier,
                     }
                 } else {
@@ -380,39 +532,47 @@ impl Scanner {
             }
             'p' => {
                 if self.current.index - self.start.index > 6 {
-                    if self
-                        .source
-                        .chars()
-                        .skip(self.start.index)
-                        .take(3)
-                        .collect::<String>()
-                        == *"pro".to_string()
-                    {
-                        match self.source.chars().nth(self.start.index + 3).unwrap() {
+                    if (0..3).map(|i| self.keyword_char(i)).eq("pro".chars()) {
+                        match self.keyword_char(3) {
                             'c' => self.check_keyword(4, "ess", TokenKind::Process),
                             'p' => self.check_keyword(4, "erty", TokenKind::Property),
                             _ => TokenKind::Identifier,
                         }
                     } else {
-                        TokenKind::Identifier
+                        self.check_keyword(1, "rimary", TokenKind::Primary)
+                    }
+                } else {
+                    TokenKind::Identifier
+                }
+            }
+            'r' => {
+                if self.current.index - self.start.index > 2 {
+                    match self.keyword_char(1) {
+                        'e' => match self.keyword_char(2) {
+                            'a' => self.check_keyword(3, "d", TokenKind::Read),
+                            'f' => self.check_keyword(3, "erences", TokenKind::References),
+                            't' => self.check_keyword(3, "urning", TokenKind::Returning),
+                            _ => TokenKind::Identifier,
+                        },
+                        'o' => self.check_keyword(2, "llback", TokenKind::Rollback),
+                        _ => TokenKind::Identifier,
                     }
                 } else {
                     TokenKind::Identifier
                 }
             }
-            'r' => self.check_keyword(1, "eferences", TokenKind::References),
             's' => {
-                if self
-                    .source
-                    .chars()
-                    .skip(self.start.index)
-                    .take(2)
-                    .collect::<String>()
-                    == *"se".to_string()
-                {
-                    match self.source.chars().nth(self.start.index + 2).unwrap() {
-                        'l' => self.check_keyword(3, "ect", TokenKind::Select),
-                        't' => self.check_keyword(3, "", TokenKind::Set),
+                if self.current.index - self.start.index > 2 {
+                    match self.keyword_char(1) {
+                        'a' => self.check_keyword(2, "vepoint", TokenKind::Savepoint),
+                        'e' => match self.keyword_char(2) {
+                            'l' => self.check_keyword(3, "ect", TokenKind::Select),
+                            't' => self.check_keyword(3, "", TokenKind::Set),
+                            _ => TokenKind::Identifier,
+                        },
+                        'u' => self.check_keyword(2, "m", TokenKind::Sum),
+                        'h' => self.check_keyword(2, "are", TokenKind::Share),
+                        'k' => self.check_keyword(2, "ip", TokenKind::Skip),
                         _ => TokenKind::Identifier,
                     }
                 } else {
@@ -420,10 +580,16 @@ impl Scanner {
                 }
             }
             't' => {
-                if self.current.index - self.start.index > 3 {
-                    match self.source.chars().nth(self.start.index + 1).unwrap() {
+                if self.current.index - self.start.index == 2 {
+                    self.check_keyword(1, "o", TokenKind::To)
+                } else if self.current.index - self.start.index > 3 {
+                    match self.keyword_char(1) {
                         'a' => self.check_keyword(2, "ble", TokenKind::Table),
-                        'r' => self.check_keyword(2, "ansaction", TokenKind::Transaction),
+                        'r' => match self.keyword_char(2) {
+                            'a' => self.check_keyword(3, "nsaction", TokenKind::Transaction),
+                            'u' => self.check_keyword(3, "ncate", TokenKind::Truncate),
+                            _ => TokenKind::Identifier,
+                        },
                         _ => TokenKind::Identifier,
                     }
                 } else {
@@ -431,10 +597,13 @@ impl Scanner {
                 }
             }
             'u' => {
-                if self.current.index - self.start.index > 5 {
-                    match self.source.chars().nth(self.start.index + 1).unwrap() {
+                if self.current.index - self.start.index > 4 {
+                    match self.keyword_char(1) {
                         'p' => self.check_keyword(2, "date", TokenKind::Update),
-                        'n' => self.check_keyword(2, "ique", TokenKind::Unique),
+                        'n' => match self.keyword_char(3) {
+                            'o' => self.check_keyword(2, "ion", TokenKind::Union),
+                            _ => self.check_keyword(2, "ique", TokenKind::Unique),
+                        },
                         _ => TokenKind::Identifier,
                     }
                 } else {
@@ -442,30 +611,68 @@ impl Scanner {
                 }
             }
             'v' => self.check_keyword(1, "alues", TokenKind::Values),
-            'w' => self.check_keyword(1, "here", TokenKind::Where),
+            'w' => match self.keyword_char(1) {
+                'h' => match self.keyword_char(2) {
+                    'e' => self.check_keyword(3, "re", TokenKind::Where),
+                    'i' => self.check_keyword(3, "le", TokenKind::While),
+                    _ => TokenKind::Identifier,
+                },
+                _ => TokenKind::Identifier,
+            },
             _ => TokenKind::Identifier,
         }
     }
 
     fn number(&mut self) -> Result<Token, ScannerError> {
-        while self.peek().is_numeric() {
+        while !self.is_at_end() && self.peek().is_numeric() {
             self.advance();
         }
 
         self.make_token(TokenKind::Number)
     }
 
+    // The lexeme keeps the enclosing quotes but holds the decoded escape sequences
     fn string(&mut self) -> Result<Token, ScannerError> {
-        while self.peek() != '\'' {
-            self.advance();
+        let mut value = String::from('\'');
+        loop {
+            if self.is_at_end() || self.peek() == '\n' {
+                return self.make_error("Unterminated string literal");
+            }
+            match self.advance() {
+                '\'' => {
+                    if self.matches('\'') {
+                        value.push('\'');
+                    } else {
+                        break;
+                    }
+                }
+                '\\' => {
+                    if self.is_at_end() {
+                        return self.make_error("Unterminated string literal");
+                    }
+                    match self.advance() {
+                        'n' => value.push('\n'),
+                        't' => value.push('\t'),
+                        '\\' => value.push('\\'),
+                        '\'' => value.push('\''),
+                        _ => {
+                            return self.make_error(
+                                "Unknown escape sequence, expected \\n, \\t, \\\\ or \\'",
+                            )
+                        }
+                    }
+                }
+                c => value.push(c),
+            }
         }
+        value.push('\'');
 
-        self.advance(); // consume closing '
-
-        self.make_token(TokenKind::String)
+        let mut token = self.make_token(TokenKind::String)?;
+        token.lexeme = value;
+        Ok(token)
     }
 
-    fn skip_whitespace(&mut self) {
+    fn skip_whitespace(&mut self) -> Result<(), ScannerError> {
         loop {
             if self.is_at_end() {
                 break;
@@ -477,22 +684,47 @@ impl Scanner {
                 } else {
                     self.advance();
                 }
+            } else if c == '-' && self.peek_next() == Some('-') {
+                while !self.is_at_end() && self.peek() != '\n' {
+                    self.advance();
+                }
+            } else if c == '/' && self.peek_next() == Some('*') {
+                self.block_comment()?;
             } else {
                 break;
             }
         }
+        Ok(())
+    }
+
+    // Block comments do not nest: the first */ closes the comment
+    fn block_comment(&mut self) -> Result<(), ScannerError> {
+        self.start = self.current;
+        self.advance();
+        self.advance();
+        loop {
+            if self.is_at_end() {
+                return Err(self.error("Unterminated block comment, expected */"));
+            }
+            if self.peek() == '*' && self.peek_next() == Some('/') {
+                self.advance();
+                self.advance();
+                return Ok(());
+            }
+            if self.peek() == '\n' {
+                self.newline();
+            } else {
+                self.advance();
+            }
+        }
     }
 
     fn check_keyword(&self, start: usize, rest: &str, kind: TokenKind) -> TokenKind {
         let length = rest.len();
         if self.current.index - self.start.index == start + length
-            && rest
-                == self
-                    .source
-                    .chars()
-                    .skip(self.start.index + start)
-                    .take(length)
-                    .collect::<String>()
+            && (start..start + length)
+                .map(|i| self.keyword_char(i))
+                .eq(rest.chars())
         {
             kind
         } else {
@@ -500,12 +732,21 @@ impl Scanner {
         }
     }
 
+    // Keywords are case insensitive, identifiers keep their original lexeme
+    fn keyword_char(&self, offset: usize) -> char {
+        self.source[self.start.index + offset].to_ascii_lowercase()
+    }
+
     fn peek(&self) -> char {
-        self.source.chars().nth(self.current.index).unwrap()
+        self.source[self.current.index]
+    }
+
+    fn peek_next(&self) -> Option<char> {
+        self.source.get(self.current.index + 1).copied()
     }
 
     fn matches(&mut self, c: char) -> bool {
-        if self.source.chars().nth(self.current.index) == Some(c) {
+        if self.source.get(self.current.index) == Some(&c) {
             self.current.advance();
             true
         } else {
@@ -515,27 +756,28 @@ impl Scanner {
 
     fn advance(&mut self) -> char {
         self.current.advance();
-        self.source.chars().nth(self.current.index - 1).unwrap()
+        self.source[self.current.index - 1]
     }
 
     fn newline(&mut self) -> char {
         self.current.newline();
-        self.source.chars().nth(self.current.index - 1).unwrap()
+        self.source[self.current.index - 1]
     }
 
     fn is_at_end(&self) -> bool {
-        self.current.index == self.source.chars().count()
+        self.current.index == self.source.len()
+    }
+
+    fn lexeme(&self) -> String {
+        self.source[self.start.index..self.current.index]
+            .iter()
+            .collect()
     }
 
     fn make_token(&self, kind: TokenKind) -> Result<Token, ScannerError> {
         Ok(Token {
             kind,
-            lexeme: self
-                .source
-                .chars()
-                .skip(self.start.index)
-                .take(self.current.index - self.start.index)
-                .collect::<String>(),
+            lexeme: self.lexeme(),
             position: Position {
                 start_line: self.start.line,
                 start_col: self.start.col,
@@ -546,20 +788,215 @@ impl Scanner {
     }
 
     fn make_error(&self, expected_message: &str) -> Result<Token, ScannerError> {
-        Err(ScannerError {
+        Err(self.error(expected_message))
+    }
+
+    fn error(&self, expected_message: &str) -> ScannerError {
+        ScannerError {
             expected: expected_message.to_string(),
-            lexeme: self
-                .source
-                .chars()
-                .skip(self.start.index)
-                .take(self.current.index - self.start.index)
-                .collect::<String>(),
+            lexeme: self.lexeme(),
             position: Position {
                 start_line: self.start.line,
                 start_col: self.start.col,
                 end_line: self.current.line,
                 end_col: self.current.col,
             },
-        })
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::scanner::{Scanner, TokenKind};
+
+    fn kinds(source: &str) -> Vec<TokenKind> {
+        let mut scanner = Scanner::new(source.to_string());
+        let mut kinds = vec![];
+        loop {
+            let token = scanner.scan_token().unwrap();
+            kinds.push(token.kind.clone());
+            if token.kind == TokenKind::Eof {
+                return kinds;
+            }
+        }
+    }
+
+    #[test]
+    fn scan_large_source() {
+        let source =
+            "let balance := `select balance from accounts where id = 1` + 1\n".repeat(5000);
+        let start = std::time::Instant::now();
+        let tokens = kinds(&source);
+        assert_eq!(5000 * 16 + 1, tokens.len());
+        assert!(start.elapsed() < std::time::Duration::from_secs(5));
+    }
+
+    #[test]
+    fn scan_case_insensitive_keywords() {
+        let mut scanner = Scanner::new("SELECT * FROM Users WHERE Id = 1".to_string());
+        let mut tokens = vec![];
+        loop {
+            let token = scanner.scan_token().unwrap();
+            if token.kind == TokenKind::Eof {
+                break;
+            }
+            tokens.push((token.kind, token.lexeme));
+        }
+        assert_eq!(
+            vec![
+                (TokenKind::Select, "SELECT".to_string()),
+                (TokenKind::Star, "*".to_string()),
+                (TokenKind::From, "FROM".to_string()),
+                (TokenKind::Identifier, "Users".to_string()),
+                (TokenKind::Where, "WHERE".to_string()),
+                (TokenKind::Identifier, "Id".to_string()),
+                (TokenKind::Equal, "=".to_string()),
+                (TokenKind::Number, "1".to_string()),
+            ],
+            tokens
+        );
+    }
+
+    #[test]
+    fn scan_property_keywords() {
+        let mut scanner = Scanner::new(
+            "forall Exists foreign exclusive forallx deadlock_free deadlock_expected deadlock"
+                .to_string(),
+        );
+        let mut kinds = vec![];
+        loop {
+            let token = scanner.scan_token().unwrap();
+            if token.kind == TokenKind::Eof {
+                break;
+            }
+            kinds.push(token.kind);
+        }
+        assert_eq!(
+            vec![
+                TokenKind::Forall,
+                TokenKind::Exists,
+                TokenKind::Foreign,
+                TokenKind::Exclusive,
+                TokenKind::Identifier,
+                TokenKind::DeadlockFree,
+                TokenKind::DeadlockExpected,
+                TokenKind::Identifier,
+            ],
+            kinds
+        );
+    }
+
+    #[test]
+    fn scan_line_comment() {
+        assert_eq!(
+            vec![
+                TokenKind::Let,
+                TokenKind::Newline,
+                TokenKind::Identifier,
+                TokenKind::Eof
+            ],
+            kinds("let -- comment := 1\nx")
+        );
+    }
+
+    #[test]
+    fn scan_comment_at_eof() {
+        assert_eq!(
+            vec![TokenKind::Identifier, TokenKind::Eof],
+            kinds("x -- comment")
+        );
+        assert_eq!(
+            vec![TokenKind::Identifier, TokenKind::Eof],
+            kinds("x /* comment */")
+        );
+    }
+
+    #[test]
+    fn scan_flat_block_comment() {
+        assert_eq!(
+            vec![
+                TokenKind::Identifier,
+                TokenKind::Star,
+                TokenKind::Slash,
+                TokenKind::Eof
+            ],
+            kinds("/* a /* b */ c */")
+        );
+    }
+
+    #[test]
+    fn scan_multiline_block_comment() {
+        let mut scanner = Scanner::new("/* a\n b */ x".to_string());
+        let token = scanner.scan_token().unwrap();
+        assert_eq!(TokenKind::Identifier, token.kind);
+        assert_eq!(
+            (2, 7),
+            (token.position.start_line, token.position.start_col)
+        );
+    }
+
+    fn string_lexeme(source: &str) -> String {
+        let mut scanner = Scanner::new(source.to_string());
+        let token = scanner.scan_token().unwrap();
+        assert_eq!(TokenKind::String, token.kind);
+        token.lexeme
+    }
+
+    #[test]
+    fn scan_string_escapes() {
+        assert_eq!("'it's'", string_lexeme("'it''s'"));
+        assert_eq!("'it's'", string_lexeme("'it\\'s'"));
+        assert_eq!("'a\nb'", string_lexeme("'a\\nb'"));
+        assert_eq!("'a\tb'", string_lexeme("'a\\tb'"));
+        assert_eq!("'a\\b'", string_lexeme("'a\\\\b'"));
+    }
+
+    #[test]
+    fn scan_string_escapes_position() {
+        let mut scanner = Scanner::new("'it''s' x".to_string());
+        let token = scanner.scan_token().unwrap();
+        assert_eq!(
+            (1, 1, 1, 8),
+            (
+                token.position.start_line,
+                token.position.start_col,
+                token.position.end_line,
+                token.position.end_col
+            )
+        );
+        let token = scanner.scan_token().unwrap();
+        assert_eq!(
+            (1, 9),
+            (token.position.start_line, token.position.start_col)
+        );
+    }
+
+    #[test]
+    fn scan_unknown_escape() {
+        let mut scanner = Scanner::new("'a\\qb'".to_string());
+        let err = scanner.scan_token().unwrap_err();
+        assert!(err.expected.starts_with("Unknown escape sequence"));
+    }
+
+    #[test]
+    fn scan_unterminated_string() {
+        let mut scanner = Scanner::new("'abc".to_string());
+        let err = scanner.scan_token().unwrap_err();
+        assert_eq!("Unterminated string literal", err.expected);
+        assert_eq!("'abc", err.lexeme);
+        assert_eq!((1, 1), (err.position.start_line, err.position.start_col));
+
+        let mut scanner = Scanner::new("'abc\n'".to_string());
+        let err = scanner.scan_token().unwrap_err();
+        assert_eq!("Unterminated string literal", err.expected);
+    }
+
+    #[test]
+    fn scan_unterminated_block_comment() {
+        let mut scanner = Scanner::new("x /* comment".to_string());
+        scanner.scan_token().unwrap();
+        let err = scanner.scan_token().unwrap_err();
+        assert_eq!("Unterminated block comment, expected */", err.expected);
+        assert_eq!((1, 3), (err.position.start_line, err.position.start_col));
     }
 }
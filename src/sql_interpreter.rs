@@ -1,36 +1,110 @@
-use std::collections::HashMap;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
 
 use crate::engine::Value;
-use crate::parser::{IsolationLevel, Item, SelectItem, SqlExpression, SqlOperator, Variable};
+use crate::parser::{
+    InsertSource, IsolationLevel, Item, LockMode, LockWait, Locking, OnConflict, ReturningItem,
+    SelectItem, SqlExpression, SqlOperator, Type, Variable,
+};
 use crate::sql_interpreter::SqlEngineError::{SqlTypeError, UnknownVariable};
 
-#[derive(Hash, Eq, PartialEq, Debug, Clone)]
+#[derive(Hash, Eq, PartialEq, PartialOrd, Ord, Debug, Clone)]
 pub struct HashableRow {
     keys: Vec<String>,
     values: Vec<Value>,
 }
 
+#[derive(Hash, Eq, PartialEq, PartialOrd, Ord, Debug, Clone)]
+pub struct HashableTable {
+    name: String,
+    types: Vec<(String, Type)>,
+    rows: Vec<HashableRow>,
+}
+
+// Transactions in the same state whatever the order their statements interleaved with others
+#[derive(Hash, Eq, PartialEq, PartialOrd, Ord, Debug, Clone)]
+pub struct HashableTransaction {
+    isolation: IsolationLevel,
+    read_only: bool,
+    snapshot: Option<Vec<HashableTable>>,
+    // In the order they ran, a rollback to a savepoint drops the latest ones
+    changes: Vec<HashableChange>,
+    locks: Vec<Lock>,
+    reads: Vec<RowId>,
+    read_predicates: Vec<Lock>,
+    concurrent_writes: Vec<RowId>,
+    concurrent_inserts: Vec<(String, HashableRow)>,
+    savepoints: Vec<(String, usize, usize)>,
+}
+
+#[derive(Hash, Eq, PartialEq, PartialOrd, Ord, Debug, Clone)]
+enum HashableChange {
+    Insert(String, RowId, HashableRow),
+    Update(String, RowId, HashableRow),
+    Delete(String, RowId),
+    Drop(String),
+}
+
 #[derive(PartialEq, Debug, Clone)]
 pub struct Row {
     pub tuples: HashMap<String, Value>,
     rid: RowId,
+    // The row of the joined table a joined row was built from
+    joined: Option<RowId>,
 }
 
 impl Row {
     pub fn to_value(&self, columns: &[String]) -> Value {
         if columns.len() == 1 {
-            self.tuples.get(&columns[0]).unwrap().clone()
+            self.get(&columns[0]).clone()
         } else {
             let mut res = vec![];
             for col in columns {
-                res.push(self.tuples.get(col).unwrap().clone())
+                res.push(self.get(col).clone())
             }
             Value::Tuple(res)
         }
     }
 
-    fn hash(self) -> HashableRow {
-        let (keys, values): (Vec<String>, Vec<Value>) = self.tuples.into_iter().unzip();
+    fn get(&self, column: &str) -> &Value {
+        match self.tuples.get(column) {
+            Some(value) => value,
+            None => {
+                let (_, name) = column.split_once('.').unwrap();
+                self.tuples.get(name).unwrap()
+            }
+        }
+    }
+
+    fn join(&self, left: &str, right: &Row, right_table: &str) -> Row {
+        let mut tuples = HashMap::new();
+        for (table, row, other) in [(left, self, right), (right_table, right, self)] {
+            for (col, value) in &row.tuples {
+                tuples.insert(format!("{table}.{col}"), value.clone());
+                if !other.tuples.contains_key(col) {
+                    tuples.insert(col.clone(), value.clone());
+                }
+            }
+        }
+        Row {
+            tuples,
+            rid: self.rid,
+            joined: Some(right.rid),
+        }
+    }
+
+    fn rids(&self) -> impl Iterator<Item = RowId> {
+        std::iter::once(self.rid).chain(self.joined)
+    }
+
+    fn hash(&self) -> HashableRow {
+        let mut tuples: Vec<_> = self.tuples.iter().collect();
+        tuples.sort_by(|a, b| a.0.cmp(b.0));
+        let (keys, values) = tuples
+            .into_iter()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .unzip();
         HashableRow { keys, values }
     }
 }
@@ -38,26 +112,52 @@ impl Row {
 #[derive(PartialEq, Debug, Clone)]
 enum Changes {
     Insert(String, Row),
+    Update(String, Row),
     Delete(String, Row),
+    Drop(String),
 }
 
-#[derive(PartialEq, Eq, Debug, Clone, Hash)]
+#[derive(PartialEq, Eq, Debug, Clone, Hash, PartialOrd, Ord)]
 pub enum Lock {
     RowUpdate(RowId),
+    RowShare(RowId),
     RowForKeyShare(RowId),
     Unique(String, UniqueIndex, Value),
+    Predicate(String, Box<SqlExpression>),
+    Table(String),
+    TableWrite(String),
+}
+
+impl Locking {
+    fn lock(&self, rid: RowId) -> Lock {
+        match self.mode {
+            LockMode::Update => Lock::RowUpdate(rid),
+            LockMode::Share => Lock::RowShare(rid),
+        }
+    }
 }
 
 impl Lock {
+    pub fn waits_for(&self, held: &Self) -> bool {
+        self == held || self.conflicts(held) || held.conflicts(self)
+    }
+
     fn conflicts(&self, existing_lock: &Self) -> bool {
         match self {
             Lock::RowUpdate(rid) => match existing_lock {
                 Lock::RowUpdate(r) => r == rid,
+                Lock::RowShare(r) => r == rid,
                 Lock::RowForKeyShare(r) => r == rid,
-                Lock::Unique(_, _, _) => false,
+                _ => false,
             },
-            Lock::RowForKeyShare(rid) => matches!(existing_lock, Lock::RowUpdate(r) if r == rid),
-            Lock::Unique(_, _, _) => false,
+            Lock::RowShare(rid) | Lock::RowForKeyShare(rid) => {
+                matches!(existing_lock, Lock::RowUpdate(r) if r == rid)
+            }
+            Lock::Table(table) => {
+                matches!(existing_lock, Lock::Table(t) | Lock::TableWrite(t) if t == table)
+            }
+            Lock::TableWrite(table) => matches!(existing_lock, Lock::Table(t) if t == table),
+            Lock::Unique(_, _, _) | Lock::Predicate(_, _) => false,
         }
     }
 }
@@ -66,14 +166,139 @@ impl Lock {
 pub struct TransactionContext {
     changes: Vec<Changes>,
     pub locks: Vec<Lock>,
+    isolation: IsolationLevel,
+    read_only: bool,
+    snapshot: Option<HashMap<String, Arc<Table>>>,
+    reads: HashSet<RowId>,
+    read_predicates: Vec<Lock>,
+    concurrent_writes: HashSet<RowId>,
+    concurrent_inserts: Vec<(String, Row)>,
+    savepoints: Vec<(String, usize, usize)>,
 }
 
 impl TransactionContext {
-    fn new() -> Self {
+    pub fn hash(&self) -> HashableTransaction {
+        let sorted = |rids: &HashSet<RowId>| {
+            let mut rids: Vec<_> = rids.iter().copied().collect();
+            rids.sort();
+            rids
+        };
+        let mut locks = self.locks.clone();
+        locks.sort();
+        let mut read_predicates = self.read_predicates.clone();
+        read_predicates.sort();
+        let mut concurrent_inserts: Vec<_> = self
+            .concurrent_inserts
+            .iter()
+            .map(|(table, row)| (table.clone(), row.hash()))
+            .collect();
+        concurrent_inserts.sort();
+        HashableTransaction {
+            isolation: self.isolation,
+            read_only: self.read_only,
+            snapshot: self.snapshot.as_ref().map(hash_tables),
+            changes: self
+                .changes
+                .iter()
+                .map(|change| match change {
+                    Changes::Insert(table, row) => {
+                        HashableChange::Insert(table.clone(), row.rid, row.hash())
+                    }
+                    Changes::Update(table, row) => {
+                        HashableChange::Update(table.clone(), row.rid, row.hash())
+                    }
+                    Changes::Delete(table, row) => HashableChange::Delete(table.clone(), row.rid),
+                    Changes::Drop(table) => HashableChange::Drop(table.clone()),
+                })
+                .collect(),
+            locks,
+            reads: sorted(&self.reads),
+            read_predicates,
+            concurrent_writes: sorted(&self.concurrent_writes),
+            concurrent_inserts,
+            savepoints: self.savepoints.clone(),
+        }
+    }
+
+    fn new(
+        isolation: IsolationLevel,
+        read_only: bool,
+        snapshot: Option<HashMap<String, Arc<Table>>>,
+    ) -> Self {
         TransactionContext {
             changes: vec![],
             locks: vec![],
+            isolation,
+            read_only,
+            snapshot,
+            reads: HashSet::new(),
+            read_predicates: vec![],
+            concurrent_writes: HashSet::new(),
+            concurrent_inserts: vec![],
+            savepoints: vec![],
+        }
+    }
+
+    fn inserted_rows(&self) -> Vec<(String, Row)> {
+        self.changes
+            .iter()
+            .filter_map(|change| match change {
+                Changes::Insert(table, row) | Changes::Update(table, row) => {
+                    Some((table.clone(), row.clone()))
+                }
+                Changes::Delete(_, _) | Changes::Drop(_) => None,
+            })
+            .collect()
+    }
+
+    // Latest version of the rows the transaction inserted or updated in the table
+    fn own_rows(&self, table: &str) -> Vec<Row> {
+        let mut rows = vec![];
+        for change in &self.changes {
+            match change {
+                Changes::Insert(t, row) | Changes::Update(t, row) if t == table => {
+                    replace_row(&mut rows, row)
+                }
+                Changes::Delete(t, row) if t == table => rows.retain(|x| x.rid != row.rid),
+                Changes::Drop(t) if t == table => rows.clear(),
+                _ => {}
+            }
         }
+        rows
+    }
+
+    fn written_rows(&self) -> HashSet<RowId> {
+        self.changes
+            .iter()
+            .filter_map(|change| match change {
+                Changes::Update(_, row) | Changes::Delete(_, row) => Some(row.rid),
+                Changes::Insert(_, _) | Changes::Drop(_) => None,
+            })
+            .collect()
+    }
+
+    pub fn changed_rows(&self) -> HashSet<(String, RowId)> {
+        self.changes
+            .iter()
+            .filter_map(|change| match change {
+                Changes::Insert(table, row)
+                | Changes::Update(table, row)
+                | Changes::Delete(table, row) => Some((table.clone(), row.rid)),
+                Changes::Drop(_) => None,
+            })
+            .collect()
+    }
+
+    pub fn pending_changes(&self) -> Vec<(&'static str, &String, Option<&Row>)> {
+        self.changes
+            .iter()
+            .map(|change| match change {
+                Changes::Insert(table, row) => ("insert", table, Some(row)),
+                Changes::Update(table, row) => ("update", table, Some(row)),
+                Changes::Delete(table, row) => ("delete", table, Some(row)),
+                Changes::Drop(table) => ("drop", table, None),
+            })
+            .collect()
     }
 }
 
@@ -88,6 +313,9 @@ enum SqlContext {
         table: String,
         row: Row,
     },
+    Group {
+        rows: Vec<Row>,
+    },
 }
 
 #[derive(PartialEq, Default, Debug, Clone)]
@@ -95,9 +323,28 @@ pub struct Table {
     pub columns: Vec<String>,
     pub rows: Vec<Row>,
     pub unique: Vec<UniqueIndex>,
+    pub primary: Option<UniqueIndex>,
+    pub types: HashMap<String, Type>,
+    pub defaults: HashMap<String, SqlExpression>,
+    pub checks: Vec<SqlExpression>,
 }
 
-#[derive(PartialEq, Eq, Default, Debug, Clone, Hash)]
+impl Table {
+    // A column without declared type takes the type of the first value committed to it
+    fn infer_types(&mut self, row: &Row) {
+        for (column, value) in &row.tuples {
+            let kind = match value {
+                Value::Integer(_) => Type::Integer,
+                Value::String(_) => Type::String,
+                Value::Bool(_) => Type::Bool,
+                _ => continue,
+            };
+            self.types.entry(column.clone()).or_insert(kind);
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Default, Debug, Clone, Hash, PartialOrd, Ord)]
 pub struct UniqueIndex {
     columns: Vec<String>,
 }
@@ -108,39 +355,69 @@ pub struct ForeignKey {
     columns: Vec<String>,
     foreign_relation: String,
     foreign_columns: Vec<String>,
+    on_delete_cascade: bool,
 }
 
 impl UniqueIndex {
-    fn tuple_from(&self, row: &Row) -> Value {
+    // Null values never conflict: rows with a null indexed column have no tuple
+    fn tuple_from(&self, row: &Row) -> Option<Value> {
         let mut tuple = vec![];
         for c in &self.columns {
-            tuple.push(row.tuples.get(c).unwrap().clone())
+            match row.tuples.get(c).unwrap() {
+                Value::Null => return None,
+                value => tuple.push(value.clone()),
+            }
         }
-        Value::Tuple(tuple)
+        Some(Value::Tuple(tuple))
     }
 }
 
 #[derive(PartialEq, Debug, Clone)]
 pub struct SqlDatabase {
     pub cur_tx: TransactionId,
-    pub tables: HashMap<String, Table>,
+    pub tables: HashMap<String, Arc<Table>>,
     pub foreign_keys: Vec<ForeignKey>,
     pub transactions: HashMap<TransactionId, TransactionContext>,
     tx: TransactionId,
     rid: RowId,
     sql_context: Option<SqlContext>,
+    statement_start: usize,
 }
 
 impl SqlDatabase {
-    pub fn hash(&self) -> Vec<(String, Vec<HashableRow>)> {
-        let mut res = vec![];
-        for (name, table) in &self.tables {
-            res.push((
-                name.clone(),
-                table.rows.iter().map(|row| row.clone().hash()).collect(),
-            ));
+    // Rows inserted, updated or deleted between two states of the database
+    pub fn changed_rows(&self, after: &SqlDatabase) -> HashSet<(String, RowId)> {
+        let mut changed = HashSet::new();
+        let names: HashSet<_> = self.tables.keys().chain(after.tables.keys()).collect();
+        for name in names {
+            let (before, after) = (self.tables.get(name), after.tables.get(name));
+            if before.zip(after).is_some_and(|(b, a)| Arc::ptr_eq(b, a)) {
+                continue;
+            }
+            let rows =
+                |table: Option<&Arc<Table>>| table.map(|t| t.rows.clone()).unwrap_or_default();
+            let (before, after) = (rows(before), rows(after));
+            let removed = before.iter().filter(|row| !after.contains(row));
+            let added = after.iter().filter(|row| !before.contains(row));
+            for row in removed.chain(added) {
+                changed.insert((name.clone(), row.rid));
+            }
         }
-        res
+        changed
+    }
+
+    pub fn find_row(&self, rid: RowId) -> Option<(&String, &Row)> {
+        self.tables.iter().find_map(|(name, table)| {
+            table
+                .rows
+                .iter()
+                .find(|row| row.rid == rid)
+                .map(|row| (name, row))
+        })
+    }
+
+    pub fn hash(&self) -> Vec<HashableTable> {
+        hash_tables(&self.tables)
     }
 }
 
@@ -154,7 +431,7 @@ impl TransactionId {
     }
 }
 
-#[derive(PartialEq, Debug, Clone, Copy, Hash, Eq)]
+#[derive(PartialEq, Debug, Clone, Copy, Hash, Eq, PartialOrd, Ord)]
 pub struct RowId(usize);
 
 impl RowId {
@@ -167,10 +444,19 @@ impl RowId {
 #[derive(PartialEq, Debug, Clone)]
 pub enum SqlEngineError {
     Locked(Lock),
-    SqlTypeError(SqlExpression, String),
+    LockNotAvailable(Lock),
+    SqlTypeError(Box<SqlExpression>, String),
     UnicityViolation,
+    NotNullViolation(String),
+    CheckViolation(Box<SqlExpression>),
     ForeignKeyViolation,
+    SerializationFailure,
+    ReadOnlyTransaction,
+    TooManyRows(usize),
     UnknownVariable(String),
+    UnknownSavepoint(String),
+    ArithmeticOverflow(Box<SqlExpression>),
+    DivisionByZero(Box<SqlExpression>),
 }
 
 type Res<T> = Result<T, SqlEngineError>;
@@ -185,13 +471,27 @@ impl SqlDatabase {
             transactions: Default::default(),
             tx: TransactionId(0),
             sql_context: None,
+            statement_start: 0,
             rid: RowId(0),
         }
     }
 
-    pub fn open_transaction(&mut self, _isolation: IsolationLevel) -> TransactionId {
+    pub fn open_transaction(
+        &mut self,
+        isolation: IsolationLevel,
+        read_only: bool,
+    ) -> TransactionId {
         let new_tx = self.tx.increment();
-        self.transactions.insert(new_tx, TransactionContext::new());
+        let snapshot = match isolation {
+            IsolationLevel::ReadCommitted => None,
+            IsolationLevel::Serializable | IsolationLevel::SnapshotIsolation => {
+                Some(self.tables.clone())
+            }
+        };
+        self.transactions.insert(
+            new_tx,
+            TransactionContext::new(isolation, read_only, snapshot),
+        );
 
         new_tx
     }
@@ -200,13 +500,27 @@ impl SqlDatabase {
         self.cur_tx = if let Some(tx) = opt_tx {
             tx
         } else {
-            self.open_transaction(IsolationLevel::ReadCommitted)
+            self.open_transaction(IsolationLevel::ReadCommitted, false)
         };
 
-        let res = self.interpret(expr)?;
+        let changes = self.transactions.get(&self.cur_tx).unwrap().changes.len();
+        self.statement_start = changes;
+        let res = match self.interpret(expr) {
+            Ok(res) => res,
+            Err(err) => {
+                self.sql_context = None;
+                if opt_tx.is_none() {
+                    self.abort(&self.cur_tx.clone());
+                } else if !matches!(err, SqlEngineError::Locked(_)) {
+                    let transaction = self.transactions.get_mut(&self.cur_tx).unwrap();
+                    transaction.changes.truncate(changes);
+                }
+                return Err(err);
+            }
+        };
 
         if opt_tx.is_none() {
-            self.commit(&self.cur_tx.clone());
+            self.commit(&self.cur_tx.clone())?;
         }
 
         Ok(res)
@@ -217,16 +531,25 @@ impl SqlDatabase {
             SqlExpression::Select {
                 columns,
                 from,
+                join_table,
+                join_condition,
                 condition,
+                group_by,
+                having,
                 order_by,
+                descending,
                 limit,
                 offset,
                 locking,
             } => self.interpret_select(
                 columns,
                 from,
+                join_table.as_ref().zip(join_condition.as_deref()),
                 condition.as_deref().unwrap_or(&SqlExpression::Bool(true)),
+                group_by,
+                having.as_deref(),
                 order_by.as_deref().unwrap_or(&SqlExpression::Integer(0)),
+                *descending,
                 *limit,
                 offset.unwrap_or(0),
                 *locking,
@@ -242,21 +565,63 @@ impl SqlDatabase {
                 relation,
                 updates,
                 condition,
+                returning,
             } => self.interpret_update(
                 relation,
                 updates,
                 condition.as_deref().unwrap_or(&SqlExpression::Bool(true)),
+                returning,
             ),
             SqlExpression::Insert {
                 relation,
                 columns,
-                values,
-            } => self.interpret_insert(relation, columns, values),
-            SqlExpression::Create { relation, columns } => {
-                let table = self.tables.entry(relation.name.clone()).or_default();
-                table.unique.push(UniqueIndex {
+                source,
+                on_conflict,
+            } => self.interpret_insert(relation, columns, source, on_conflict.as_ref()),
+            SqlExpression::Create {
+                relation,
+                columns,
+                primary,
+            } => {
+                let table = Arc::make_mut(self.tables.entry(relation.name.clone()).or_default());
+                let index = UniqueIndex {
                     columns: columns.iter().map(|c| c.name.clone()).collect(),
-                });
+                };
+                if *primary {
+                    table.primary = Some(index.clone());
+                }
+                table.unique.push(index);
+                Ok(Value::Nil)
+            }
+            SqlExpression::Union { left, right, all } => self.interpret_union(left, right, *all),
+            SqlExpression::CreateTable { relation, columns } => {
+                let table = Arc::make_mut(self.tables.entry(relation.name.clone()).or_default());
+                table.columns = columns.iter().map(|c| c.name.name.clone()).collect();
+                table.types = columns
+                    .iter()
+                    .map(|c| (c.name.name.clone(), c.kind))
+                    .collect();
+                table.defaults = columns
+                    .iter()
+                    .filter_map(|c| Some((c.name.name.clone(), c.default.clone()?)))
+                    .collect();
+                table.checks = columns.iter().filter_map(|c| c.check.clone()).collect();
+                Ok(Value::Nil)
+            }
+            SqlExpression::LockTable(relation) => {
+                self.request_row_lock(Lock::Table(relation.name.clone()))?;
+                Ok(Value::Nil)
+            }
+            SqlExpression::Drop(relation) => {
+                self.clear_table(&relation.name)?;
+                let transaction = self.transactions.get_mut(&self.cur_tx).unwrap();
+                transaction
+                    .changes
+                    .push(Changes::Drop(relation.name.clone()));
+                Ok(Value::Nil)
+            }
+            SqlExpression::Truncate(relation) => {
+                self.clear_table(&relation.name)?;
                 Ok(Value::Nil)
             }
             SqlExpression::Alter {
@@ -264,6 +629,7 @@ impl SqlDatabase {
                 columns,
                 reference_relation,
                 reference_columns,
+                on_delete_cascade,
                 ..
             } => {
                 self.foreign_keys.push(ForeignKey {
@@ -271,6 +637,7 @@ impl SqlDatabase {
                     columns: columns.iter().map(|c| c.name.clone()).collect(),
                     foreign_relation: reference_relation.name.clone(),
                     foreign_columns: reference_columns.iter().map(|c| c.name.clone()).collect(),
+                    on_delete_cascade: *on_delete_cascade,
                 });
                 Ok(Value::Nil)
             }
@@ -287,11 +654,18 @@ impl SqlDatabase {
                 }
                 Ok(Value::Tuple(res))
             }
-            SqlExpression::Var(var) => {
-                if let Some(SqlContext::Where { row, .. }) = &self.sql_context {
-                    Ok(row.tuples.get(&var.name).unwrap().clone())
+            SqlExpression::Var(var) => match &self.sql_context {
+                Some(SqlContext::Where { row, .. }) | Some(SqlContext::Update { row, .. }) => {
+                    Ok(row.get(&var.name).clone())
+                }
+                Some(SqlContext::Group { rows }) => Ok(rows[0].get(&var.name).clone()),
+                _ => Err(UnknownVariable(var.name.clone())),
+            },
+            SqlExpression::Aggregate(item) => {
+                if let Some(SqlContext::Group { rows }) = &self.sql_context {
+                    aggregate(item, &rows.iter().collect::<Vec<_>>())
                 } else {
-                    Err(UnknownVariable(var.name.clone()))
+                    Err(UnknownVariable(item.to_string()))
                 }
             }
             SqlExpression::UpVariable(_) => panic!("UpVariable should not be interpreted directly"),
@@ -307,11 +681,66 @@ impl SqlDatabase {
                 panic!()
             }
             SqlExpression::String(s) => Ok(Value::String(s.clone())),
+            SqlExpression::Null => Ok(Value::Null),
             SqlExpression::Bool(b) => Ok(Value::Bool(*b)),
             SqlExpression::Scalar(expr) => Ok(Value::Scalar(Box::new(self.interpret(expr)?))),
+            SqlExpression::Subquery(select) => match self.subquery(select)? {
+                Value::Set(rows) => scalar(rows),
+                value => Ok(value),
+            },
+            SqlExpression::Not(expr) => match self.nullable_bool(expr)? {
+                Some(b) => Ok(Value::Bool(!b)),
+                None => Ok(Value::Null),
+            },
         }
     }
 
+    // Rows of a union are sorted, to not depend on the order rows were inserted
+    fn interpret_union(
+        &mut self,
+        left: &SqlExpression,
+        right: &SqlExpression,
+        all: bool,
+    ) -> Res<Value> {
+        let mut rows = vec![];
+        let mut arity = None;
+        for select in [left, right] {
+            let values = match self.interpret(select)? {
+                Value::Set(values) => values,
+                value => vec![value],
+            };
+            for value in values {
+                let columns = match &value {
+                    Value::Tuple(columns) => columns.len(),
+                    _ => 1,
+                };
+                if *arity.get_or_insert(columns) != columns {
+                    return Err(SqlTypeError(
+                        Box::new(select.clone()),
+                        format!("{} columns", arity.unwrap()),
+                    ));
+                }
+                if all || !rows.contains(&value) {
+                    rows.push(value);
+                }
+            }
+        }
+        rows.sort_by(order);
+        Ok(Value::Set(rows))
+    }
+
+    // Subqueries do not see the changes of the statement being executed
+    fn subquery(&mut self, select: &SqlExpression) -> Res<Value> {
+        let transaction = self.transactions.get_mut(&self.cur_tx).unwrap();
+        let pending = transaction.changes.split_off(self.statement_start);
+        let context = self.sql_context.take();
+        let res = self.interpret(select);
+        self.sql_context = context;
+        let transaction = self.transactions.get_mut(&self.cur_tx).unwrap();
+        transaction.changes.extend(pending);
+        res
+    }
+
     fn interpret_binary(
         &mut self,
         left: &SqlExpression,
@@ -320,79 +749,143 @@ impl SqlDatabase {
     ) -> Res<Value> {
         match operator {
             SqlOperator::And => {
-                let left = self.assert_bool(left)?;
-                let right = self.assert_bool(right)?;
-                Ok(Value::Bool(left && right))
-            }
-            SqlOperator::Add => {
-                let left = self.assert_integer(left)?;
-                let right = self.assert_integer(right)?;
-                Ok(Value::Integer(left + right))
-            }
-            SqlOperator::Subtract => {
-                let left = self.assert_integer(left)?;
-                let right = self.assert_integer(right)?;
-                Ok(Value::Integer(left - right))
-            }
-            SqlOperator::Multiply => {
-                let left = self.assert_integer(left)?;
-                let right = self.assert_integer(right)?;
-                Ok(Value::Integer(left * right))
-            }
-            SqlOperator::Divide => {
-                let left = self.assert_integer(left)?;
-                let right = self.assert_integer(right)?;
-                Ok(Value::Integer(left / right))
-            }
-            SqlOperator::Rem => {
-                let left = self.assert_integer(left)?;
-                let right = self.assert_integer(right)?;
-                Ok(Value::Integer(left % right))
-            }
-            SqlOperator::Equal => {
-                let left = self.interpret(left)?;
-                let right = self.interpret(right)?;
-                Ok(Value::Bool(left == right))
+                let left = self.nullable_bool(left)?;
+                let right = self.nullable_bool(right)?;
+                match (left, right) {
+                    (Some(false), _) | (_, Some(false)) => Ok(Value::Bool(false)),
+                    (Some(true), Some(true)) => Ok(Value::Bool(true)),
+                    _ => Ok(Value::Null),
+                }
             }
-            SqlOperator::NotEqual => {
+            SqlOperator::Or => {
+                let left = self.nullable_bool(left)?;
+                if left == Some(true) {
+                    return Ok(Value::Bool(true));
+                }
+                match (left, self.nullable_bool(right)?) {
+                    (_, Some(true)) => Ok(Value::Bool(true)),
+                    (Some(false), Some(false)) => Ok(Value::Bool(false)),
+                    _ => Ok(Value::Null),
+                }
+            }
+            SqlOperator::Equal | SqlOperator::NotEqual => {
                 let left = self.interpret(left)?;
                 let right = self.interpret(right)?;
-                Ok(Value::Bool(left != right))
+                if left == Value::Null || right == Value::Null {
+                    Ok(Value::Null)
+                } else if operator == &SqlOperator::Equal {
+                    Ok(Value::Bool(left == right))
+                } else {
+                    Ok(Value::Bool(left != right))
+                }
             }
-            SqlOperator::Less => {
-                let left = self.assert_integer(left)?;
-                let right = self.assert_integer(right)?;
-                Ok(Value::Bool(left < right))
+            SqlOperator::Add
+            | SqlOperator::Subtract
+            | SqlOperator::Multiply
+            | SqlOperator::Divide
+            | SqlOperator::Rem => {
+                let expression = || {
+                    Box::new(SqlExpression::Binary {
+                        left: Box::new(left.clone()),
+                        operator: operator.clone(),
+                        right: Box::new(right.clone()),
+                    })
+                };
+                let (Some(left), Some(right)) =
+                    (self.nullable_integer(left)?, self.nullable_integer(right)?)
+                else {
+                    return Ok(Value::Null);
+                };
+                let checked = |result: Option<i64>| {
+                    result
+                        .map(Value::Integer)
+                        .ok_or_else(|| SqlEngineError::ArithmeticOverflow(expression()))
+                };
+                match operator {
+                    SqlOperator::Add => checked(left.checked_add(right)),
+                    SqlOperator::Subtract => checked(left.checked_sub(right)),
+                    SqlOperator::Multiply => checked(left.checked_mul(right)),
+                    SqlOperator::Divide | SqlOperator::Rem if right == 0 => {
+                        Err(SqlEngineError::DivisionByZero(expression()))
+                    }
+                    SqlOperator::Divide => checked(left.checked_div(right)),
+                    _ => checked(left.checked_rem(right)),
+                }
             }
-            SqlOperator::LessEqual => {
-                let left = self.assert_integer(left)?;
-                let right = self.assert_integer(right)?;
-                Ok(Value::Bool(left <= right))
+            SqlOperator::Less
+            | SqlOperator::LessEqual
+            | SqlOperator::Greater
+            | SqlOperator::GreaterEqual => {
+                let Some(ordering) = self.nullable_ordering(left, right)? else {
+                    return Ok(Value::Null);
+                };
+                Ok(Value::Bool(match operator {
+                    SqlOperator::Less => ordering.is_lt(),
+                    SqlOperator::LessEqual => ordering.is_le(),
+                    SqlOperator::Greater => ordering.is_gt(),
+                    _ => ordering.is_ge(),
+                }))
             }
-            SqlOperator::Greater => {
-                let left = self.assert_integer(left)?;
-                let right = self.assert_integer(right)?;
-                Ok(Value::Bool(left > right))
+            SqlOperator::Like => {
+                let (Some(value), Some(pattern)) =
+                    (self.nullable_string(left)?, self.nullable_string(right)?)
+                else {
+                    return Ok(Value::Null);
+                };
+                let value: Vec<char> = value.chars().collect();
+                let pattern: Vec<char> = pattern.chars().collect();
+                Ok(Value::Bool(like(&value, &pattern)))
             }
-            SqlOperator::GreaterEqual => {
-                let left = self.assert_integer(left)?;
-                let right = self.assert_integer(right)?;
-                Ok(Value::Bool(left >= right))
+            SqlOperator::Is | SqlOperator::IsNot => {
+                let is_null = self.interpret(left)? == Value::Null;
+                Ok(Value::Bool(is_null == (operator == &SqlOperator::Is)))
             }
-            SqlOperator::In => {
-                let left = self.interpret(left)?;
-                let right = self.assert_set(right)?;
-                Ok(Value::Bool(right.contains(&left)))
+            SqlOperator::In | SqlOperator::NotIn => {
+                // A parenthesized list is a tuple when compared to the rows of a subquery
+                let left = match (left, right) {
+                    (SqlExpression::Set(members), SqlExpression::Subquery(_)) => Value::Tuple(
+                        members
+                            .iter()
+                            .map(|member| self.interpret(member))
+                            .collect::<Res<_>>()?,
+                    ),
+                    (left, _) => self.interpret(left)?,
+                };
+                let right = match right {
+                    SqlExpression::Subquery(select) => match self.subquery(select)? {
+                        Value::Set(rows) => rows,
+                        row => vec![row],
+                    },
+                    _ => self.assert_set(right)?,
+                };
+                // Null could be any value: a null member makes a missing value unknown
+                let found = if right.is_empty() {
+                    Some(false)
+                } else if left == Value::Null {
+                    None
+                } else if right.contains(&left) {
+                    Some(true)
+                } else if right.contains(&Value::Null) {
+                    None
+                } else {
+                    Some(false)
+                };
+                Ok(match found {
+                    Some(found) => Value::Bool(found == (operator == &SqlOperator::In)),
+                    None => Value::Null,
+                })
             }
             SqlOperator::Between => {
-                if let SqlExpression::Tuple(tuples) = (*right).clone() {
-                    let left = self.assert_integer(left)?;
-                    let lower = self.assert_integer(&tuples[0])?;
-                    let upper = self.assert_integer(&tuples[1])?;
-                    Ok(Value::Bool(left >= lower && left <= upper))
-                } else {
-                    panic!()
-                }
+                let SqlExpression::Tuple(bounds) = right else {
+                    return Err(SqlTypeError(Box::new(right.clone()), "tuple".to_string()));
+                };
+                let (Some(lower), Some(upper)) = (
+                    self.nullable_ordering(left, &bounds[0])?,
+                    self.nullable_ordering(left, &bounds[1])?,
+                ) else {
+                    return Ok(Value::Null);
+                };
+                Ok(Value::Bool(lower.is_ge() && upper.is_le()))
             }
         }
     }
@@ -401,24 +894,80 @@ impl SqlDatabase {
         &mut self,
         relation: &Variable,
         columns: &[Variable],
-        exprs: &[SqlExpression],
+        source: &InsertSource,
+        on_conflict: Option<&OnConflict>,
     ) -> Res<Value> {
         let mut values = vec![];
-        for expr in exprs {
-            values.push(self.assert_tuple(expr)?)
+        match source {
+            InsertSource::Values(exprs) => {
+                for expr in exprs {
+                    values.push(self.assert_tuple(expr)?)
+                }
+            }
+            InsertSource::Select(select) => {
+                let rows = match self.interpret(select)? {
+                    Value::Set(rows) => rows,
+                    row => vec![row],
+                };
+                for row in rows {
+                    values.push(match row {
+                        Value::Tuple(tuple) => tuple,
+                        value => vec![value],
+                    })
+                }
+            }
         }
 
         let table = &relation.name;
+        self.request_table_write(table)?;
+        let mut inserted = 0;
         for value in values {
             let mut new_tuples = HashMap::new();
             for (i, col) in columns.iter().enumerate() {
                 new_tuples.insert(col.name.clone(), value[i].clone());
             }
+            // Known columns of the table missing from the insert take their default value or null
+            if let Some(t) = self.tables.get(table).cloned() {
+                for column in &t.columns {
+                    if !new_tuples.contains_key(column) {
+                        let value = match t.defaults.get(column) {
+                            Some(default) => self.interpret(default)?,
+                            None => Value::Null,
+                        };
+                        new_tuples.insert(column.clone(), value);
+                    }
+                }
+            }
             let new_row = Row {
+                rid: self.row_id(table, &new_tuples),
                 tuples: new_tuples,
-                rid: self.rid.increment(),
+                joined: None,
             };
-            self.check_unique_values(table, &new_row)?;
+            self.check_types(table, &new_row)?;
+            self.check_constraints(table, &new_row)?;
+            self.check_primary_key(table, &new_row)?;
+            match self.check_unique_values(table, &new_row) {
+                Err(SqlEngineError::UnicityViolation) => match on_conflict {
+                    Some(OnConflict::Nothing) => continue,
+                    Some(OnConflict::Update { columns, updates }) => {
+                        let Some(existing) = self.conflicting_row(table, columns, &new_row) else {
+                            return Err(SqlEngineError::UnicityViolation);
+                        };
+                        self.sql_context = Some(SqlContext::Update {
+                            tx: self.cur_tx,
+                            row: existing.clone(),
+                            table: table.clone(),
+                        });
+                        self.updates(updates, table, &existing)?;
+                        self.sql_context = None;
+                        inserted += 1;
+                        continue;
+                    }
+                    None => return Err(SqlEngineError::UnicityViolation),
+                },
+                res => res?,
+            }
+            self.check_predicate_locks(table, &new_row)?;
             let foreign_rows = self.check_foreign_key(table, &new_row)?;
 
             for rid in foreign_rows {
@@ -429,29 +978,35 @@ impl SqlDatabase {
 
             let t = self.tables.entry(table.clone()).or_default();
             if t.columns.is_empty() {
-                t.columns = columns.iter().map(|c| c.name.clone()).collect();
+                Arc::make_mut(t).columns = columns.iter().map(|c| c.name.clone()).collect();
             }
             for unique in &t.unique {
-                transaction.locks.push(Lock::Unique(
-                    table.clone(),
-                    unique.clone(),
-                    unique.tuple_from(&new_row),
-                ));
+                if let Some(tuple) = unique.tuple_from(&new_row) {
+                    transaction
+                        .locks
+                        .push(Lock::Unique(table.clone(), unique.clone(), tuple));
+                }
             }
 
             transaction
                 .changes
                 .push(Changes::Insert(table.to_string(), new_row));
+            inserted += 1;
         }
-        Ok(Value::Nil)
+        Ok(Value::Integer(inserted))
     }
 
     fn interpret_delete(&mut self, relation: &Variable, condition: &SqlExpression) -> Res<Value> {
         let table = &relation.name;
+        self.request_table_write(table)?;
         let rows = self.rows(&self.cur_tx, table);
 
         let mut mutated = 0;
+        let mut deleted = HashSet::new();
         for row in &rows {
+            if deleted.contains(&row.rid) {
+                continue;
+            }
             self.sql_context = Some(SqlContext::Where {
                 row: row.clone(),
                 table: table.clone(),
@@ -463,37 +1018,7 @@ impl SqlDatabase {
                     table: table.clone(),
                 });
 
-                let mut cascade_rows = vec![];
-                for foreign_key in &self.foreign_keys {
-                    if &foreign_key.foreign_relation == table {
-                        let foreign_rows = self.rows(&self.cur_tx, &foreign_key.relation);
-                        for cascade_row in foreign_rows {
-                            let mut p = foreign_key
-                                .foreign_columns
-                                .iter()
-                                .zip(foreign_key.columns.iter());
-                            if p.all(|(col, f_col)| {
-                                row.tuples.get(col).unwrap()
-                                    == cascade_row.tuples.get(f_col).unwrap()
-                            }) {
-                                cascade_rows.push((foreign_key.relation.clone(), cascade_row));
-                            }
-                        }
-                    }
-                }
-
-                self.request_row_lock(Lock::RowUpdate(row.rid))?;
-
-                let transaction = self.transactions.get_mut(&self.cur_tx).unwrap();
-
-                transaction
-                    .changes
-                    .push(Changes::Delete(table.clone(), row.clone()));
-                for (f_table, cascade_row) in cascade_rows {
-                    transaction
-                        .changes
-                        .push(Changes::Delete(f_table, cascade_row.clone()));
-                }
+                self.delete_row(table, row, &mut deleted)?;
 
                 mutated += 1;
             }
@@ -503,16 +1028,79 @@ impl SqlDatabase {
         Ok(Value::Integer(mutated))
     }
 
+    // Rows already deleted by the statement are skipped, so self-referencing cascades terminate
+    fn delete_row(&mut self, table: &String, row: &Row, deleted: &mut HashSet<RowId>) -> Unit {
+        deleted.insert(row.rid);
+        self.request_row_lock(Lock::RowUpdate(row.rid))?;
+
+        for foreign_key in self.foreign_keys.clone() {
+            if &foreign_key.foreign_relation == table {
+                let foreign_rows = self.rows(&self.cur_tx, &foreign_key.relation);
+                for foreign_row in foreign_rows {
+                    if deleted.contains(&foreign_row.rid) {
+                        continue;
+                    }
+                    let mut p = foreign_key
+                        .foreign_columns
+                        .iter()
+                        .zip(foreign_key.columns.iter());
+                    if p.all(|(col, f_col)| {
+                        row.tuples.get(col).unwrap() == foreign_row.tuples.get(f_col).unwrap()
+                    }) {
+                        if !foreign_key.on_delete_cascade {
+                            return Err(SqlEngineError::ForeignKeyViolation);
+                        }
+                        self.delete_row(&foreign_key.relation, &foreign_row, deleted)?;
+                    }
+                }
+            }
+        }
+
+        let transaction = self.transactions.get_mut(&self.cur_tx).unwrap();
+        transaction
+            .changes
+            .push(Changes::Delete(table.clone(), row.clone()));
+        Ok(())
+    }
+
+    // Like postgres, tables referenced by another table cannot be dropped or truncated
+    fn clear_table(&mut self, table: &String) -> Unit {
+        if self
+            .foreign_keys
+            .iter()
+            .any(|fk| &fk.foreign_relation == table && &fk.relation != table)
+        {
+            return Err(SqlEngineError::ForeignKeyViolation);
+        }
+        self.request_table_write(table)?;
+        let lock = Lock::Table(table.clone());
+        if !self.transactions[&self.cur_tx].locks.contains(&lock) {
+            self.request_row_lock(lock)?;
+        }
+
+        for row in self.rows(&self.cur_tx, table) {
+            self.request_row_lock(Lock::RowUpdate(row.rid))?;
+            let transaction = self.transactions.get_mut(&self.cur_tx).unwrap();
+            transaction
+                .changes
+                .push(Changes::Delete(table.clone(), row));
+        }
+        Ok(())
+    }
+
     fn interpret_update(
         &mut self,
         relation: &Variable,
         updates: &[SqlExpression],
         condition: &SqlExpression,
+        returning: &[ReturningItem],
     ) -> Res<Value> {
         let table = &relation.name;
+        self.request_table_write(table)?;
         let rows = self.rows(&self.cur_tx, table);
 
         let mut mutated = 0;
+        let mut returned = vec![];
         for row in rows {
             self.sql_context = Some(SqlContext::Where {
                 row: row.clone(),
@@ -524,13 +1112,70 @@ impl SqlDatabase {
                     row: row.clone(),
                     table: table.clone(),
                 });
-                self.updates(updates, table, &row)?;
+                let new_row = self.updates(updates, table, &row)?;
+                if !returning.is_empty() {
+                    returned.push(self.returned_value(table, returning, &row, &new_row));
+                }
                 mutated += 1;
             }
             self.sql_context = None;
         }
 
-        Ok(Value::Integer(mutated))
+        if returning.is_empty() {
+            Ok(Value::Integer(mutated))
+        } else if returned.len() == 1 {
+            Ok(returned.remove(0))
+        } else {
+            Ok(Value::Set(returned))
+        }
+    }
+
+    fn returned_value(
+        &self,
+        table: &String,
+        returning: &[ReturningItem],
+        old_row: &Row,
+        new_row: &Row,
+    ) -> Value {
+        let columns = &self.tables.get(table).unwrap().columns;
+        let mut values = vec![];
+        for item in returning {
+            let (row, item) = match item {
+                ReturningItem::Old(item) => (old_row, item),
+                ReturningItem::New(item) => (new_row, item),
+            };
+            match item {
+                Item::Wildcard => values.push(row.to_value(columns)),
+                Item::Column(col) => values.push(row.to_value(std::slice::from_ref(col))),
+            }
+        }
+
+        if values.len() == 1 {
+            values.remove(0)
+        } else {
+            Value::Tuple(values)
+        }
+    }
+
+    fn join_rows(&mut self, from: &Variable, join: &Variable, on: &SqlExpression) -> Res<Vec<Row>> {
+        let left = self.rows(&self.cur_tx, &from.name);
+        let right = self.rows(&self.cur_tx, &join.name);
+
+        let mut res = vec![];
+        for l in &left {
+            for r in &right {
+                let row = l.join(&from.name, r, &join.name);
+                self.sql_context = Some(SqlContext::Where {
+                    row: row.clone(),
+                    table: from.name.clone(),
+                });
+                if self.interpret(on)? == Value::Bool(true) {
+                    res.push(row);
+                }
+                self.sql_context = None;
+            }
+        }
+        Ok(res)
     }
 
     #[allow(clippy::too_many_arguments)]
@@ -538,13 +1183,20 @@ impl SqlDatabase {
         &mut self,
         item_list: &[SelectItem],
         from: &Variable,
+        join: Option<(&Variable, &SqlExpression)>,
         condition: &SqlExpression,
+        group_by: &[Item],
+        having: Option<&SqlExpression>,
         order_by: &SqlExpression,
-        limit: Option<i16>,
-        offset: i16,
-        for_update: bool,
+        descending: bool,
+        limit: Option<i64>,
+        offset: i64,
+        locking: Option<Locking>,
     ) -> Res<Value> {
-        let rows = self.rows(&self.cur_tx, &from.name);
+        let rows = match join {
+            Some((table, on)) => self.join_rows(from, table, on)?,
+            None => self.rows(&self.cur_tx, &from.name),
+        };
 
         let mut res = vec![];
         for row in &rows {
@@ -552,41 +1204,132 @@ impl SqlDatabase {
                 row: row.clone(),
                 table: from.name.clone(),
             });
-            if for_update {
-                self.request_row_lock(Lock::RowUpdate(row.rid))?;
-            }
             if self.interpret(condition)? == Value::Bool(true) {
-                res.push(row)
+                let skipped = locking.is_some_and(|locking| {
+                    locking.wait == LockWait::SkipLocked
+                        && row
+                            .rids()
+                            .any(|rid| self.locked_by_other(&locking.lock(rid)))
+                });
+                if !skipped {
+                    res.push(row)
+                }
             }
             self.sql_context = None;
         }
 
-        res.sort_by(|left, right| {
-            self.sql_context = Some(SqlContext::Where {
-                row: (*left).clone(),
-                table: from.name.clone(),
-            });
-            let l = self.interpret(order_by).unwrap();
-
-            self.sql_context = Some(SqlContext::Where {
-                row: (*right).clone(),
-                table: from.name.clone(),
-            });
-            let r = self.interpret(order_by).unwrap();
+        let transaction = self.transactions.get_mut(&self.cur_tx).unwrap();
+        if transaction.isolation == IsolationLevel::Serializable {
+            transaction
+                .reads
+                .extend(res.iter().flat_map(|row| row.rids()));
+            // Any row inserted in a joined table could join, the whole tables are read
+            let predicates = match join {
+                Some((table, _)) => vec![
+                    Lock::Predicate(from.name.clone(), Box::new(SqlExpression::Bool(true))),
+                    Lock::Predicate(table.name.clone(), Box::new(SqlExpression::Bool(true))),
+                ],
+                None => vec![Lock::Predicate(
+                    from.name.clone(),
+                    Box::new(condition.clone()),
+                )],
+            };
+            for predicate in predicates {
+                if !transaction.read_predicates.contains(&predicate) {
+                    transaction.read_predicates.push(predicate);
+                }
+            }
+        }
+        if locking.is_some() && join.is_none() && transaction.snapshot.is_some() {
+            let predicate = Lock::Predicate(from.name.clone(), Box::new(condition.clone()));
+            if !transaction.locks.contains(&predicate) {
+                transaction.locks.push(predicate);
+            }
+        }
 
-            Ord::cmp(&l, &r)
+        let keys = res
+            .iter()
+            .map(|row| {
+                self.sql_context = Some(SqlContext::Where {
+                    row: (*row).clone(),
+                    table: from.name.clone(),
+                });
+                self.interpret(order_by)
+            })
+            .collect::<Res<Vec<_>>>();
+        self.sql_context = None;
+        // The sort is stable, rows with the same key keep their insertion order
+        let mut keyed: Vec<_> = keys?.into_iter().zip(res).collect();
+        keyed.sort_by(|(left, _), (right, _)| {
+            if descending {
+                order(right, left)
+            } else {
+                order(left, right)
+            }
         });
+        res = keyed.into_iter().map(|(_, row)| row).collect();
+
+        // The parser only accepts unsigned numbers after limit and offset
+        let offset = offset as usize;
+        let limit = limit.map_or(usize::MAX, |l| l as usize);
+
+        if !group_by.is_empty() || having.is_some() {
+            let mut groups: BTreeMap<Vec<Value>, Vec<&Row>> = BTreeMap::new();
+            for row in res {
+                let key = group_by
+                    .iter()
+                    .map(|item| match item {
+                        Item::Column(col) => row.get(col).clone(),
+                        Item::Wildcard => panic!("Unexpected wildcard in group by"),
+                    })
+                    .collect();
+                groups.entry(key).or_default().push(row);
+            }
 
-        res = res.into_iter().skip(offset as usize).collect();
-        if let Some(l) = limit {
-            res = res.into_iter().take(l as usize).collect();
+            let mut values = vec![];
+            for rows in groups.into_values() {
+                if let Some(having) = having {
+                    self.sql_context = Some(SqlContext::Group {
+                        rows: rows.iter().map(|row| (*row).clone()).collect(),
+                    });
+                    let kept = self.interpret(having)? == Value::Bool(true);
+                    self.sql_context = None;
+                    if !kept {
+                        continue;
+                    }
+                }
+                values.push(group_value(item_list, &rows)?);
+            }
+            values = values.into_iter().skip(offset).take(limit).collect();
+            return Ok(Value::Set(values));
         }
 
-        if item_list
-            .iter()
-            .any(|col| matches!(col, SelectItem::Count(_)))
-        {
-            Ok(Value::Integer(res.len() as i16))
+        res = res.into_iter().skip(offset).take(limit).collect();
+
+        if let Some(locking) = locking {
+            self.request_table_write(&from.name)?;
+            if let Some((table, _)) = join {
+                self.request_table_write(&table.name)?;
+            }
+            for row in &res {
+                for rid in row.rids() {
+                    let lock = locking.lock(rid);
+                    // A joined row can appear in several results
+                    if self.transactions[&self.cur_tx].locks.contains(&lock) {
+                        continue;
+                    }
+                    match self.request_row_lock(lock) {
+                        Err(SqlEngineError::Locked(lock)) if locking.wait == LockWait::NoWait => {
+                            return Err(SqlEngineError::LockNotAvailable(lock));
+                        }
+                        res => res?,
+                    }
+                }
+            }
+        }
+
+        if item_list.iter().any(SelectItem::is_aggregate) {
+            group_value(item_list, &res)
         } else {
             let mut values = vec![];
             let table = self.tables.get(&from.name).cloned().unwrap_or_default();
@@ -594,51 +1337,96 @@ impl SqlDatabase {
             for col in item_list {
                 match col {
                     SelectItem::Column(item) => match item {
-                        Item::Wildcard => selected_columns.extend(table.columns.clone()),
+                        Item::Wildcard => match join {
+                            Some((join_table, _)) => {
+                                let other = self
+                                    .tables
+                                    .get(&join_table.name)
+                                    .cloned()
+                                    .unwrap_or_default();
+                                for (name, columns) in [
+                                    (&from.name, &table.columns),
+                                    (&join_table.name, &other.columns),
+                                ] {
+                                    selected_columns
+                                        .extend(columns.iter().map(|col| format!("{name}.{col}")));
+                                }
+                            }
+                            None => selected_columns.extend(table.columns.clone()),
+                        },
                         Item::Column(col) => selected_columns.push(col.clone()),
                     },
-                    SelectItem::Count(_) => panic!(),
+                    aggregate => return Err(UnknownVariable(aggregate.to_string())),
                 }
             }
             for r in res {
                 values.push(r.to_value(&selected_columns));
             }
+            Ok(Value::Set(values))
+        }
+    }
 
-            if values.len() == 1 {
-                return Ok(values[0].clone());
-            }
+    fn nullable_integer(&mut self, expr: &SqlExpression) -> Res<Option<i64>> {
+        match self.interpret(expr)? {
+            Value::Scalar(boxed) => match *boxed {
+                Value::Integer(i) => Ok(Some(i)),
+                Value::Null => Ok(None),
+                _ => Err(SqlTypeError(Box::new(expr.clone()), "integer".to_string())),
+            },
+            Value::Integer(i) => Ok(Some(i)),
+            Value::Null => Ok(None),
+            _ => Err(SqlTypeError(Box::new(expr.clone()), "integer".to_string())),
+        }
+    }
 
-            Ok(Value::Set(values))
+    fn nullable_string(&mut self, expr: &SqlExpression) -> Res<Option<String>> {
+        match self.interpret(expr)? {
+            Value::String(s) => Ok(Some(s)),
+            Value::Null => Ok(None),
+            _ => Err(SqlTypeError(Box::new(expr.clone()), "string".to_string())),
         }
     }
 
-    fn assert_integer(&mut self, expr: &SqlExpression) -> Res<i16> {
-        let value = self.interpret(expr)?;
-        if let Value::Integer(value) = value {
-            Ok(value)
-        } else if let Value::Scalar(boxed) = &value {
-            if let Value::Integer(i) = *(*boxed) {
-                Ok(i)
-            } else {
-                Err(SqlTypeError(expr.clone(), "integer".to_string()))
+    // Integers compare numerically and strings lexicographically, without their quotes
+    fn nullable_ordering(
+        &mut self,
+        left: &SqlExpression,
+        right: &SqlExpression,
+    ) -> Res<Option<Ordering>> {
+        let unscalar = |value| match value {
+            Value::Scalar(boxed) => *boxed,
+            value => value,
+        };
+        match (
+            unscalar(self.interpret(left)?),
+            unscalar(self.interpret(right)?),
+        ) {
+            (Value::Null, _) | (_, Value::Null) => Ok(None),
+            (Value::Integer(l), Value::Integer(r)) => Ok(Some(l.cmp(&r))),
+            (Value::String(l), Value::String(r)) => Ok(Some(unquoted(&l).cmp(unquoted(&r)))),
+            (Value::Integer(_), _) => {
+                Err(SqlTypeError(Box::new(right.clone()), "integer".to_string()))
             }
-        } else {
-            Err(SqlTypeError(expr.clone(), "integer".to_string()))
+            (Value::String(_), _) => {
+                Err(SqlTypeError(Box::new(right.clone()), "string".to_string()))
+            }
+            _ => Err(SqlTypeError(
+                Box::new(left.clone()),
+                "integer or string".to_string(),
+            )),
         }
     }
 
-    fn assert_bool(&mut self, expr: &SqlExpression) -> Res<bool> {
-        let value = self.interpret(expr)?;
-        if let Value::Bool(value) = value {
-            Ok(value)
-        } else if let Value::Scalar(boxed) = &value {
-            if let Value::Bool(b) = *(*boxed) {
-                Ok(b)
-            } else {
-                Err(SqlTypeError(expr.clone(), "integer".to_string()))
-            }
-        } else {
-            Err(SqlTypeError(expr.clone(), "bool".to_string()))
+    fn nullable_bool(&mut self, expr: &SqlExpression) -> Res<Option<bool>> {
+        match self.interpret(expr)? {
+            Value::Scalar(boxed) => match *boxed {
+                Value::Bool(b) => Ok(Some(b)),
+                Value::Null => Ok(None),
+                _ => Err(SqlTypeError(Box::new(expr.clone()), "bool".to_string())),
+            },
+            Value::Bool(b) => Ok(Some(b)),
+            Value::Null => Ok(None),
+            _ => Err(SqlTypeError(Box::new(expr.clone()), "bool".to_string())),
         }
     }
 
@@ -646,7 +1434,7 @@ impl SqlDatabase {
         if let Value::Tuple(value) = self.interpret(expr)? {
             Ok(value)
         } else {
-            Err(SqlTypeError(expr.clone(), "tuple".to_string()))
+            Err(SqlTypeError(Box::new(expr.clone()), "tuple".to_string()))
         }
     }
 
@@ -654,58 +1442,147 @@ impl SqlDatabase {
         if let Value::Set(value) = self.interpret(expr)? {
             Ok(value)
         } else {
-            Err(SqlTypeError(expr.clone(), "set".to_string()))
+            Err(SqlTypeError(Box::new(expr.clone()), "set".to_string()))
         }
     }
 
     fn rows(&self, tx: &TransactionId, table_name: &String) -> Vec<Row> {
-        let mut table = self.tables.get(table_name).cloned().unwrap_or_default();
-
         let transaction = self.transactions.get(tx).unwrap();
+        let tables = transaction.snapshot.as_ref().unwrap_or(&self.tables);
+        let mut rows = tables
+            .get(table_name)
+            .map(|table| table.rows.clone())
+            .unwrap_or_default();
+
         for changes in &transaction.changes {
             match changes {
                 Changes::Insert(insert_table, insert_row) => {
                     if insert_table == table_name {
-                        table.rows.push(insert_row.clone());
+                        rows.push(insert_row.clone());
+                    }
+                }
+                Changes::Update(update_table, row) => {
+                    if update_table == table_name {
+                        replace_row(&mut rows, row);
                     }
                 }
                 Changes::Delete(delete_table, row) => {
                     if delete_table == table_name {
-                        table.rows.retain(|x| x != row);
+                        rows.retain(|x| x != row);
+                    }
+                }
+                Changes::Drop(drop_table) => {
+                    if drop_table == table_name {
+                        rows.clear();
                     }
                 }
             }
         }
-        table.rows
+        rows
     }
 
-    pub fn commit(&mut self, tx: &TransactionId) {
+    pub fn commit(&mut self, tx: &TransactionId) -> Unit {
+        let context = self.transactions.get(tx).unwrap();
+        let written = context.written_rows();
+        if written
+            .union(&context.reads)
+            .any(|rid| context.concurrent_writes.contains(rid))
+        {
+            return Err(SqlEngineError::SerializationFailure);
+        }
+        if context.isolation == IsolationLevel::Serializable {
+            let predicates: Vec<_> = context
+                .locks
+                .iter()
+                .chain(&context.read_predicates)
+                .filter(|lock| matches!(lock, Lock::Predicate(_, _)))
+                .cloned()
+                .collect();
+            for (table, row) in context.concurrent_inserts.clone() {
+                if self
+                    .matching_predicate(&predicates, &table, &row)?
+                    .is_some()
+                {
+                    return Err(SqlEngineError::SerializationFailure);
+                }
+            }
+        }
+
+        let context = self.transactions.get(tx).unwrap();
+        let inserted = context.inserted_rows();
+        for (id, other) in self.transactions.iter_mut() {
+            if id != tx && other.snapshot.is_some() {
+                other.concurrent_writes.extend(&written);
+                other.concurrent_inserts.extend(inserted.iter().cloned());
+            }
+        }
+
         let tx = self.transactions.remove(tx).unwrap();
         for change in tx.changes {
             match change {
                 Changes::Insert(table, row) => {
-                    let table = self.tables.entry(table.clone()).or_default();
+                    let table = Arc::make_mut(self.tables.entry(table.clone()).or_default());
+                    table.infer_types(&row);
                     table.rows.push(row);
                 }
+                Changes::Update(table, row) => {
+                    let table = Arc::make_mut(self.tables.entry(table.clone()).or_default());
+                    table.infer_types(&row);
+                    replace_row(&mut table.rows, &row);
+                }
                 Changes::Delete(table, row) => {
-                    let table = self.tables.entry(table.clone()).or_default();
+                    let table = Arc::make_mut(self.tables.entry(table.clone()).or_default());
                     table.rows.retain(|x| x != &row);
                 }
+                Changes::Drop(table) => {
+                    self.tables.remove(&table);
+                    self.foreign_keys.retain(|fk| fk.relation != table);
+                }
             }
         }
+        Ok(())
+    }
+
+    pub fn savepoint(&mut self, tx: &TransactionId, name: &str) {
+        let context = self.transactions.get_mut(tx).unwrap();
+        let marker = (name.to_string(), context.changes.len(), context.locks.len());
+        context.savepoints.push(marker);
+    }
+
+    pub fn rollback_to(&mut self, tx: &TransactionId, name: &str) -> Unit {
+        let context = self.transactions.get_mut(tx).unwrap();
+        let Some(index) = context.savepoints.iter().rposition(|(n, _, _)| n == name) else {
+            return Err(SqlEngineError::UnknownSavepoint(name.to_string()));
+        };
+        let (_, changes, locks) = context.savepoints[index];
+        context.savepoints.truncate(index + 1);
+        context.changes.truncate(changes);
+        context.locks.truncate(locks);
+        Ok(())
     }
 
     pub fn abort(&mut self, tx: &TransactionId) {
         self.transactions.remove(tx).unwrap();
     }
 
-    fn updates(&mut self, updates: &[SqlExpression], table: &String, row: &Row) -> Unit {
-        let mut new_row = self.execute_assignment(row, table, &updates[0])?;
-        for update in &updates[1..] {
-            new_row = self.execute_assignment(&new_row, table, update)?;
+    fn updates(&mut self, updates: &[SqlExpression], table: &String, row: &Row) -> Res<Row> {
+        // Every assignment reads the row as it was before the update
+        let mut values = vec![];
+        for update in updates {
+            if let SqlExpression::Assignment(name, expr) = update {
+                values.push((name.name.clone(), self.interpret(expr)?));
+            } else {
+                panic!("{update}")
+            }
         }
+        let mut new_row = row.clone();
+        new_row.tuples.extend(values);
 
+        self.check_types(table, &new_row)?;
+        self.check_constraints(table, &new_row)?;
+        self.check_primary_key(table, &new_row)?;
         self.check_unique_values(table, &new_row)?;
+        self.check_predicate_locks(table, &new_row)?;
 
         let foreign_rows = self.check_foreign_key(table, &new_row)?;
         for rid in foreign_rows {
@@ -713,58 +1590,186 @@ impl SqlDatabase {
         }
         self.request_row_lock(Lock::RowUpdate(row.rid))?;
 
+        let t = &self.tables[table];
         let transaction = self.transactions.get_mut(&self.cur_tx).unwrap();
-
-        let t = self.tables.get(table).unwrap();
         for unique in &t.unique {
-            transaction.locks.push(Lock::Unique(
-                table.clone(),
-                unique.clone(),
-                unique.tuple_from(&new_row),
-            ));
+            if let Some(tuple) = unique.tuple_from(&new_row) {
+                transaction
+                    .locks
+                    .push(Lock::Unique(table.clone(), unique.clone(), tuple));
+            }
         }
         transaction
             .changes
-            .push(Changes::Delete(table.clone(), row.clone()));
-        transaction
-            .changes
-            .push(Changes::Insert(table.clone(), new_row.clone()));
+            .push(Changes::Update(table.clone(), new_row.clone()));
+
+        Ok(new_row)
+    }
 
+    fn locked_by_other(&self, requested_lock: &Lock) -> bool {
+        self.transactions.iter().any(|(id, t)| {
+            id != &self.cur_tx && t.locks.iter().any(|l| requested_lock.conflicts(l))
+        })
+    }
+
+    fn request_row_lock(&mut self, requested_lock: Lock) -> Unit {
+        if self.locked_by_other(&requested_lock) {
+            return Err(SqlEngineError::Locked(requested_lock));
+        }
+        let tx = self.transactions.get_mut(&self.cur_tx).unwrap();
+        tx.locks.push(requested_lock);
         Ok(())
     }
 
-    fn execute_assignment(&mut self, row: &Row, table: &String, expr: &SqlExpression) -> Res<Row> {
-        if let SqlExpression::Assignment(name, expr) = expr {
-            let value = self.interpret(expr)?;
-            let t = self.tables.get(table).unwrap();
-            let mut new_tuples = HashMap::new();
-            for col in &t.columns {
-                if col == &name.name {
-                    new_tuples.insert(name.name.clone(), value.clone());
-                } else {
-                    new_tuples.insert(col.clone(), row.tuples.get(col).unwrap().clone());
-                }
+    fn request_table_write(&mut self, table: &str) -> Unit {
+        if self.transactions[&self.cur_tx].read_only {
+            return Err(SqlEngineError::ReadOnlyTransaction);
+        }
+        let lock = Lock::TableWrite(table.to_string());
+        if self.transactions[&self.cur_tx].locks.contains(&lock) {
+            return Ok(());
+        }
+        self.request_row_lock(lock)
+    }
+
+    fn check_predicate_locks(&mut self, table: &String, row: &Row) -> Unit {
+        let predicates: Vec<_> = self
+            .transactions
+            .iter()
+            .filter(|(id, _)| id != &&self.cur_tx)
+            .flat_map(|(_, t)| t.locks.iter())
+            .filter(|lock| matches!(lock, Lock::Predicate(_, _)))
+            .cloned()
+            .collect();
+        match self.matching_predicate(&predicates, table, row)? {
+            Some(lock) => Err(SqlEngineError::Locked(lock)),
+            None => Ok(()),
+        }
+    }
+
+    fn matching_predicate(
+        &mut self,
+        predicates: &[Lock],
+        table: &String,
+        row: &Row,
+    ) -> Res<Option<Lock>> {
+        let context = self.sql_context.replace(SqlContext::Where {
+            row: row.clone(),
+            table: table.clone(),
+        });
+        let mut matching = Ok(None);
+        for lock in predicates {
+            match lock {
+                Lock::Predicate(t, condition) if t == table => match self.interpret(condition) {
+                    Ok(Value::Bool(true)) => matching = Ok(Some(lock.clone())),
+                    Ok(_) => continue,
+                    Err(err) => matching = Err(err),
+                },
+                _ => continue,
             }
-            Ok(Row {
-                tuples: new_tuples,
-                rid: row.rid,
-            })
-        } else {
-            panic!("{expr}")
+            break;
         }
+        self.sql_context = context;
+        matching
     }
 
-    fn request_row_lock(&mut self, requested_lock: Lock) -> Unit {
-        for (id, t) in &self.transactions {
-            if id != &self.cur_tx && t.locks.iter().any(|l| requested_lock.conflicts(l)) {
-                return Err(SqlEngineError::Locked(requested_lock));
+    // A keyed row deleted then inserted again by the transaction keeps its identity and locks
+    fn row_id(&mut self, table: &str, tuples: &HashMap<String, Value>) -> RowId {
+        let Some(primary) = self.tables.get(table).and_then(|t| t.primary.as_ref()) else {
+            return self.rid.increment();
+        };
+        let same_key = |row: &Row| {
+            primary
+                .columns
+                .iter()
+                .all(|c| row.tuples.get(c) == tuples.get(c))
+        };
+        let deleted = self.transactions[&self.cur_tx]
+            .changes
+            .iter()
+            .rev()
+            .find_map(|change| match change {
+                Changes::Delete(t, row) if t == table && same_key(row) => Some(row.rid),
+                _ => None,
+            });
+        deleted.unwrap_or_else(|| self.rid.increment())
+    }
+
+    fn check_types(&self, table: &str, row: &Row) -> Unit {
+        let Some(t) = self.tables.get(table) else {
+            return Ok(());
+        };
+        let mut columns: Vec<_> = row.tuples.iter().collect();
+        columns.sort_by_key(|(column, _)| {
+            let position = t.columns.iter().position(|c| c == *column);
+            (position.unwrap_or(usize::MAX), *column)
+        });
+        for (column, value) in columns {
+            let kind = self.column_type(table, column);
+            let matches = matches!(
+                (kind, value),
+                (None, _)
+                    | (_, Value::Null)
+                    | (Some(Type::Integer), Value::Integer(_))
+                    | (Some(Type::String), Value::String(_))
+                    | (Some(Type::Bool), Value::Bool(_))
+            );
+            if !matches {
+                return Err(SqlTypeError(
+                    Box::new(SqlExpression::Value(value.clone())),
+                    kind.unwrap().to_string(),
+                ));
             }
         }
-        let tx = self.transactions.get_mut(&self.cur_tx).unwrap();
-        tx.locks.push(requested_lock);
         Ok(())
     }
 
+    // Types are inferred when a transaction commits, until then its own writes type the columns
+    fn column_type(&self, table: &str, column: &str) -> Option<Type> {
+        if let Some(kind) = self.tables.get(table)?.types.get(column) {
+            return Some(*kind);
+        }
+        let mut own = Table::default();
+        for row in self.transactions.get(&self.cur_tx)?.own_rows(table) {
+            own.infer_types(&row);
+        }
+        own.types.get(column).copied()
+    }
+
+    fn check_primary_key(&self, table: &str, row: &Row) -> Unit {
+        let Some(primary) = self.tables.get(table).and_then(|t| t.primary.as_ref()) else {
+            return Ok(());
+        };
+        match primary
+            .columns
+            .iter()
+            .find(|c| row.tuples.get(*c) == Some(&Value::Null))
+        {
+            Some(column) => Err(SqlEngineError::NotNullViolation(column.clone())),
+            None => Ok(()),
+        }
+    }
+
+    // Unlike a where clause, which filters out unknown, a check evaluating to null accepts the row
+    fn check_constraints(&mut self, table: &str, row: &Row) -> Unit {
+        let Some(t) = self.tables.get(table).cloned() else {
+            return Ok(());
+        };
+        let context = self.sql_context.replace(SqlContext::Where {
+            table: table.to_string(),
+            row: row.clone(),
+        });
+        let res = t
+            .checks
+            .iter()
+            .try_for_each(|check| match self.interpret(check)? {
+                Value::Bool(false) => Err(SqlEngineError::CheckViolation(Box::new(check.clone()))),
+                _ => Ok(()),
+            });
+        self.sql_context = context;
+        res
+    }
+
     fn check_unique_values(&self, table: &str, row: &Row) -> Unit {
         for (id, tc) in &self.transactions {
             if id == &self.cur_tx {
@@ -772,17 +1777,40 @@ impl SqlDatabase {
             }
             for lock in &tc.locks {
                 if let Lock::Unique(t, unique, value) = &lock {
-                    if t == table && &unique.tuple_from(row) == value {
+                    if t == table && unique.tuple_from(row).as_ref() == Some(value) {
                         return Err(SqlEngineError::Locked(lock.clone()));
                     }
                 }
             }
         }
 
+        // Rows deleted by the transaction, like a truncated table, no longer conflict
+        let context = &self.transactions[&self.cur_tx];
+        if context
+            .changes
+            .iter()
+            .any(|change| matches!(change, Changes::Drop(t) if t == table))
+        {
+            return Ok(());
+        }
+        let deleted = context.written_rows();
+        // Rows written by the transaction, including the previous rows of the same insert, conflict too
+        let written = context.own_rows(table);
         if let Some(t) = self.tables.get(table) {
+            let rows: Vec<_> = t
+                .rows
+                .iter()
+                .filter(|existing| !deleted.contains(&existing.rid))
+                .chain(&written)
+                .collect();
             for unique in &t.unique {
-                for existing in &t.rows {
-                    if unique.tuple_from(existing) == unique.tuple_from(row) {
+                let Some(tuple) = unique.tuple_from(row) else {
+                    continue;
+                };
+                for existing in &rows {
+                    if existing.rid != row.rid
+                        && unique.tuple_from(existing).as_ref() == Some(&tuple)
+                    {
                         return Err(SqlEngineError::UnicityViolation);
                     }
                 }
@@ -791,10 +1819,27 @@ impl SqlDatabase {
         Ok(())
     }
 
+    fn conflicting_row(&self, table: &String, columns: &[Variable], row: &Row) -> Option<Row> {
+        let index = UniqueIndex {
+            columns: columns.iter().map(|c| c.name.clone()).collect(),
+        };
+        let tuple = index.tuple_from(row)?;
+        self.rows(&self.cur_tx, table)
+            .into_iter()
+            .find(|existing| index.tuple_from(existing).as_ref() == Some(&tuple))
+    }
+
     fn check_foreign_key(&self, table: &str, row: &Row) -> Res<Vec<RowId>> {
         let mut res = vec![];
         'outer: for foreign_key in &self.foreign_keys {
             if foreign_key.relation == table {
+                if foreign_key
+                    .columns
+                    .iter()
+                    .any(|col| row.tuples.get(col) == Some(&Value::Null))
+                {
+                    continue;
+                }
                 let foreign_rows = self.rows(&self.cur_tx, &foreign_key.foreign_relation);
                 for foreign_row in foreign_rows {
                     let mut p = foreign_key
@@ -814,3 +1859,173 @@ impl SqlDatabase {
         Ok(res)
     }
 }
+
+// Strings sort alphabetically rather than by their quoted lexeme
+fn order(left: &Value, right: &Value) -> Ordering {
+    match (left, right) {
+        (Value::String(l), Value::String(r)) => unquoted(l).cmp(unquoted(r)),
+        _ => Ord::cmp(left, right),
+    }
+}
+
+fn hash_tables(tables: &HashMap<String, Arc<Table>>) -> Vec<HashableTable> {
+    let mut res = vec![];
+    for (name, table) in tables {
+        let mut types: Vec<_> = table.types.iter().map(|(c, t)| (c.clone(), *t)).collect();
+        types.sort();
+        let mut rows: Vec<_> = table.rows.iter().map(Row::hash).collect();
+        rows.sort();
+        res.push(HashableTable {
+            name: name.clone(),
+            types,
+            rows,
+        });
+    }
+    res.sort_by(|a, b| a.name.cmp(&b.name));
+    res
+}
+
+fn replace_row(rows: &mut Vec<Row>, row: &Row) {
+    match rows.iter_mut().find(|x| x.rid == row.rid) {
+        Some(existing) => *existing = row.clone(),
+        None => rows.push(row.clone()),
+    }
+}
+
+// A query used as a value returns its only row, or null when it returns none
+pub fn scalar(mut rows: Vec<Value>) -> Res<Value> {
+    match rows.len() {
+        0 => Ok(Value::Null),
+        1 => Ok(rows.remove(0)),
+        count => Err(SqlEngineError::TooManyRows(count)),
+    }
+}
+
+pub fn unquoted(s: &str) -> &str {
+    s.strip_prefix('\'')
+        .and_then(|s| s.strip_suffix('\''))
+        .unwrap_or(s)
+}
+
+fn like(value: &[char], pattern: &[char]) -> bool {
+    match pattern.first() {
+        None => value.is_empty(),
+        Some('%') => (0..=value.len()).any(|i| like(&value[i..], &pattern[1..])),
+        Some('_') => !value.is_empty() && like(&value[1..], &pattern[1..]),
+        Some(c) => value.first() == Some(c) && like(&value[1..], &pattern[1..]),
+    }
+}
+
+fn group_value(items: &[SelectItem], rows: &[&Row]) -> Res<Value> {
+    let mut values = vec![];
+    for item in items {
+        match item {
+            SelectItem::Column(Item::Column(col)) => {
+                values.push(rows[0].tuples.get(col).unwrap().clone())
+            }
+            _ => values.push(aggregate(item, rows)?),
+        }
+    }
+
+    if values.len() == 1 {
+        Ok(values.remove(0))
+    } else {
+        Ok(Value::Tuple(values))
+    }
+}
+
+fn aggregate(item: &SelectItem, rows: &[&Row]) -> Res<Value> {
+    let column = match item {
+        SelectItem::Count(Item::Wildcard) => return Ok(Value::Integer(rows.len() as i64)),
+        SelectItem::Count(Item::Column(col))
+        | SelectItem::CountDistinct(Item::Column(col))
+        | SelectItem::Sum(Item::Column(col))
+        | SelectItem::Min(Item::Column(col))
+        | SelectItem::Max(Item::Column(col))
+        | SelectItem::Avg(Item::Column(col)) => col,
+        _ => panic!("Unexpected aggregate {item}"),
+    };
+    let values: Vec<Value> = rows
+        .iter()
+        .map(|row| row.tuples.get(column).unwrap().clone())
+        .filter(|value| value != &Value::Null)
+        .collect();
+
+    match item {
+        SelectItem::Count(_) => Ok(Value::Integer(values.len() as i64)),
+        SelectItem::CountDistinct(_) => {
+            let mut distinct = values;
+            distinct.sort();
+            distinct.dedup();
+            Ok(Value::Integer(distinct.len() as i64))
+        }
+        SelectItem::Min(_) => Ok(values.into_iter().min().unwrap_or(Value::Null)),
+        SelectItem::Max(_) => Ok(values.into_iter().max().unwrap_or(Value::Null)),
+        _ => {
+            let mut sum: i64 = 0;
+            for value in &values {
+                if let Value::Integer(i) = value {
+                    sum = sum.checked_add(*i).ok_or_else(|| {
+                        SqlEngineError::ArithmeticOverflow(Box::new(SqlExpression::Aggregate(
+                            item.clone(),
+                        )))
+                    })?;
+                } else {
+                    return Err(SqlTypeError(
+                        Box::new(SqlExpression::Var(Variable {
+                            name: column.clone(),
+                        })),
+                        "integer".to_string(),
+                    ));
+                }
+            }
+
+            if values.is_empty() {
+                Ok(Value::Null)
+            } else if matches!(item, SelectItem::Avg(_)) {
+                Ok(Value::Integer(sum / values.len() as i64))
+            } else {
+                Ok(Value::Integer(sum))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::parser::{Expression, Parser, Statement};
+    use crate::sql_interpreter::SqlDatabase;
+
+    fn database(source: &str) -> SqlDatabase {
+        let mets = Parser::new(source.to_string()).compile().unwrap();
+        let mut database = SqlDatabase::new();
+        for statement in &mets.init {
+            if let Statement::Expression(Expression::Sql(expr)) = statement {
+                database.execute(expr, None).unwrap();
+            }
+        }
+        database
+    }
+
+    #[test]
+    fn hash_does_not_depend_on_insertion_order() {
+        let first = database(
+            "init do
+    `insert into users (id, age) values (1, 10)`
+    `insert into accounts (id) values (1)`
+    `insert into users (id, age) values (2, 20)`
+end
+",
+        );
+        let second = database(
+            "init do
+    `insert into users (id, age) values (2, 20)`
+    `insert into accounts (id) values (1)`
+    `insert into users (id, age) values (1, 10)`
+end
+",
+        );
+
+        assert_eq!(first.hash(), second.hash());
+    }
+}
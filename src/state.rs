@@ -1,22 +1,61 @@
 use crate::engine::{TransactionState, Value};
-use crate::sql_interpreter::{HashableRow, Lock, SqlDatabase, TransactionId};
-use std::cell::{Ref, RefCell, RefMut};
-use std::collections::{HashMap, HashSet, VecDeque};
-use std::rc::Rc;
+use crate::parser::Expression;
+use crate::sql_interpreter::{
+    HashableTable, HashableTransaction, Lock, SqlDatabase, TransactionContext, TransactionId,
+};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
 
 #[derive(Hash, Eq, PartialEq, Debug, Clone)]
 pub struct HashableState {
     pc: Vec<usize>,
     state: Vec<ProcessState>,
-    global: Vec<(String, Vec<HashableRow>)>,
-    locals: Vec<(String, Value)>,
+    txs: Vec<HashableTransactionInfo>,
+    global: Vec<HashableTable>,
+    globals: Vec<(String, Value)>,
+    locals: Vec<Vec<(String, Value)>>,
     eventually: Vec<(usize, bool)>,
 }
 
-#[derive(PartialEq, Debug, Clone, Hash, Eq)]
+impl HashableState {
+    pub fn symmetric(mut self, groups: &[Vec<usize>]) -> HashableState {
+        for group in groups {
+            let mut components: Vec<_> = group
+                .iter()
+                .map(|&i| {
+                    (
+                        self.pc[i],
+                        self.state[i].clone(),
+                        self.txs[i].clone(),
+                        std::mem::take(&mut self.locals[i]),
+                    )
+                })
+                .collect();
+            components.sort();
+            for (&i, (pc, state, tx, locals)) in group.iter().zip(components) {
+                self.pc[i] = pc;
+                self.state[i] = state;
+                self.txs[i] = tx;
+                self.locals[i] = locals;
+            }
+        }
+        self
+    }
+}
+
+// Name, state and failure of the transaction of a process, with its snapshot, changes, locks and reads
+type HashableTransactionInfo = (
+    Option<String>,
+    TransactionState,
+    bool,
+    Option<HashableTransaction>,
+);
+
+#[derive(PartialEq, Debug, Clone, Hash, Eq, PartialOrd, Ord)]
 pub enum ProcessState {
     Running,
     Latching,
+    Awaiting(Expression),
     Locked(Lock),
     Finished,
 }
@@ -26,6 +65,7 @@ pub struct TransactionInfo {
     pub id: Option<TransactionId>,
     pub name: Option<String>,
     pub state: TransactionState,
+    pub failed: bool,
 }
 
 #[derive(PartialEq, Debug, Clone)]
@@ -34,9 +74,15 @@ pub struct State {
     pub processes: Vec<ProcessState>,
     pub txs: Vec<TransactionInfo>,
     pub sql: SqlDatabase,
-    pub locals: HashMap<String, Value>,
+    pub globals: HashMap<String, Value>,
+    pub locals: Vec<HashMap<String, Value>>,
+    // States are explored breadth first, the first ancestor is on a shortest path from the initial state
     pub ancestors: Vec<RcState>,
     pub eventually: HashMap<usize, bool>,
+    // Process and statement index of the step leading to this state
+    pub transition: Option<(usize, usize)>,
+    // Value of the sql statement run by the step leading to this state
+    pub result: Option<Value>,
 }
 
 impl State {
@@ -45,12 +91,44 @@ impl State {
             pc: self.pc.clone(),
             global: self.sql.hash(),
             state: self.processes.clone(),
+            txs: self
+                .txs
+                .iter()
+                .map(|tx| {
+                    let context = tx.id.and_then(|id| self.sql.transactions.get(&id));
+                    (
+                        tx.name.clone(),
+                        tx.state.clone(),
+                        tx.failed,
+                        context.map(TransactionContext::hash),
+                    )
+                })
+                .collect(),
+            globals: {
+                let mut globals: Vec<_> = self
+                    .globals
+                    .iter()
+                    .map(|(l, r)| (l.clone(), r.clone()))
+                    .collect();
+                globals.sort_by(|a, b| a.0.cmp(&b.0));
+                globals
+            },
             locals: self
                 .locals
                 .iter()
-                .map(|(l, r)| (l.clone(), r.clone()))
+                .map(|locals| {
+                    let mut locals: Vec<_> =
+                        locals.iter().map(|(l, r)| (l.clone(), r.clone())).collect();
+                    locals.sort_by(|a, b| a.0.cmp(&b.0));
+                    locals
+                })
                 .collect(),
-            eventually: self.eventually.iter().map(|(l, r)| (*l, *r)).collect(),
+            eventually: {
+                let mut eventually: Vec<_> =
+                    self.eventually.iter().map(|(l, r)| (*l, *r)).collect();
+                eventually.sort();
+                eventually
+            },
         }
     }
 
@@ -58,8 +136,10 @@ impl State {
         let mut unlocks = vec![];
         'outer: for (i, s) in self.processes.iter().enumerate() {
             if let ProcessState::Locked(lock) = &s {
-                for context in self.sql.transactions.values() {
-                    if context.locks.contains(lock) {
+                for (id, context) in &self.sql.transactions {
+                    if self.txs[i].id != Some(*id)
+                        && context.locks.iter().any(|l| lock.waits_for(l))
+                    {
                         continue 'outer;
                     }
                 }
@@ -73,29 +153,28 @@ impl State {
         }
     }
 
+    // A deadlock is a cycle in the graph linking each locked process to the processes holding its lock
     pub fn find_deadlocks(&self) -> Option<HashSet<usize>> {
-        for i in 0..self.processes.len() {
-            let mut deq = VecDeque::from([i]);
-            let mut cycle = HashSet::new();
-            while let Some(x) = deq.pop_front() {
-                if let ProcessState::Locked(lock) = &self.processes[x] {
-                    if cycle.contains(&x) {
-                        return Some(cycle);
-                    }
-                    cycle.insert(x);
-                    for (j, context) in &self.sql.transactions {
-                        if context.locks.contains(lock) {
-                            for (pc, k) in self.txs.iter().enumerate() {
-                                if k.id == Some(*j) {
-                                    deq.push_back(pc);
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-        }
-        None
+        let waits_for: Vec<_> = (0..self.processes.len())
+            .map(|p| self.lock_holders(p))
+            .collect();
+        let mut visited = vec![false; waits_for.len()];
+        (0..waits_for.len()).find_map(|p| wait_cycle(&waits_for, p, &mut vec![], &mut visited))
+    }
+
+    fn lock_holders(&self, p: usize) -> Vec<usize> {
+        let ProcessState::Locked(lock) = &self.processes[p] else {
+            return vec![];
+        };
+        (0..self.txs.len())
+            .filter(|q| {
+                *q != p
+                    && self.txs[*q]
+                        .id
+                        .and_then(|id| self.sql.transactions.get(&id))
+                        .is_some_and(|context| context.locks.iter().any(|l| lock.waits_for(l)))
+            })
+            .collect()
     }
 
     pub fn release_latches(&mut self) {
@@ -113,19 +192,72 @@ impl State {
     }
 }
 
-#[derive(PartialEq, Debug, Clone)]
-pub struct RcState(Rc<RefCell<State>>);
+#[derive(Debug, Clone)]
+pub struct RcState(Arc<RwLock<State>>);
+
+impl PartialEq for RcState {
+    fn eq(&self, other: &Self) -> bool {
+        self.ptr_eq(other) || *self.borrow() == *other.borrow()
+    }
+}
 
 impl RcState {
     pub fn new(state: State) -> RcState {
-        RcState(Rc::new(RefCell::new(state)))
+        RcState(Arc::new(RwLock::new(state)))
+    }
+
+    pub fn borrow(&self) -> RwLockReadGuard<'_, State> {
+        self.0.read().unwrap()
+    }
+
+    pub fn borrow_mut(&self) -> RwLockWriteGuard<'_, State> {
+        self.0.write().unwrap()
     }
 
-    pub fn borrow(&self) -> Ref<'_, State> {
-        RefCell::borrow(&self.0)
+    pub fn ptr_eq(&self, other: &RcState) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
     }
 
-    pub fn borrow_mut(&self) -> RefMut<'_, State> {
-        RefCell::borrow_mut(&self.0)
+    pub fn release_awaits<E>(
+        &self,
+        mut holds: impl FnMut(usize, &Expression) -> Result<bool, E>,
+    ) -> Result<(), E> {
+        let awaiting: Vec<_> = self
+            .borrow()
+            .processes
+            .iter()
+            .enumerate()
+            .filter_map(|(i, process)| match process {
+                ProcessState::Awaiting(expr) => Some((i, expr.clone())),
+                _ => None,
+            })
+            .collect();
+        for (i, expr) in awaiting {
+            if holds(i, &expr)? {
+                self.borrow_mut().processes[i] = ProcessState::Running;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn wait_cycle(
+    waits_for: &[Vec<usize>],
+    p: usize,
+    path: &mut Vec<usize>,
+    visited: &mut [bool],
+) -> Option<HashSet<usize>> {
+    if let Some(start) = path.iter().position(|q| *q == p) {
+        return Some(path[start..].iter().copied().collect());
+    }
+    if visited[p] {
+        return None;
     }
+    visited[p] = true;
+    path.push(p);
+    let cycle = waits_for[p]
+        .iter()
+        .find_map(|q| wait_cycle(waits_for, *q, path, visited));
+    path.pop();
+    cycle
 }
@@ -0,0 +1,645 @@
+use std::collections::BTreeSet;
+
+use crate::parser::{
+    Expression, InsertSource, Item, Mets, Operator, SelectItem, SqlExpression, SqlOperator,
+    Statement,
+};
+use crate::reduction::accessed_tables;
+
+// Renders an approximation of the model as a TLA+ specification:
+// statements are atomic steps, isolation levels and rollbacks are not modeled
+pub fn tla(mets: &Mets, name: &str) -> String {
+    Translator::new(mets).module(name)
+}
+
+struct Translator<'a> {
+    mets: &'a Mets,
+    setup: Vec<Statement>,
+    tables: Vec<String>,
+    globals: BTreeSet<String>,
+    locals: Vec<BTreeSet<String>>,
+    bound: Vec<String>,
+    depth: usize,
+}
+
+struct Step {
+    conjuncts: Vec<String>,
+    changed: BTreeSet<String>,
+}
+
+fn defined(statements: &[Statement]) -> BTreeSet<String> {
+    let mut names = BTreeSet::new();
+    for statement in statements {
+        match statement {
+            Statement::Begin(_, Some(name), _) => {
+                names.insert(name.name.clone());
+            }
+            Statement::Expression(Expression::Assignment(name, _)) => {
+                names.insert(name.name.clone());
+            }
+            _ => {}
+        }
+    }
+    names
+}
+
+impl<'a> Translator<'a> {
+    fn new(mets: &'a Mets) -> Translator<'a> {
+        let setup: Vec<_> = mets.globals.iter().chain(&mets.init).cloned().collect();
+        let globals = defined(&setup);
+        let locals = mets
+            .processes
+            .iter()
+            .map(|code| defined(code).difference(&globals).cloned().collect())
+            .collect();
+        let statements = setup.iter().chain(mets.processes.iter().flatten());
+        let mut tables: Vec<_> = accessed_tables(statements).into_iter().collect();
+        tables.sort();
+        Translator {
+            mets,
+            setup,
+            tables,
+            globals,
+            locals,
+            bound: vec![],
+            depth: 0,
+        }
+    }
+
+    fn module(&mut self, name: &str) -> String {
+        let mut x = format!("---- MODULE {name} ----\nEXTENDS Integers, FiniteSets\n\n");
+        x.push_str(
+            "\\* Statements are atomic steps, isolation levels and rollbacks are not modeled\n\n",
+        );
+        for (constant, expr) in &self.mets.constants.clone() {
+            x.push_str(&format!(
+                "{} == {}\n",
+                constant.name,
+                self.expression(None, expr)
+            ));
+        }
+        x.push_str("Null == \"null\"\n");
+        x.push_str("Rows(S) == IF Cardinality(S) = 1 THEN CHOOSE v \\in S : TRUE ELSE S\n\n");
+
+        let variables = self.variables();
+        x.push_str(&format!("VARIABLES {}\n\n", variables.join(", ")));
+        x.push_str(&format!("vars == <<{}>>\n\n", variables.join(", ")));
+
+        let processes = self.mets.processes.len();
+        x.push_str("Init ==\n    /\\ ipc = 0\n");
+        x.push_str(&format!(
+            "    /\\ pc = [i \\in 0..{} |-> 0]\n",
+            processes as isize - 1
+        ));
+        if !self.tables.is_empty() {
+            let tables: Vec<_> = self
+                .tables
+                .iter()
+                .map(|t| format!("{t} |-> {{}}"))
+                .collect();
+            x.push_str(&format!("    /\\ tables = [{}]\n", tables.join(", ")));
+        }
+        for variable in variables
+            .iter()
+            .skip(if self.tables.is_empty() { 2 } else { 3 })
+        {
+            x.push_str(&format!("    /\\ {variable} = Null\n"));
+        }
+        x.push('\n');
+
+        let mut actions = vec![];
+        let setup = self.setup.clone();
+        for k in 0..setup.len() {
+            let step = self.step(None, &setup, k);
+            let action = format!("Setup_{k}");
+            x.push_str(&self.action(&action, step, &variables));
+            actions.push(action);
+        }
+        let mets = self.mets;
+        for (i, code) in mets.processes.iter().enumerate() {
+            let mut steps = vec![];
+            for k in 0..code.len() {
+                let step = self.step(Some(i), code, k);
+                let action = format!("P{i}_{k}");
+                x.push_str(&self.action(&action, step, &variables));
+                steps.push(action);
+            }
+            if steps.is_empty() {
+                steps.push("FALSE".to_string());
+            }
+            x.push_str(&format!("P{i} == {}\n\n", steps.join(" \\/ ")));
+            actions.push(format!("P{i}"));
+        }
+
+        let mut done = vec![format!("ipc = {}", setup.len())];
+        for (i, code) in mets.processes.iter().enumerate() {
+            done.push(format!("pc[{i}] = {}", code.len()));
+        }
+        x.push_str(&format!("Done == {}\n\n", done.join(" /\\ ")));
+        x.push_str("Terminating == Done /\\ UNCHANGED vars\n\n");
+        actions.push("Terminating".to_string());
+        x.push_str(&format!("Next == {}\n\n", actions.join(" \\/ ")));
+
+        let mut fairness = vec!["WF_vars(Next)".to_string()];
+        for (i, fair) in mets.fair_processes.iter().enumerate() {
+            if *fair {
+                fairness.push(format!("WF_vars(P{i})"));
+            }
+        }
+        x.push_str(&format!(
+            "Spec == Init /\\ [][Next]_vars /\\ {}\n\n",
+            fairness.join(" /\\ ")
+        ));
+
+        let ready = format!("ipc = {}", setup.len());
+        for (k, property) in mets.properties.iter().enumerate() {
+            let formula = match property {
+                Statement::Always(expr) => {
+                    format!("[]({ready} => {})", self.expression(None, expr))
+                }
+                Statement::Never(expr) => {
+                    format!("[]({ready} => ~{})", self.expression(None, expr))
+                }
+                Statement::Eventually(expr) => {
+                    format!("<>({ready} /\\ {})", self.expression(None, expr))
+                }
+                Statement::LeadsTo(cause, consequence) => format!(
+                    "({ready} /\\ {}) ~> {}",
+                    self.expression(None, cause),
+                    self.expression(None, consequence)
+                ),
+                _ => continue,
+            };
+            x.push_str(&format!("Property{k} == {formula}\n"));
+        }
+        x.push_str("====");
+        x
+    }
+
+    fn variables(&self) -> Vec<String> {
+        let mut variables = vec!["ipc".to_string(), "pc".to_string()];
+        if !self.tables.is_empty() {
+            variables.push("tables".to_string());
+        }
+        variables.extend(self.globals.iter().map(|name| format!("g_{name}")));
+        for (i, locals) in self.locals.iter().enumerate() {
+            variables.extend(locals.iter().map(|name| format!("p{i}_{name}")));
+        }
+        variables
+    }
+
+    fn action(&self, name: &str, step: Step, variables: &[String]) -> String {
+        let mut x = format!("{name} ==\n");
+        for conjunct in step.conjuncts {
+            x.push_str(&format!("    /\\ {conjunct}\n"));
+        }
+        let unchanged: Vec<_> = variables
+            .iter()
+            .filter(|v| !step.changed.contains(*v))
+            .cloned()
+            .collect();
+        if !unchanged.is_empty() {
+            x.push_str(&format!("    /\\ UNCHANGED <<{}>>\n", unchanged.join(", ")));
+        }
+        x.push('\n');
+        x
+    }
+
+    fn step(&mut self, process: Option<usize>, code: &[Statement], k: usize) -> Step {
+        let mut step = Step {
+            conjuncts: vec![],
+            changed: BTreeSet::new(),
+        };
+        let update = |next: String| match process {
+            None => format!("ipc' = {next}"),
+            Some(i) => format!("pc' = [pc EXCEPT ![{i}] = {next}]"),
+        };
+        match process {
+            None => {
+                step.conjuncts.push(format!("ipc = {k}"));
+                step.changed.insert("ipc".to_string());
+            }
+            Some(i) => {
+                step.conjuncts.push(format!("ipc = {}", self.setup.len()));
+                step.conjuncts.push(format!("pc[{i}] = {k}"));
+                step.changed.insert("pc".to_string());
+            }
+        }
+
+        let next = |offset: isize| (k as isize + offset).to_string();
+        let mut target = next(1);
+        match &code[k] {
+            Statement::Begin(_, Some(name), _) => {
+                self.assign(process, &mut step, &name.name, "\"running\"".to_string())
+            }
+            Statement::Commit => self.tx_state(process, code, k, &mut step, "committed"),
+            Statement::Abort => self.tx_state(process, code, k, &mut step, "aborted"),
+            Statement::Expression(Expression::Assignment(name, expr)) => {
+                let value = match expr.as_ref() {
+                    Expression::Sql(sql) if !matches!(sql, SqlExpression::Select { .. }) => {
+                        self.write(process, &mut step, sql);
+                        "Null".to_string()
+                    }
+                    expr => self.expression(process, expr),
+                };
+                self.assign(process, &mut step, &name.name, value);
+            }
+            Statement::Expression(Expression::Sql(sql)) => self.write(process, &mut step, sql),
+            Statement::Await(expr) => step.conjuncts.push(self.expression(process, expr)),
+            Statement::If(expr, offset) | Statement::While(expr, offset) => {
+                target = format!(
+                    "IF {} THEN {} ELSE {}",
+                    self.expression(process, expr),
+                    next(1),
+                    next(offset.get() as isize)
+                )
+            }
+            Statement::EndWhile(expr, offset) => {
+                target = format!(
+                    "IF {} THEN {} ELSE {}",
+                    self.expression(process, expr),
+                    next(-(offset.get() as isize)),
+                    next(1)
+                )
+            }
+            Statement::Else(offset) | Statement::Or(offset) => target = next(offset.get() as isize),
+            Statement::Either(offset) => {
+                step.conjuncts.push(format!(
+                    "\\E next \\in {{{}, {}}} : {}",
+                    next(1),
+                    next(offset.get() as isize),
+                    update("next".to_string())
+                ));
+                return step;
+            }
+            _ => {}
+        }
+        step.conjuncts.push(update(target));
+        step
+    }
+
+    // A transaction statement changes the state of the transaction opened by the closest begin
+    fn tx_state(
+        &mut self,
+        process: Option<usize>,
+        code: &[Statement],
+        k: usize,
+        step: &mut Step,
+        state: &str,
+    ) {
+        let name = code[..k]
+            .iter()
+            .rev()
+            .find_map(|statement| match statement {
+                Statement::Begin(_, name, _) => Some(name.clone()),
+                _ => None,
+            });
+        if let Some(Some(name)) = name {
+            self.assign(process, step, &name.name, format!("\"{state}\""));
+        }
+    }
+
+    fn assign(&mut self, process: Option<usize>, step: &mut Step, name: &str, value: String) {
+        let variable = self.var(process, name);
+        step.conjuncts.push(format!("{variable}' = {value}"));
+        step.changed.insert(variable);
+    }
+
+    fn var(&self, process: Option<usize>, name: &str) -> String {
+        if self.bound.iter().any(|bound| bound == name)
+            || self.mets.constants.iter().any(|(c, _)| c.name == name)
+        {
+            name.to_string()
+        } else if self.globals.contains(name) {
+            format!("g_{name}")
+        } else if let Some(i) = process.filter(|i| self.locals[*i].contains(name)) {
+            format!("p{i}_{name}")
+        } else if let Some(i) = self.locals.iter().position(|l| l.contains(name)) {
+            format!("p{i}_{name}")
+        } else {
+            "Null".to_string()
+        }
+    }
+
+    fn write(&mut self, process: Option<usize>, step: &mut Step, sql: &SqlExpression) {
+        let rows = match sql {
+            SqlExpression::Select { .. } => return,
+            SqlExpression::Insert {
+                relation,
+                columns,
+                source: InsertSource::Values(values),
+                on_conflict: None,
+            } => {
+                let mut records = vec![];
+                for value in values {
+                    let members = match value {
+                        SqlExpression::Tuple(members) => members.clone(),
+                        value => vec![value.clone()],
+                    };
+                    let fields: Vec<_> = columns
+                        .iter()
+                        .zip(&members)
+                        .map(|(column, member)| {
+                            format!("{} |-> {}", column.name, self.sql(process, member))
+                        })
+                        .collect();
+                    records.push(format!("[{}]", fields.join(", ")));
+                }
+                Some((
+                    relation,
+                    format!(
+                        "tables.{} \\union {{{}}}",
+                        relation.name,
+                        records.join(", ")
+                    ),
+                ))
+            }
+            SqlExpression::Update {
+                relation,
+                updates,
+                condition,
+                returning,
+            } if returning.is_empty() => {
+                let row = self.row();
+                let mut assignments = vec![];
+                for update in updates {
+                    if let SqlExpression::Assignment(column, expr) = update {
+                        let value = self.sql(process, expr);
+                        assignments.push(format!("!.{} = {value}", column.name));
+                    }
+                }
+                let updated = format!("[{row} EXCEPT {}]", assignments.join(", "));
+                let updated = match condition {
+                    Some(condition) => format!(
+                        "IF {} THEN {updated} ELSE {row}",
+                        self.sql(process, condition)
+                    ),
+                    None => updated,
+                };
+                self.depth -= 1;
+                Some((
+                    relation,
+                    format!("{{{updated} : {row} \\in tables.{}}}", relation.name),
+                ))
+            }
+            SqlExpression::Delete {
+                relation,
+                condition,
+            } => {
+                let row = self.row();
+                let kept = match condition {
+                    Some(condition) => format!("~{}", self.sql(process, condition)),
+                    None => "FALSE".to_string(),
+                };
+                self.depth -= 1;
+                Some((
+                    relation,
+                    format!("{{{row} \\in tables.{} : {kept}}}", relation.name),
+                ))
+            }
+            _ => None,
+        };
+        match rows {
+            Some((relation, rows)) => {
+                step.conjuncts.push(format!(
+                    "tables' = [tables EXCEPT !.{} = {rows}]",
+                    relation.name
+                ));
+                step.changed.insert("tables".to_string());
+            }
+            None => step.conjuncts.push(format!("TRUE \\* unsupported: {sql}")),
+        }
+    }
+
+    // Rows of nested selects are bound to distinct names
+    fn row(&mut self) -> String {
+        let row = match self.depth {
+            0 => "r".to_string(),
+            depth => format!("r{depth}"),
+        };
+        self.depth += 1;
+        row
+    }
+
+    fn current_row(&self) -> String {
+        match self.depth {
+            0 | 1 => "r".to_string(),
+            depth => format!("r{}", depth - 1),
+        }
+    }
+
+    fn expression(&mut self, process: Option<usize>, expr: &Expression) -> String {
+        match expr {
+            Expression::Sql(SqlExpression::Select { .. }) => {
+                format!("Rows({})", self.set(process, expr))
+            }
+            Expression::Sql(sql) => format!("Null (* unsupported: {sql} *)"),
+            Expression::Binary {
+                left,
+                operator: Operator::Included,
+                right,
+            } => format!(
+                "({} \\in {})",
+                self.expression(process, left),
+                self.set(process, right)
+            ),
+            Expression::Binary {
+                left,
+                operator,
+                right,
+            } => {
+                let operator = match operator {
+                    Operator::Add => "+",
+                    Operator::Subtract => "-",
+                    Operator::Multiply => "*",
+                    Operator::Divide => "\\div",
+                    Operator::Rem => "%",
+                    Operator::Equal => "=",
+                    Operator::NotEqual => "#",
+                    Operator::Less => "<",
+                    Operator::LessEqual => "<=",
+                    Operator::Greater => ">",
+                    Operator::GreaterEqual => ">=",
+                    Operator::Included => "\\in",
+                    Operator::And => "/\\",
+                    Operator::Or => "\\/",
+                };
+                format!(
+                    "({} {operator} {})",
+                    self.expression(process, left),
+                    self.expression(process, right)
+                )
+            }
+            Expression::Member { call_site, member } => match member.name.as_str() {
+                "committed" | "aborted" => format!(
+                    "({} = \"{}\")",
+                    self.expression(process, call_site),
+                    member.name
+                ),
+                _ => format!("FALSE (* unsupported: {expr} *)"),
+            },
+            Expression::Assignment(_, _) => format!("Null (* unsupported: {expr} *)"),
+            Expression::Var(variable) => self.var(process, &variable.name),
+            Expression::Integer(i) => i.to_string(),
+            Expression::String(s) => format!("\"{}\"", s.trim_matches('\'')),
+            Expression::Null => "Null".to_string(),
+            Expression::Set(members) => {
+                let members: Vec<_> = members
+                    .iter()
+                    .map(|m| self.expression(process, m))
+                    .collect();
+                format!("{{{}}}", members.join(", "))
+            }
+            Expression::Tuple(members) => {
+                let members: Vec<_> = members
+                    .iter()
+                    .map(|m| self.expression(process, m))
+                    .collect();
+                format!("<<{}>>", members.join(", "))
+            }
+            Expression::Scalar(expr) => self.expression(process, expr),
+            Expression::ToSet(expr) => self.set(process, expr),
+            Expression::Forall(variable, domain, body)
+            | Expression::Exists(variable, domain, body) => {
+                let quantifier = match expr {
+                    Expression::Forall(..) => "\\A",
+                    _ => "\\E",
+                };
+                let domain = self.set(process, domain);
+                self.bound.push(variable.name.clone());
+                let body = self.expression(process, body);
+                self.bound.pop();
+                format!("({quantifier} {} \\in {domain} : {body})", variable.name)
+            }
+        }
+    }
+
+    // Selects evaluate to the set of their rows
+    fn set(&mut self, process: Option<usize>, expr: &Expression) -> String {
+        match expr {
+            Expression::Sql(select) => self
+                .select(process, select)
+                .unwrap_or_else(|| format!("{{}} (* unsupported: {select} *)")),
+            Expression::ToSet(expr) => self.set(process, expr),
+            expr => self.expression(process, expr),
+        }
+    }
+
+    fn select(&mut self, process: Option<usize>, select: &SqlExpression) -> Option<String> {
+        let SqlExpression::Select {
+            columns,
+            from,
+            join_table: None,
+            condition,
+            group_by,
+            having: None,
+            limit: None,
+            offset: None,
+            ..
+        } = select
+        else {
+            return None;
+        };
+        if !group_by.is_empty() {
+            return None;
+        }
+        let row = self.row();
+        let rows = match condition {
+            Some(condition) => format!(
+                "{{{row} \\in tables.{} : {}}}",
+                from.name,
+                self.sql(process, condition)
+            ),
+            None => format!("tables.{}", from.name),
+        };
+        let field = |item: &Item| match item {
+            Item::Wildcard => None,
+            Item::Column(column) => Some(format!(
+                "{row}.{}",
+                column.rsplit('.').next().unwrap_or(column)
+            )),
+        };
+        let set = match columns.as_slice() {
+            [SelectItem::Column(Item::Wildcard)] => Some(rows),
+            [SelectItem::Column(item)] => {
+                field(item).map(|field| format!("{{{field} : {row} \\in {rows}}}"))
+            }
+            [SelectItem::Count(Item::Wildcard)] => Some(format!("{{Cardinality({rows})}}")),
+            [SelectItem::Count(item)] => field(item)
+                .map(|field| format!("{{Cardinality({{{row} \\in {rows} : {field} # Null}})}}")),
+            items if items.iter().all(|i| matches!(i, SelectItem::Column(_))) => {
+                let fields: Option<Vec<_>> = items
+                    .iter()
+                    .map(|item| match item {
+                        SelectItem::Column(item) => field(item),
+                        _ => None,
+                    })
+                    .collect();
+                fields.map(|fields| format!("{{<<{}>> : {row} \\in {rows}}}", fields.join(", ")))
+            }
+            _ => None,
+        };
+        self.depth -= 1;
+        set
+    }
+
+    fn sql(&mut self, process: Option<usize>, sql: &SqlExpression) -> String {
+        match sql {
+            SqlExpression::Binary {
+                left,
+                operator,
+                right,
+            } => {
+                let operator = match operator {
+                    SqlOperator::Add => "+",
+                    SqlOperator::Subtract => "-",
+                    SqlOperator::Multiply => "*",
+                    SqlOperator::Divide => "\\div",
+                    SqlOperator::Rem => "%",
+                    SqlOperator::Equal => "=",
+                    SqlOperator::NotEqual => "#",
+                    SqlOperator::Less => "<",
+                    SqlOperator::LessEqual => "<=",
+                    SqlOperator::Greater => ">",
+                    SqlOperator::GreaterEqual => ">=",
+                    SqlOperator::In => "\\in",
+                    SqlOperator::NotIn => "\\notin",
+                    SqlOperator::Is => "=",
+                    SqlOperator::IsNot => "#",
+                    SqlOperator::And => "/\\",
+                    SqlOperator::Or => "\\/",
+                    _ => return format!("FALSE (* unsupported: {sql} *)"),
+                };
+                format!(
+                    "({} {operator} {})",
+                    self.sql(process, left),
+                    self.sql(process, right)
+                )
+            }
+            SqlExpression::Not(expr) => format!("~{}", self.sql(process, expr)),
+            SqlExpression::Subquery(select) => self
+                .select(process, select)
+                .unwrap_or_else(|| format!("{{}} (* unsupported: {select} *)")),
+            SqlExpression::Tuple(members) => {
+                let members: Vec<_> = members.iter().map(|m| self.sql(process, m)).collect();
+                format!("<<{}>>", members.join(", "))
+            }
+            SqlExpression::Set(members) => {
+                let members: Vec<_> = members.iter().map(|m| self.sql(process, m)).collect();
+                format!("{{{}}}", members.join(", "))
+            }
+            SqlExpression::Var(column) => format!(
+                "{}.{}",
+                self.current_row(),
+                column.name.rsplit('.').next().unwrap_or(&column.name)
+            ),
+            SqlExpression::UpVariable(variable) => self.var(process, &variable.name),
+            SqlExpression::Integer(i) => i.to_string(),
+            SqlExpression::String(s) => format!("\"{}\"", s.trim_matches('\'')),
+            SqlExpression::Null => "Null".to_string(),
+            SqlExpression::Bool(true) => "TRUE".to_string(),
+            SqlExpression::Bool(false) => "FALSE".to_string(),
+            sql => format!("Null (* unsupported: {sql} *)"),
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use std::collections::HashSet;
+
+use crate::parser::{Location, Mets, Positions, Statement};
+use crate::reduction::variables;
+use crate::scanner::Position;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValidationError {
+    UndeclaredVariable(String, Position),
+    // Path and source of the included file declaring the statement
+    Included(String, String, Box<ValidationError>),
+}
+
+// A process only reads the globals and its own locals, properties read the locals of every process
+pub fn validate(mets: &Mets) -> Result<(), ValidationError> {
+    let (_, mut shared) = variables(mets.globals.iter().chain(&mets.init));
+    shared.extend(mets.constants.iter().map(|(v, _)| v.name.clone()));
+
+    let constants: Vec<_> = mets
+        .constants
+        .iter()
+        .map(|(_, expr)| Statement::Expression(expr.clone()))
+        .collect();
+    let positions = &mets.positions;
+    check_declared(
+        positions,
+        constants
+            .iter()
+            .zip(&positions.constants)
+            .chain(mets.globals.iter().zip(&positions.globals))
+            .chain(mets.init.iter().zip(&positions.init)),
+        &shared,
+    )?;
+
+    for (process, locations) in mets.processes.iter().zip(&positions.processes) {
+        let (_, mut declared) = variables(process);
+        declared.extend(shared.iter().cloned());
+        check_declared(&mets.positions, process.iter().zip(locations), &declared)?;
+    }
+
+    let (_, mut declared) = variables(mets.processes.iter().flatten().chain(&mets.properties));
+    declared.extend(shared);
+    check_declared(
+        positions,
+        mets.properties.iter().zip(&positions.properties),
+        &declared,
+    )
+}
+
+fn check_declared<'a>(
+    positions: &Positions,
+    statements: impl Iterator<Item = (&'a Statement, &'a Location)>,
+    declared: &HashSet<String>,
+) -> Result<(), ValidationError> {
+    for (statement, location) in statements {
+        let (used, _) = variables([statement]);
+        let mut undeclared: Vec<_> = used.difference(declared).collect();
+        undeclared.sort();
+        if let Some(name) = undeclared.first() {
+            let error =
+                ValidationError::UndeclaredVariable(name.to_string(), location.position.clone());
+            return Err(match location.file {
+                Some(file) => {
+                    let (path, source) = positions.files[file].clone();
+                    ValidationError::Included(path, source, Box::new(error))
+                }
+                None => error,
+            });
+        }
+    }
+    Ok(())
+}
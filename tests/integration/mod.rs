@@ -1,4 +1,4 @@
-fn make_path(module: &str, name: &str) -> (String, String) {
+fn make_path(module: &str, name: &str) -> (String, String, String) {
     let mut path = std::path::PathBuf::from("");
     for part in module.split("::").collect::<Vec<_>>() {
         path.push(part);
@@ -6,23 +6,30 @@ fn make_path(module: &str, name: &str) -> (String, String) {
     path.push(name);
     let mut mets = path.clone();
     let mut expected = path.clone();
+    let mut args = path.clone();
     mets.set_extension("mets");
     expected.set_extension("expected");
+    args.set_extension("args");
     (
         mets.to_str().unwrap().to_string(),
         expected.to_str().unwrap().to_string(),
+        args.to_str().unwrap().to_string(),
     )
 }
 
 fn test_file(module: &str, name: &str) {
-    let (mets, expected) = make_path(module, name);
-    std::fs::File::open(&mets).unwrap_or_else(|_| panic!("No file {mets}"));
-    let x = std::process::Command::new("cargo")
-        .arg("run")
-        .arg(mets)
-        .output()
-        .expect("failed to execute process");
-    let output = String::from_utf8(x.stdout).expect("no stdout");
+    let (mets, expected, args) = make_path(module, name);
+    let source = std::fs::read_to_string(&mets).unwrap_or_else(|_| panic!("No file {mets}"));
+    let args = std::fs::read_to_string(&args).unwrap_or_default();
+    let (_, options) = entremets::parse_args(args.split_whitespace().map(String::from))
+        .unwrap_or_else(|err| panic!("invalid args for {name}: {err}"));
+    let output = match entremets::compile_file(&source, &mets) {
+        Ok(model) => match entremets::check(&model, &options) {
+            Ok(report) => entremets::render(&model, &report, &options),
+            Err(err) => err.report(&mets, &source),
+        },
+        Err(err) => err.report(&mets, &source),
+    };
 
     let expected_output =
         std::fs::read_to_string(&expected).unwrap_or_else(|_| panic!("no file {expected}"));
@@ -34,6 +41,135 @@ fn test_file(module: &str, name: &str) {
     );
 }
 
+#[test]
+fn check_model_api() {
+    let source = "init do\n    `insert into users (id) values (1)`\nend\n\nproperty always(`select count(*) from users` = 1)\n";
+    let report = entremets::check_model(source).unwrap();
+    assert!(report.violation.is_none());
+    assert_eq!(entremets::Completeness::Complete, report.completeness);
+
+    let source = source.replace("= 1)", "= 0)");
+    let report = entremets::check_model(&source).unwrap();
+    assert!(report.violation.is_some());
+
+    let source =
+        std::fs::read_to_string("tests/integration/validation/undeclared_variable.mets").unwrap();
+    assert!(matches!(
+        entremets::check_model(&source),
+        Err(entremets::CheckError::Validation(_))
+    ));
+}
+
+#[test]
+fn report_stats() {
+    let source =
+        std::fs::read_to_string("tests/integration/read_committed/lost_update.mets").unwrap();
+    let model = entremets::compile(&source).unwrap();
+    let options = entremets::Options::default();
+    let report = entremets::check(&model, &options).unwrap();
+    assert!(report.duration > std::time::Duration::ZERO);
+    assert!(report.states_per_second() > 0.0);
+    assert!(report.visited_states >= report.states_explored);
+    let summary = entremets::render(&model, &report, &options);
+    assert!(summary.contains(&format!(
+        "\nStates explored: {}\nElapsed: ",
+        report.states_explored
+    )));
+}
+
+#[test]
+fn report_progress() {
+    let source =
+        std::fs::read_to_string("tests/integration/read_committed/lost_update.mets").unwrap();
+    let model = entremets::compile(&source).unwrap();
+    let mut progress = vec![];
+    let report =
+        entremets::check_with_progress(&model, &entremets::Options::default(), |depth, states| {
+            progress.push((depth, states))
+        })
+        .unwrap();
+    assert_eq!(Some(&(1, 0)), progress.first());
+    assert!(progress
+        .windows(2)
+        .all(|pair| pair[1].0 == pair[0].0 + 1 && pair[1].1 >= pair[0].1));
+    assert!(progress.last().unwrap().1 <= report.states_explored);
+}
+
+#[test]
+fn interactive_trace() {
+    let source =
+        std::fs::read_to_string("tests/integration/read_committed/lost_update.mets").unwrap();
+    let model = entremets::compile(&source).unwrap();
+    let report = entremets::check(&model, &entremets::Options::default()).unwrap();
+    let mut output = vec![];
+    entremets::interactive(
+        &model,
+        &report,
+        "n\nn\nn\np\np\np\np\nx\nq\nn\n".as_bytes(),
+        &mut output,
+    )
+    .unwrap();
+    let expected = "Initial state
+    users: {age: 10, id: 1}
+> Step 1/8: Process 0: begin read committed (tx1)
+    users: {age: 10, id: 1}
+    Process 0 locals: tx1: running transaction
+> Step 2/8: Process 0: t1_age := select age from users where id = 1
+    users: {age: 10, id: 1}
+    Process 0 locals: t1_age: 10, tx1: running transaction
+> Step 3/8: Process 0: update users set age := $t1_age + 1 where id = 1
+    users: {age: 10, id: 1}
+    Process 0 pending: update users {age: 11, id: 1}
+    Process 0 locals: t1_age: 10, tx1: running transaction
+> Step 2/8: Process 0: t1_age := select age from users where id = 1
+    users: {age: 10, id: 1}
+    Process 0 locals: t1_age: 10, tx1: running transaction
+> Step 1/8: Process 0: begin read committed (tx1)
+    users: {age: 10, id: 1}
+    Process 0 locals: tx1: running transaction
+> Initial state
+    users: {age: 10, id: 1}
+> Start of the counter example
+Initial state
+    users: {age: 10, id: 1}
+> Unknown command, use n (next), p (previous) or q (quit)
+Initial state
+    users: {age: 10, id: 1}
+> ";
+    assert_eq!(expected, String::from_utf8(output).unwrap());
+}
+
+#[test]
+fn dot_graph() {
+    let source = "init do\n    `insert into counters (n) values (0)`\nend\n\nprocess do\n    `update counters set n := n + 1`\nend\n\nprocess do\n    `update counters set n := n + 2`\nend\n";
+    let model = entremets::compile(source).unwrap();
+    let options = entremets::Options {
+        format: entremets::Format::Dot,
+        ..Default::default()
+    };
+    let report = entremets::check(&model, &options).unwrap();
+    let output = entremets::render(&model, &report, &options);
+
+    assert!(output.starts_with("digraph {"));
+    assert!(output.ends_with('}'));
+    let edges = output.lines().filter(|l| l.contains(" -> ")).count();
+    let nodes = output
+        .lines()
+        .filter(|l| l.contains("[label=") && !l.contains(" -> "))
+        .count();
+    assert_eq!(4, nodes);
+    assert_eq!(4, edges);
+}
+
+#[test]
+fn tla_translation() {
+    let source =
+        std::fs::read_to_string("tests/integration/read_committed/lost_update.mets").unwrap();
+    let model = entremets::compile(&source).unwrap();
+    let expected = std::fs::read_to_string("tests/integration/tla/lost_update.tla").unwrap();
+    assert_eq!(expected.trim_end(), entremets::tla(&model, "lost_update"));
+}
+
 macro_rules! entremets_test {
     ($($name:ident),*) => {
     $(
@@ -46,12 +182,103 @@ macro_rules! entremets_test {
     }
 }
 
+mod large_table {
+    entremets_test! {
+        many_states
+    }
+}
+
+mod parallel {
+    entremets_test! {
+        parallel,
+        sequential
+    }
+}
+
+mod partial_order {
+    entremets_test! {
+        disjoint_tables,
+        reduced
+    }
+
+    // The reduction reports the same violation as the full search
+    fn same_verdict(name: &str) {
+        let path = format!("tests/integration/partial_order/{name}.mets");
+        let source = std::fs::read_to_string(&path).unwrap();
+        let model = entremets::compile_file(&source, &path).unwrap();
+        let verdict = |options: entremets::Options| {
+            let report = entremets::check(&model, &options).unwrap();
+            let output = entremets::render(&model, &report, &options);
+            output.lines().next().unwrap().to_string()
+        };
+        let full = verdict(entremets::Options::default());
+        let reduced = verdict(entremets::Options {
+            partial_order: true,
+            ..entremets::Options::default()
+        });
+        assert!(
+            full.starts_with("Following property was violated"),
+            "{full}"
+        );
+        assert_eq!(full, reduced);
+    }
+
+    #[test]
+    fn always_verdict() {
+        same_verdict("always_hidden");
+    }
+
+    #[test]
+    fn never_verdict() {
+        same_verdict("never_hidden");
+    }
+
+    #[test]
+    fn eventually_verdict() {
+        same_verdict("eventually_hidden");
+    }
+
+    #[test]
+    fn leads_to_verdict() {
+        same_verdict("leads_to_hidden");
+    }
+}
+
+mod replication {
+    entremets_test! {
+        atomic_increments,
+        lost_update,
+        own_locals,
+        symmetric_failures,
+        symmetry
+    }
+}
+
+mod read_only {
+    entremets_test! {
+        accidental_update,
+        report
+    }
+}
+
+mod primary_key {
+    entremets_test! {
+        concurrent_updates,
+        duplicate_key,
+        null_key
+    }
+}
+
 mod read_committed {
     entremets_test! {
         aborted_reads,
         circular_information_flow,
+        deadlock_chain,
+        deadlock_expected,
+        deadlock_free,
         deadlocks,
         duplicate_creation,
+        duplicate_creation_do_nothing,
         intermediate_reads,
         lost_update,
         multiple_columns_unique_contraint,
@@ -61,8 +288,13 @@ mod read_committed {
         optimistic_lost_update,
         optimistic_lost_update_aborted,
         predicate_many_preceders,
+        resumed_increment,
+        resumed_update_condition,
+        shared_wait,
+        stale_read,
         unique_contraint,
-        write_cycles
+        write_cycles,
+        write_cycle_deadlock
     }
 }
 
@@ -70,27 +302,96 @@ mod comparisons {
     entremets_test! {
         comparison,
         between,
+        between_null,
+        between_strings,
+        between_violation,
+        case_sensitive_names,
+        in_null,
+        in_subquery,
+        in_tuple_subquery,
+        not_in_null,
+        or_not,
         order_by,
-        precedence
+        order_by_error,
+        precedence,
+        uppercase_keywords,
+        where_operators
     }
 }
 
 mod count {
     entremets_test! {
+        count_nulls,
         count_star,
-        item_not_in_aggregate
+        item_not_in_aggregate,
+        item_not_in_group_by
     }
 }
 
 mod string {
     entremets_test! {
+        escapes,
+        like,
+        like_integer,
+        ordering,
+        ordering_mixed_types,
         string
     }
 }
 
+mod aggregate {
+    entremets_test! {
+        aggregates,
+        column_not_in_aggregate,
+        sum_of_strings
+    }
+}
+
+mod union {
+    entremets_test! {
+        union_all,
+        union_arity,
+        union_in
+    }
+}
+
+mod check {
+    entremets_test! {
+        negative_balance
+    }
+}
+
+mod create_table {
+    entremets_test! {
+        aborted_types,
+        default_values,
+        inferred_types,
+        type_mismatch,
+        wildcard
+    }
+}
+
+mod truncate {
+    entremets_test! {
+        pending_truncate,
+        waits_for_writers,
+        keeps_schema,
+        drop_table,
+        referenced_table
+    }
+}
+
+mod update {
+    entremets_test! {
+        swap_columns,
+        reads_previous_value
+    }
+}
+
 mod delete {
     entremets_test! {
         delete,
+        delete_count,
         delete_visibility_in_transaction,
         delete_with_unicity,
         delete_with_update_lock
@@ -99,13 +400,26 @@ mod delete {
 
 mod limit {
     entremets_test! {
-        limit
+        limit,
+        negative_limit
+    }
+}
+
+mod global {
+    entremets_test! {
+        missing_turn,
+        peterson
     }
 }
 
 mod group {
     entremets_test! {
-        group
+        column_not_in_group_by,
+        duplicate_emails,
+        group,
+        group_by,
+        group_by_multiple_columns,
+        having
     }
 }
 
@@ -114,8 +428,561 @@ mod foreign_key {
         delete_cascade,
         non_existing_foreign_key,
         concurrent_deletion_and_insert,
+        consistent_deletion_and_insert,
+        customer_orders_cascade,
+        key_share_deadlock,
         not_working_insert,
         not_working_update,
+        orphan_insert,
+        restricted_deletion,
+        self_referencing_cascade,
+        self_referencing_chain,
         working_update
     }
 }
+
+mod constant {
+    entremets_test! {
+        constant,
+        constant_assignment
+    }
+}
+
+mod include {
+    entremets_test! {
+        shared_init,
+        include_cycle,
+        included_error,
+        missing_include,
+        duplicate_init,
+        diamond_include,
+        included_runtime_error
+    }
+}
+
+mod validation {
+    entremets_test! {
+        invalid_token,
+        no_replica,
+        runtime_property_error,
+        runtime_statement_error,
+        unknown_column,
+        unknown_inserted_column,
+        unknown_table
+    }
+}
+
+mod returning {
+    entremets_test! {
+        lost_update_returning,
+        returning
+    }
+}
+
+mod unique_violation {
+    entremets_test! {
+        abort_policy,
+        error_policy,
+        same_statement,
+        skip_block
+    }
+}
+
+mod set {
+    entremets_test! {
+        scalar_membership,
+        scalar_query_rows,
+        select_membership,
+        single_row_membership,
+        single_row_variable
+    }
+}
+
+mod null {
+    entremets_test! {
+        is_null,
+        null_comparisons,
+        unique_nulls
+    }
+}
+
+mod savepoint {
+    entremets_test! {
+        outside_transaction,
+        partial_rollback,
+        released_lock
+    }
+}
+
+mod serializable {
+    entremets_test! {
+        lost_update,
+        phantom,
+        phantom_write_skew,
+        predicate_lock,
+        serialization_failure,
+        snapshot_serialization_failure,
+        write_cycles,
+        write_skew
+    }
+}
+
+mod snapshot_isolation {
+    entremets_test! {
+        anti_dependency_cycle,
+        lost_update,
+        snapshot_before_write,
+        write_skew
+    }
+}
+
+mod subquery {
+    entremets_test! {
+        scalar_subquery,
+        too_many_rows
+    }
+}
+
+mod awaiting {
+    entremets_test! {
+        local_condition,
+        never_satisfied,
+        non_empty_queue
+    }
+}
+
+mod bounds {
+    entremets_test! {
+        deep_violation,
+        large_model,
+        max_depth,
+        max_states,
+        shallow_violation,
+        timeout,
+        unreached_bound,
+        violation_within_max_states
+    }
+}
+
+mod join {
+    entremets_test! {
+        ambiguous_column,
+        inner_join,
+        join_for_update
+    }
+}
+
+mod insert {
+    entremets_test! {
+        insert_select
+    }
+}
+
+mod upsert {
+    entremets_test! {
+        counter_increment,
+        lost_update
+    }
+}
+
+mod integer {
+    entremets_test! {
+        division_by_zero,
+        integer_out_of_range,
+        large_integers,
+        negative_integers,
+        overflow,
+        sql_division_by_zero,
+        sql_overflow,
+        sum_overflow
+    }
+}
+
+mod for_share {
+    entremets_test! {
+        shared_readers,
+        writer_blocks
+    }
+}
+
+mod lock_table {
+    entremets_test! {
+        blocked_writer,
+        serialized_updates
+    }
+}
+
+mod locks {
+    entremets_test! {
+        handoff
+    }
+}
+
+mod mixed_isolation {
+    entremets_test! {
+        lost_update
+    }
+}
+
+mod nowait {
+    entremets_test! {
+        blocking,
+        nowait
+    }
+}
+
+mod skip_locked {
+    entremets_test! {
+        disjoint_batches
+    }
+}
+
+mod report {
+    entremets_test! {
+        history,
+        json,
+        verbose
+    }
+}
+
+mod quantifier {
+    entremets_test! {
+        exists_account,
+        forall_negative_balance
+    }
+}
+
+mod leads_to {
+    entremets_test! {
+        acknowledged_request,
+        unacknowledged_request
+    }
+}
+
+mod else_if {
+    entremets_test! {
+        every_arm,
+        no_else,
+        second_arm
+    }
+}
+
+mod while_loop {
+    entremets_test! {
+        counter,
+        retry_until_commit,
+        without_retry
+    }
+}
+
+mod either {
+    entremets_test! {
+        aborted_branch,
+        commit_or_abort,
+        shortest_trace
+    }
+}
+
+mod fairness {
+    entremets_test! {
+        fair_producer_consumer,
+        unfair_producer_consumer
+    }
+}
+
+mod exit_code {
+    fn exit_code(model: &str) -> Option<i32> {
+        std::process::Command::new(env!("CARGO_BIN_EXE_entremets"))
+            .arg(model)
+            .output()
+            .expect("failed to execute process")
+            .status
+            .code()
+    }
+
+    #[test]
+    fn no_violation() {
+        assert_eq!(Some(0), exit_code("tests/integration/string/string.mets"));
+    }
+
+    #[test]
+    fn violation() {
+        assert_eq!(
+            Some(1),
+            exit_code("tests/integration/read_committed/lost_update.mets")
+        );
+    }
+
+    #[test]
+    fn error() {
+        assert_eq!(
+            Some(2),
+            exit_code("tests/integration/join/ambiguous_column.mets")
+        );
+    }
+}
+
+mod cli {
+    use entremets::{parse_command, Command, Emit, Format, Options};
+    use std::io::Write;
+    use std::process::{Command as Process, Stdio};
+
+    fn parse(args: &str) -> Result<Command, String> {
+        parse_command(args.split_whitespace().map(String::from))
+    }
+
+    #[test]
+    fn no_args() {
+        assert_eq!(Ok(Command::Check(None, Options::default())), parse(""));
+    }
+
+    #[test]
+    fn check_subcommand() {
+        assert_eq!(
+            Ok(Command::Check(
+                Some("model.mets".to_string()),
+                Options::default()
+            )),
+            parse("check model.mets")
+        );
+        assert_eq!(parse("model.mets"), parse("check model.mets"));
+    }
+
+    #[test]
+    fn flags() {
+        let options = Options {
+            format: Format::Json,
+            verbose: true,
+            max_states: Some(10),
+            jobs: 4,
+            ..Default::default()
+        };
+        assert_eq!(
+            Ok(Command::Check(Some("-".to_string()), options.clone())),
+            parse("check --format json - --verbose --max-states 10 --jobs 4")
+        );
+        assert_eq!(
+            Ok(Command::Check(None, options)),
+            parse("--jobs 4 --verbose --max-states 10 --format json")
+        );
+    }
+
+    #[test]
+    fn help() {
+        assert_eq!(Ok(Command::Help), parse("--help"));
+        assert_eq!(Ok(Command::Help), parse("check model.mets -h"));
+        assert_eq!(Ok(Command::Help), parse("help"));
+    }
+
+    #[test]
+    fn interactive() {
+        let options = Options {
+            interactive: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            Ok(Command::Check(Some("model.mets".to_string()), options)),
+            parse("check model.mets --interactive")
+        );
+    }
+
+    #[test]
+    fn emit() {
+        assert_eq!(
+            Ok(Command::Emit(Some("model.mets".to_string()), Emit::Tla)),
+            parse("--emit tla model.mets")
+        );
+        assert_eq!(
+            Ok(Command::Emit(Some("-".to_string()), Emit::Tla)),
+            parse("check - --emit tla")
+        );
+        assert_eq!(
+            Err("Expected tla after --emit".to_string()),
+            parse("model.mets --emit")
+        );
+    }
+
+    #[test]
+    fn check_only() {
+        assert_eq!(
+            Ok(Command::Validate(Some("model.mets".to_string()))),
+            parse("--check-only model.mets")
+        );
+    }
+
+    fn check_only_output(model: &str) -> (Option<i32>, String) {
+        let output = Process::new(env!("CARGO_BIN_EXE_entremets"))
+            .args([model, "--check-only"])
+            .output()
+            .expect("failed to execute process");
+        (
+            output.status.code(),
+            String::from_utf8(output.stdout).unwrap(),
+        )
+    }
+
+    #[test]
+    fn check_only_valid_model() {
+        let (code, output) = check_only_output("tests/integration/while_loop/without_retry.mets");
+        assert_eq!(Some(0), code);
+        assert_eq!(
+            "No error found in tests/integration/while_loop/without_retry.mets\n",
+            output
+        );
+    }
+
+    #[test]
+    fn check_only_other_process_local() {
+        let (code, output) =
+            check_only_output("tests/integration/validation/other_process_local.mets");
+        assert_eq!(Some(2), code);
+        assert!(
+            output.starts_with(
+                "Error at tests/integration/validation/other_process_local.mets:6:5: Undeclared variable ready"
+            ),
+            "{output}"
+        );
+    }
+
+    #[test]
+    fn check_only_included_undeclared() {
+        let (code, output) =
+            check_only_output("tests/integration/include/included_undeclared.mets");
+        assert_eq!(Some(2), code);
+        assert!(
+            output.starts_with(
+                "Error at tests/integration/include/shared/undeclared.mets:2:5: Undeclared variable y"
+            ),
+            "{output}"
+        );
+    }
+
+    #[test]
+    fn check_only_unknown_inserted_column() {
+        let (code, output) =
+            check_only_output("tests/integration/validation/unknown_inserted_column.mets");
+        assert_eq!(Some(2), code);
+        assert!(
+            output.starts_with(
+                "Error at tests/integration/validation/unknown_inserted_column.mets:6:45: Unknown column agee in table users"
+            ),
+            "{output}"
+        );
+    }
+
+    #[test]
+    fn check_only_undeclared_variable() {
+        let (code, output) =
+            check_only_output("tests/integration/validation/undeclared_variable.mets");
+        assert_eq!(Some(2), code);
+        assert!(
+            output.starts_with(
+                "Error at tests/integration/validation/undeclared_variable.mets:8:9: Undeclared variable balanec"
+            ),
+            "{output}"
+        );
+    }
+
+    #[test]
+    fn check_validates_model() {
+        let output = Process::new(env!("CARGO_BIN_EXE_entremets"))
+            .args(["tests/integration/validation/undeclared_variable.mets"])
+            .output()
+            .expect("failed to execute process");
+        assert_eq!(Some(2), output.status.code());
+        let output = String::from_utf8(output.stdout).unwrap();
+        assert!(
+            output.starts_with(
+                "Error at tests/integration/validation/undeclared_variable.mets:8:9: Undeclared variable balanec"
+            ),
+            "{output}"
+        );
+    }
+
+    #[test]
+    fn invalid_args() {
+        assert_eq!(
+            Err("Unknown option --verbos".to_string()),
+            parse("check model.mets --verbos")
+        );
+        assert_eq!(
+            Err("Unexpected argument other.mets".to_string()),
+            parse("model.mets other.mets")
+        );
+        assert_eq!(
+            Err("Expected a positive number after --max-depth".to_string()),
+            parse("--max-depth")
+        );
+    }
+
+    #[test]
+    fn usage() {
+        let output = Process::new(env!("CARGO_BIN_EXE_entremets"))
+            .arg("--help")
+            .output()
+            .expect("failed to execute process");
+        assert_eq!(Some(0), output.status.code());
+        assert!(String::from_utf8(output.stdout)
+            .unwrap()
+            .starts_with("Usage: entremets"));
+    }
+
+    fn pipe(model: &str) -> (Option<i32>, String) {
+        let mut child = Process::new(env!("CARGO_BIN_EXE_entremets"))
+            .arg("-")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("failed to execute process");
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(model.as_bytes())
+            .unwrap();
+        let output = child.wait_with_output().unwrap();
+        (
+            output.status.code(),
+            String::from_utf8(output.stdout).unwrap(),
+        )
+    }
+
+    #[test]
+    fn stdin() {
+        let (code, output) =
+            pipe(&std::fs::read_to_string("tests/integration/string/string.mets").unwrap());
+        assert_eq!(Some(0), code);
+        assert!(output.starts_with("No counter example found"), "{output}");
+    }
+
+    #[test]
+    fn stdin_parse_error() {
+        let (code, output) =
+            pipe("process do\n    `update user set age := 11 where id = 1`\nend\n");
+        assert_eq!(Some(2), code);
+        assert!(
+            output.starts_with("Error at <stdin>:2:13: Unknown table user"),
+            "{output}"
+        );
+    }
+
+    #[test]
+    fn empty_stdin() {
+        assert_eq!(
+            (Some(2), "No model read from stdin\n".to_string()),
+            pipe("")
+        );
+    }
+
+    #[test]
+    fn missing_file() {
+        let output = Process::new(env!("CARGO_BIN_EXE_entremets"))
+            .arg("tests/integration/missing.mets")
+            .output()
+            .expect("failed to execute process");
+        assert_eq!(Some(2), output.status.code());
+        assert!(String::from_utf8(output.stdout)
+            .unwrap()
+            .starts_with("Could not open tests/integration/missing.mets"));
+    }
+}
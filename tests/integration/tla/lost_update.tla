@@ -0,0 +1,97 @@
+---- MODULE lost_update ----
+EXTENDS Integers, FiniteSets
+
+\* Statements are atomic steps, isolation levels and rollbacks are not modeled
+
+Null == "null"
+Rows(S) == IF Cardinality(S) = 1 THEN CHOOSE v \in S : TRUE ELSE S
+
+VARIABLES ipc, pc, tables, p0_t1_age, p0_tx1, p1_t2_age, p1_tx2
+
+vars == <<ipc, pc, tables, p0_t1_age, p0_tx1, p1_t2_age, p1_tx2>>
+
+Init ==
+    /\ ipc = 0
+    /\ pc = [i \in 0..1 |-> 0]
+    /\ tables = [users |-> {}]
+    /\ p0_t1_age = Null
+    /\ p0_tx1 = Null
+    /\ p1_t2_age = Null
+    /\ p1_tx2 = Null
+
+Setup_0 ==
+    /\ ipc = 0
+    /\ tables' = [tables EXCEPT !.users = tables.users \union {[id |-> 1, age |-> 10]}]
+    /\ ipc' = 1
+    /\ UNCHANGED <<pc, p0_t1_age, p0_tx1, p1_t2_age, p1_tx2>>
+
+P0_0 ==
+    /\ ipc = 1
+    /\ pc[0] = 0
+    /\ p0_tx1' = "running"
+    /\ pc' = [pc EXCEPT ![0] = 1]
+    /\ UNCHANGED <<ipc, tables, p0_t1_age, p1_t2_age, p1_tx2>>
+
+P0_1 ==
+    /\ ipc = 1
+    /\ pc[0] = 1
+    /\ p0_t1_age' = Rows({r.age : r \in {r \in tables.users : (r.id = 1)}})
+    /\ pc' = [pc EXCEPT ![0] = 2]
+    /\ UNCHANGED <<ipc, tables, p0_tx1, p1_t2_age, p1_tx2>>
+
+P0_2 ==
+    /\ ipc = 1
+    /\ pc[0] = 2
+    /\ tables' = [tables EXCEPT !.users = {IF (r.id = 1) THEN [r EXCEPT !.age = (p0_t1_age + 1)] ELSE r : r \in tables.users}]
+    /\ pc' = [pc EXCEPT ![0] = 3]
+    /\ UNCHANGED <<ipc, p0_t1_age, p0_tx1, p1_t2_age, p1_tx2>>
+
+P0_3 ==
+    /\ ipc = 1
+    /\ pc[0] = 3
+    /\ p0_tx1' = "committed"
+    /\ pc' = [pc EXCEPT ![0] = 4]
+    /\ UNCHANGED <<ipc, tables, p0_t1_age, p1_t2_age, p1_tx2>>
+
+P0 == P0_0 \/ P0_1 \/ P0_2 \/ P0_3
+
+P1_0 ==
+    /\ ipc = 1
+    /\ pc[1] = 0
+    /\ p1_tx2' = "running"
+    /\ pc' = [pc EXCEPT ![1] = 1]
+    /\ UNCHANGED <<ipc, tables, p0_t1_age, p0_tx1, p1_t2_age>>
+
+P1_1 ==
+    /\ ipc = 1
+    /\ pc[1] = 1
+    /\ p1_t2_age' = Rows({r.age : r \in {r \in tables.users : (r.id = 1)}})
+    /\ pc' = [pc EXCEPT ![1] = 2]
+    /\ UNCHANGED <<ipc, tables, p0_t1_age, p0_tx1, p1_tx2>>
+
+P1_2 ==
+    /\ ipc = 1
+    /\ pc[1] = 2
+    /\ tables' = [tables EXCEPT !.users = {IF (r.id = 1) THEN [r EXCEPT !.age = (p1_t2_age * 2)] ELSE r : r \in tables.users}]
+    /\ pc' = [pc EXCEPT ![1] = 3]
+    /\ UNCHANGED <<ipc, p0_t1_age, p0_tx1, p1_t2_age, p1_tx2>>
+
+P1_3 ==
+    /\ ipc = 1
+    /\ pc[1] = 3
+    /\ p1_tx2' = "committed"
+    /\ pc' = [pc EXCEPT ![1] = 4]
+    /\ UNCHANGED <<ipc, tables, p0_t1_age, p0_tx1, p1_t2_age>>
+
+P1 == P1_0 \/ P1_1 \/ P1_2 \/ P1_3
+
+Done == ipc = 1 /\ pc[0] = 4 /\ pc[1] = 4
+
+Terminating == Done /\ UNCHANGED vars
+
+Next == Setup_0 \/ P0 \/ P1 \/ Terminating
+
+Spec == Init /\ [][Next]_vars /\ WF_vars(Next)
+
+Property0 == <>(ipc = 1 /\ (Rows({r.age : r \in {r \in tables.users : (r.id = 1)}}) \in {21, 22}))
+====